@@ -19,14 +19,17 @@ use dragonfly_client_core::Result;
 use local_ip_address::{local_ip, local_ipv6};
 use regex::Regex;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 use tokio::fs;
-use tracing::info;
-use validator::Validate;
+use tracing::{info, warn};
+use tracing_subscriber::filter::Directive;
+use validator::{Validate, ValidationError};
 
 // NAME is the name of dfdaemon.
 pub const NAME: &str = "dfdaemon";
@@ -127,18 +130,90 @@ fn default_download_max_schedule_count() -> u32 {
     5
 }
 
+// default_scheduler_hashring_key_length is the default length of the task id prefix
+// used as the key of the scheduler hashring.
+#[inline]
+fn default_scheduler_hashring_key_length() -> usize {
+    5
+}
+
+// default_manager_announce_interval is the default interval to announce seed peer to the manager.
+#[inline]
+fn default_manager_announce_interval() -> Duration {
+    Duration::from_secs(300)
+}
+
 // default_scheduler_announce_interval is the default interval to announce peer to the scheduler.
 #[inline]
 fn default_scheduler_announce_interval() -> Duration {
     Duration::from_secs(300)
 }
 
+// MIN_SCHEDULER_ANNOUNCE_INTERVAL is the min interval to announce peer to the scheduler, the
+// smaller interval is clamped to avoid flooding the scheduler with announcements.
+const MIN_SCHEDULER_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+// default_scheduler_announce_interval_jitter is the default jitter ratio of the announce interval.
+#[inline]
+fn default_scheduler_announce_interval_jitter() -> f64 {
+    0.1
+}
+
 // default_scheduler_schedule_timeout is the default timeout for scheduling.
 #[inline]
 fn default_scheduler_schedule_timeout() -> Duration {
     Duration::from_secs(30)
 }
 
+// default_scheduler_announce_retry_max_retries is the default max retries to announce host to the scheduler.
+#[inline]
+fn default_scheduler_announce_retry_max_retries() -> u32 {
+    5
+}
+
+// default_scheduler_announce_retry_initial_backoff is the default initial backoff to retry announcing host.
+#[inline]
+fn default_scheduler_announce_retry_initial_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+// default_scheduler_announce_retry_max_backoff is the default max backoff to retry announcing host.
+#[inline]
+fn default_scheduler_announce_retry_max_backoff() -> Duration {
+    Duration::from_secs(30)
+}
+
+// default_tracing_sample_ratio is the default ratio of the sampled traces.
+#[inline]
+fn default_tracing_sample_ratio() -> f64 {
+    1.0
+}
+
+// default_tracing_metrics_interval is the default interval of pushing the metrics to the
+// otlp endpoint.
+#[inline]
+fn default_tracing_metrics_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+// default_tracing_redacted_headers is the default headers whose values are redacted
+// in the logs and spans.
+#[inline]
+fn default_tracing_redacted_headers() -> Vec<String> {
+    vec![
+        "authorization".to_string(),
+        "proxy-authorization".to_string(),
+        "cookie".to_string(),
+        "x-amz-security-token".to_string(),
+    ]
+}
+
+// default_shutdown_announce_timeout is the default timeout to deregister from the manager and scheduler.
+#[inline]
+fn default_shutdown_announce_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
 // default_dynconfig_refresh_interval is the default interval to refresh dynamic configuration from manager.
 #[inline]
 fn default_dynconfig_refresh_interval() -> Duration {
@@ -151,6 +226,24 @@ fn default_storage_keep() -> bool {
     false
 }
 
+// default_storage_verify_on_upload is the default of verifying the piece digest when uploading the piece.
+#[inline]
+fn default_storage_verify_on_upload() -> bool {
+    false
+}
+
+// default_storage_preallocate is the default of preallocating the task content file.
+#[inline]
+fn default_storage_preallocate() -> bool {
+    false
+}
+
+// default_storage_digest_algorithm is the default algorithm of the piece digest.
+#[inline]
+fn default_storage_digest_algorithm() -> String {
+    "blake3".to_string()
+}
+
 // default_storage_write_buffer_size is the default buffer size for writing piece to disk, default is 128KB.
 #[inline]
 fn default_storage_write_buffer_size() -> usize {
@@ -163,6 +256,34 @@ fn default_storage_read_buffer_size() -> usize {
     128 * 1024
 }
 
+// default_storage_metadata_block_cache_size is the default block cache size of the metadata
+// store, default is 32MiB.
+#[inline]
+fn default_storage_metadata_block_cache_size() -> usize {
+    32 * 1024 * 1024
+}
+
+// default_storage_metadata_write_buffer_size is the default memtable size of the metadata
+// store, default is 16MiB.
+#[inline]
+fn default_storage_metadata_write_buffer_size() -> usize {
+    16 * 1024 * 1024
+}
+
+// default_storage_metadata_max_write_buffer_number is the default max number of the memtables
+// of the metadata store.
+#[inline]
+fn default_storage_metadata_max_write_buffer_number() -> i32 {
+    6
+}
+
+// default_storage_metadata_max_background_jobs is the default max number of the concurrent
+// flushes and compactions of the metadata store, default is the number of cpus.
+#[inline]
+fn default_storage_metadata_max_background_jobs() -> i32 {
+    num_cpus::get() as i32
+}
+
 // default_seed_peer_cluster_id is the default cluster id of seed peer.
 #[inline]
 fn default_seed_peer_cluster_id() -> u64 {
@@ -424,6 +545,11 @@ pub struct UploadServer {
     // port is the port to the grpc server.
     #[serde(default = "default_upload_grpc_server_port")]
     pub port: u16,
+
+    // download_port is the port announced to the manager and scheduler for downloading
+    // pieces, it is used when the pieces are downloaded behind a proxy with a different
+    // port. If it is not set, the port of the grpc server is announced.
+    pub download_port: Option<u16>,
 }
 
 // UploadServer implements Default.
@@ -432,6 +558,7 @@ impl Default for UploadServer {
         UploadServer {
             ip: None,
             port: default_upload_grpc_server_port(),
+            download_port: None,
         }
     }
 }
@@ -459,12 +586,71 @@ impl Default for Upload {
 }
 
 // Manager is the manager configuration for dfdaemon.
-#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Manager {
     // addrs is manager addresses.
     #[validate(length(min = 1))]
     pub addrs: Vec<String>,
+
+    // announce_interval is the interval to announce seed peer to the manager, the
+    // seed peer is registered again to keep it alive after the manager restarts.
+    #[serde(
+        default = "default_manager_announce_interval",
+        with = "humantime_serde"
+    )]
+    pub announce_interval: Duration,
+
+    // announce_retry is the retry configuration for announcing seed peer to the manager.
+    #[validate]
+    pub announce_retry: AnnounceRetry,
+}
+
+// Manager implements Default.
+impl Default for Manager {
+    fn default() -> Self {
+        Manager {
+            addrs: Vec::new(),
+            announce_interval: default_manager_announce_interval(),
+            announce_retry: AnnounceRetry::default(),
+        }
+    }
+}
+
+// AnnounceRetry is the retry configuration for announcing to the manager or scheduler.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AnnounceRetry {
+    // max_retries is the max retries to announce when the announcement fails,
+    // 0 means the announcement is not retried until the next interval.
+    #[serde(default = "default_scheduler_announce_retry_max_retries")]
+    pub max_retries: u32,
+
+    // initial_backoff is the backoff of the first retry, the backoff is doubled
+    // on every retry with jitter.
+    #[serde(
+        default = "default_scheduler_announce_retry_initial_backoff",
+        with = "humantime_serde"
+    )]
+    pub initial_backoff: Duration,
+
+    // max_backoff is the max backoff of the retry.
+    #[serde(
+        default = "default_scheduler_announce_retry_max_backoff",
+        with = "humantime_serde"
+    )]
+    pub max_backoff: Duration,
+}
+
+// AnnounceRetry implements Default.
+impl Default for AnnounceRetry {
+    fn default() -> Self {
+        AnnounceRetry {
+            max_retries: default_scheduler_announce_retry_max_retries(),
+            initial_backoff: default_scheduler_announce_retry_initial_backoff(),
+            max_backoff: default_scheduler_announce_retry_max_backoff(),
+        }
+    }
 }
 
 // Scheduler is the scheduler configuration for dfdaemon.
@@ -474,12 +660,25 @@ pub struct Scheduler {
     // announce_interval is the interval to announce peer to the scheduler.
     // Announcer will provide the scheduler with peer information for scheduling,
     // peer information includes cpu, memory, etc.
+    // The zero interval is invalid, and the interval less than 1s is clamped to 1s.
     #[serde(
         default = "default_scheduler_announce_interval",
         with = "humantime_serde"
     )]
+    #[validate(custom = "validate_scheduler_announce_interval")]
     pub announce_interval: Duration,
 
+    // announce_interval_jitter is the jitter ratio of the announce interval, every
+    // interval is randomized in [interval * (1 - jitter), interval * (1 + jitter)]
+    // to avoid the peers announcing to the scheduler at the same time.
+    #[serde(default = "default_scheduler_announce_interval_jitter")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub announce_interval_jitter: f64,
+
+    // announce_retry is the retry configuration for announcing host to the scheduler.
+    #[validate]
+    pub announce_retry: AnnounceRetry,
+
     // schedule_timeout is the timeout for scheduling. If the scheduling timeout, dfdaemon will back-to-source
     // download if enable_back_to_source is true, otherwise dfdaemon will return download failed.
     #[serde(
@@ -492,6 +691,41 @@ pub struct Scheduler {
     #[serde(default = "default_download_max_schedule_count")]
     #[validate(range(min = 1))]
     pub max_schedule_count: u32,
+
+    // hashring_key_length is the length of the task id prefix used as the key of the scheduler
+    // hashring, it must match the partitioning of the schedulers, each task is scheduled by
+    // the scheduler picked by the prefix of the task id.
+    #[serde(default = "default_scheduler_hashring_key_length")]
+    #[validate(range(min = 1))]
+    pub hashring_key_length: usize,
+}
+
+// validate_scheduler_announce_interval validates the interval to announce peer to the scheduler,
+// the zero interval makes the announcer busy-spin.
+fn validate_scheduler_announce_interval(
+    interval: &Duration,
+) -> std::result::Result<(), ValidationError> {
+    if interval.is_zero() {
+        let mut err = ValidationError::new("invalid_announce_interval");
+        err.message = Some(Cow::from("announceInterval must be greater than 0"));
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// clamp_scheduler_announce_interval clamps the non-zero interval to announce peer to the
+// scheduler to the min interval, the zero interval is left to be rejected by the validation.
+fn clamp_scheduler_announce_interval(interval: Duration) -> Duration {
+    if !interval.is_zero() && interval < MIN_SCHEDULER_ANNOUNCE_INTERVAL {
+        warn!(
+            "scheduler announce interval {:?} is too small, clamp it to {:?}",
+            interval, MIN_SCHEDULER_ANNOUNCE_INTERVAL
+        );
+        return MIN_SCHEDULER_ANNOUNCE_INTERVAL;
+    }
+
+    interval
 }
 
 // Scheduler implements Default.
@@ -499,8 +733,11 @@ impl Default for Scheduler {
     fn default() -> Self {
         Scheduler {
             announce_interval: default_scheduler_announce_interval(),
+            announce_interval_jitter: default_scheduler_announce_interval_jitter(),
+            announce_retry: AnnounceRetry::default(),
             schedule_timeout: default_scheduler_schedule_timeout(),
             max_schedule_count: default_download_max_schedule_count(),
+            hashring_key_length: default_scheduler_hashring_key_length(),
         }
     }
 }
@@ -607,13 +844,219 @@ pub struct Storage {
     #[serde(default = "default_storage_keep")]
     pub keep: bool,
 
-    // write_buffer_size is the buffer size for writing piece to disk, default is 4KB.
-    #[serde(default = "default_storage_write_buffer_size")]
+    // write_buffer_size is the buffer size for writing piece to disk, default is 128KiB. The
+    // size is the number of bytes or the string with the binary suffix, e.g. "4MiB".
+    #[serde(
+        default = "default_storage_write_buffer_size",
+        deserialize_with = "deserialize_byte_size"
+    )]
+    #[validate(range(min = 4096, max = 67108864))]
     pub write_buffer_size: usize,
 
-    // read_buffer_size is the buffer size for reading piece from disk, default is 4KB.
-    #[serde(default = "default_storage_read_buffer_size")]
+    // read_buffer_size is the buffer size for reading piece from disk, default is 128KiB. The
+    // size is the number of bytes or the string with the binary suffix, e.g. "4MiB".
+    #[serde(
+        default = "default_storage_read_buffer_size",
+        deserialize_with = "deserialize_byte_size"
+    )]
+    #[validate(range(min = 4096, max = 67108864))]
     pub read_buffer_size: usize,
+
+    // verify_on_upload indicates whether verify the digest of the piece while uploading the
+    // piece to other peers, the corrupted piece is invalidated and not uploaded anymore.
+    #[serde(default = "default_storage_verify_on_upload")]
+    pub verify_on_upload: bool,
+
+    // digest_algorithm is the algorithm to calculate the digest of the downloaded piece, the
+    // options are sha256, sha512, crc32c and blake3. The digest of the piece downloaded from
+    // the remote peer is verified by the algorithm declared in the digest, so the peers with
+    // different algorithms interoperate.
+    #[serde(default = "default_storage_digest_algorithm")]
+    #[validate(custom = "validate_storage_digest_algorithm")]
+    pub digest_algorithm: String,
+
+    // preallocate indicates whether preallocate the task content file to the content length
+    // as soon as it is known, then the file is not fragmented by the piece writes and the
+    // lack of disk space is reported before downloading.
+    #[serde(default = "default_storage_preallocate")]
+    pub preallocate: bool,
+
+    // check_on_startup is the mode of checking the integrity of the tasks when the dfdaemon
+    // starts, the inconsistent tasks and pieces are deleted.
+    #[serde(default)]
+    pub check_on_startup: StorageCheck,
+
+    // metadata is the tuning configuration of the metadata store.
+    #[validate]
+    pub metadata: StorageMetadata,
+}
+
+// StorageMetadata is the tuning configuration of the rocksdb of the metadata store.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StorageMetadata {
+    // block_cache_size is the size of the lru cache of the uncompressed blocks, default is
+    // 32MiB. The size is the number of bytes or the string with the binary suffix, e.g. "1GiB".
+    #[serde(
+        default = "default_storage_metadata_block_cache_size",
+        deserialize_with = "deserialize_byte_size"
+    )]
+    pub block_cache_size: usize,
+
+    // write_buffer_size is the size of a memtable before it is flushed, default is 16MiB. The
+    // size is the number of bytes or the string with the binary suffix, e.g. "64MiB".
+    #[serde(
+        default = "default_storage_metadata_write_buffer_size",
+        deserialize_with = "deserialize_byte_size"
+    )]
+    #[validate(range(min = 1048576))]
+    pub write_buffer_size: usize,
+
+    // max_write_buffer_number is the max number of the memtables, the writes are stalled
+    // if all the memtables are full and waiting for the flushes.
+    #[serde(default = "default_storage_metadata_max_write_buffer_number")]
+    #[validate(range(min = 2))]
+    pub max_write_buffer_number: i32,
+
+    // max_background_jobs is the max number of the concurrent flushes and compactions,
+    // default is the number of cpus.
+    #[serde(default = "default_storage_metadata_max_background_jobs")]
+    #[validate(range(min = 1))]
+    pub max_background_jobs: i32,
+
+    // compression_type is the compression of the sst files, the first two levels are not
+    // compressed to reduce the cost of the frequent compactions.
+    #[serde(default)]
+    pub compression_type: StorageMetadataCompression,
+
+    // bloom_filter indicates whether enable the bloom filters of the sst files, then the
+    // lookups of the missing keys skip reading the blocks.
+    #[serde(default)]
+    pub bloom_filter: bool,
+}
+
+// StorageMetadata implements Default.
+impl Default for StorageMetadata {
+    fn default() -> Self {
+        StorageMetadata {
+            block_cache_size: default_storage_metadata_block_cache_size(),
+            write_buffer_size: default_storage_metadata_write_buffer_size(),
+            max_write_buffer_number: default_storage_metadata_max_write_buffer_number(),
+            max_background_jobs: default_storage_metadata_max_background_jobs(),
+            compression_type: StorageMetadataCompression::default(),
+            bloom_filter: false,
+        }
+    }
+}
+
+// StorageMetadataCompression is the compression type of the metadata store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum StorageMetadataCompression {
+    // None disables the compression.
+    #[serde(rename = "none")]
+    None,
+
+    // Snappy compresses by snappy.
+    #[serde(rename = "snappy")]
+    Snappy,
+
+    // Lz4 compresses by lz4.
+    #[default]
+    #[serde(rename = "lz4")]
+    Lz4,
+
+    // Zstd compresses by zstd.
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+// StorageCheck is the mode of checking the integrity of the storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum StorageCheck {
+    // Off disables the check.
+    #[default]
+    #[serde(rename = "off")]
+    Off,
+
+    // Quick compares the sizes of the task contents with the metadata, the piece
+    // contents are not read.
+    #[serde(rename = "quick")]
+    Quick,
+
+    // Full verifies the digests of the pieces in addition to the quick check.
+    #[serde(rename = "full")]
+    Full,
+}
+
+// StorageCheck implements Display.
+impl fmt::Display for StorageCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageCheck::Off => write!(f, "off"),
+            StorageCheck::Quick => write!(f, "quick"),
+            StorageCheck::Full => write!(f, "full"),
+        }
+    }
+}
+
+// deserialize_byte_size deserializes the byte size from the number of bytes or the string
+// with the binary suffix, e.g. 4096, "4KiB", "128KB" and "64M".
+fn deserialize_byte_size<'de, D>(deserializer: D) -> std::result::Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ByteSize {
+        Number(usize),
+        String(String),
+    }
+
+    match ByteSize::deserialize(deserializer)? {
+        ByteSize::Number(size) => Ok(size),
+        ByteSize::String(size) => parse_byte_size(&size).map_err(serde::de::Error::custom),
+    }
+}
+
+// parse_byte_size parses the byte size with the binary suffix, the suffixes are case
+// insensitive and the decimal suffixes are the same as the binary suffixes, e.g. "1KB"
+// is 1024 bytes.
+fn parse_byte_size(size: &str) -> std::result::Result<usize, String> {
+    let size = size.trim();
+    let (number, suffix) = size.split_at(
+        size.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(size.len()),
+    );
+
+    let number = number
+        .parse::<usize>()
+        .map_err(|_| format!("invalid byte size: {}", size))?;
+    let unit = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid byte size suffix: {}", size)),
+    };
+
+    number
+        .checked_mul(unit)
+        .ok_or_else(|| format!("byte size overflows: {}", size))
+}
+
+// validate_storage_digest_algorithm validates the algorithm of the piece digest.
+fn validate_storage_digest_algorithm(algorithm: &str) -> std::result::Result<(), ValidationError> {
+    match algorithm {
+        "sha256" | "sha512" | "crc32c" | "blake3" => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("invalid_digest_algorithm");
+            err.message = Some(Cow::from(format!(
+                "invalid digest algorithm: {}",
+                algorithm
+            )));
+            Err(err)
+        }
+    }
 }
 
 // Storage implements Default.
@@ -624,6 +1067,11 @@ impl Default for Storage {
             keep: default_storage_keep(),
             write_buffer_size: default_storage_write_buffer_size(),
             read_buffer_size: default_storage_read_buffer_size(),
+            verify_on_upload: default_storage_verify_on_upload(),
+            digest_algorithm: default_storage_digest_algorithm(),
+            preallocate: default_storage_preallocate(),
+            check_on_startup: StorageCheck::default(),
+            metadata: StorageMetadata::default(),
         }
     }
 }
@@ -631,6 +1079,7 @@ impl Default for Storage {
 // Policy is the policy configuration for gc.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
+#[validate(schema(function = "validate_gc_policy"))]
 pub struct Policy {
     // task_ttl is the ttl of the task.
     #[serde(
@@ -664,6 +1113,21 @@ impl Default for Policy {
     }
 }
 
+// validate_gc_policy validates the gc policy, the low threshold of the disk usage must be
+// less than the high threshold, otherwise gc can never stop evicting.
+fn validate_gc_policy(policy: &Policy) -> std::result::Result<(), ValidationError> {
+    if policy.dist_low_threshold_percent >= policy.dist_high_threshold_percent {
+        let mut err = ValidationError::new("invalid_dist_threshold_percent");
+        err.message = Some(Cow::from(format!(
+            "distLowThresholdPercent {} must be less than distHighThresholdPercent {}",
+            policy.dist_low_threshold_percent, policy.dist_high_threshold_percent
+        )));
+        return Err(err);
+    }
+
+    Ok(())
+}
+
 // GC is the gc configuration for dfdaemon.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -673,6 +1137,7 @@ pub struct GC {
     pub interval: Duration,
 
     // policy is the gc policy.
+    #[validate]
     pub policy: Policy,
 }
 
@@ -944,12 +1409,201 @@ pub struct Stats {
     pub server: StatsServer,
 }
 
-// Tracing is the tracing configuration for dfdaemon.
+// Log is the log configuration for dfdaemon.
 #[derive(Debug, Clone, Default, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
+pub struct Log {
+    // filters is the filter directives appended to the log level, e.g. h2=warn and
+    // dragonfly_client_storage=debug, the later directive overrides the former one for
+    // the same target. The filters are ignored if the RUST_LOG environment variable is set.
+    #[validate(custom = "validate_log_filters")]
+    pub filters: Vec<String>,
+}
+
+// validate_log_filters validates the filter directives of the log.
+fn validate_log_filters(filters: &Vec<String>) -> std::result::Result<(), ValidationError> {
+    for filter in filters {
+        if filter.parse::<Directive>().is_err() {
+            let mut err = ValidationError::new("invalid_log_filter");
+            err.message = Some(Cow::from(format!("invalid log filter: {}", filter)));
+            err.add_param(Cow::from("filter"), filter);
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+// Tracing is the tracing configuration for dfdaemon.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[validate(schema(function = "validate_tracing"))]
 pub struct Tracing {
-    // addr is the address to report tracing log.
+    // addr is the address to report tracing log, it is the address of the jaeger agent, or
+    // the endpoint of the otlp collector if the protocol is set, e.g. collector:4317 or
+    // https://collector:4318.
     pub addr: Option<String>,
+
+    // protocol is the protocol of the otlp exporter, grpc or http. The spans are reported
+    // to the jaeger agent of the addr if it is not set.
+    pub protocol: Option<TracingProtocol>,
+
+    // headers is the headers of the otlp exporter, e.g. the authorization header of the
+    // collector, it overrides the headers of the OTEL_EXPORTER_OTLP_HEADERS.
+    pub headers: HashMap<String, String>,
+
+    // sample_ratio is the ratio of the sampled traces in [0, 1], the tracing
+    // is disabled if the ratio is 0.
+    #[serde(default = "default_tracing_sample_ratio")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub sample_ratio: f64,
+
+    // syslog_addr is the address of the syslog to send the logs, e.g. /dev/log,
+    // udp://127.0.0.1:514 or tcp://127.0.0.1:601, it requires the syslog feature.
+    pub syslog_addr: Option<String>,
+
+    // redacted_headers is the headers whose values are redacted in the logs and spans,
+    // the header names are case-insensitive.
+    #[serde(default = "default_tracing_redacted_headers")]
+    pub redacted_headers: Vec<String>,
+
+    // resource_attributes is the additional attributes of the traced resource, e.g.
+    // environment, cluster and region, it overrides the built-in attributes with the same key.
+    pub resource_attributes: HashMap<String, String>,
+
+    // tls is the tls configuration of the otlp exporter, it is applied if the otlp
+    // endpoint is https.
+    #[validate]
+    pub tls: TracingTLS,
+
+    // metrics_otlp indicates whether push the metrics to the otlp endpoint by grpc, the
+    // endpoint is the OTEL_EXPORTER_OTLP_METRICS_ENDPOINT, the metrics_endpoint, or the
+    // endpoint of the spans if the spans are exported by grpc. The headers and the tls are
+    // shared with the spans.
+    pub metrics_otlp: bool,
+
+    // metrics_endpoint is the grpc endpoint of the otlp collector to push the metrics, e.g.
+    // collector:4317, it is required to push the metrics to the collector if the spans are
+    // exported by http.
+    pub metrics_endpoint: Option<String>,
+
+    // metrics_interval is the interval of pushing the metrics to the otlp endpoint.
+    #[serde(default = "default_tracing_metrics_interval", with = "humantime_serde")]
+    pub metrics_interval: Duration,
+}
+
+// Tracing implements Default.
+impl Default for Tracing {
+    fn default() -> Self {
+        Tracing {
+            addr: None,
+            protocol: None,
+            headers: HashMap::new(),
+            sample_ratio: default_tracing_sample_ratio(),
+            syslog_addr: None,
+            redacted_headers: default_tracing_redacted_headers(),
+            resource_attributes: HashMap::new(),
+            tls: TracingTLS::default(),
+            metrics_otlp: false,
+            metrics_endpoint: None,
+            metrics_interval: default_tracing_metrics_interval(),
+        }
+    }
+}
+
+// validate_tracing validates the tracing configuration, the grpc exporter always verifies
+// the cert of the collector.
+fn validate_tracing(tracing: &Tracing) -> std::result::Result<(), ValidationError> {
+    if tracing.tls.insecure_skip_verify && tracing.protocol == Some(TracingProtocol::Grpc) {
+        let mut err = ValidationError::new("invalid_tracing");
+        err.message = Some(Cow::from(
+            "insecure skip verify is not supported by the grpc protocol",
+        ));
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// TracingProtocol is the protocol of the otlp exporter for tracing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum TracingProtocol {
+    // Grpc exports by grpc.
+    #[default]
+    #[serde(rename = "grpc")]
+    Grpc,
+
+    // Http exports by http with the protobuf encoding.
+    #[serde(rename = "http", alias = "http/protobuf")]
+    Http,
+}
+
+// TracingProtocol implements FromStr, the value is the protocol of the
+// OTEL_EXPORTER_OTLP_PROTOCOL, e.g. grpc or http/protobuf.
+impl FromStr for TracingProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "grpc" => Ok(TracingProtocol::Grpc),
+            "http" | "http/protobuf" => Ok(TracingProtocol::Http),
+            _ => Err(format!("invalid tracing protocol {}", s)),
+        }
+    }
+}
+
+// TracingTLS is the tls configuration of the otlp exporter for tracing.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[validate(schema(function = "validate_tracing_tls"))]
+pub struct TracingTLS {
+    // ca_cert is the CA cert path with PEM format to verify the collector, the system
+    // root certs are used if it is not set.
+    pub ca_cert: Option<PathBuf>,
+
+    // cert is the client cert path with PEM format for the mutual tls, it requires the key.
+    pub cert: Option<PathBuf>,
+
+    // key is the client key path with PEM format for the mutual tls, it requires the cert.
+    pub key: Option<PathBuf>,
+
+    // insecure_skip_verify skips verifying the cert of the collector, it is only supported
+    // by the http protocol and must not be used in production.
+    pub insecure_skip_verify: bool,
+}
+
+// validate_tracing_tls validates the tls configuration of the otlp exporter, the client
+// cert and key must be set together.
+fn validate_tracing_tls(tls: &TracingTLS) -> std::result::Result<(), ValidationError> {
+    if tls.cert.is_some() != tls.key.is_some() {
+        let mut err = ValidationError::new("invalid_tracing_tls");
+        err.message = Some(Cow::from("cert and key must be set together"));
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// Shutdown is the shutdown configuration for dfdaemon.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Shutdown {
+    // announce_timeout is the timeout to deregister from the manager and scheduler
+    // when shutting down, it avoids delaying the shutdown if they are unreachable.
+    #[serde(
+        default = "default_shutdown_announce_timeout",
+        with = "humantime_serde"
+    )]
+    pub announce_timeout: Duration,
+}
+
+// Shutdown implements Default.
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            announce_timeout: default_shutdown_announce_timeout(),
+        }
+    }
 }
 
 // Config is the configuration for dfdaemon.
@@ -1016,6 +1670,10 @@ pub struct Config {
     #[validate]
     pub stats: Stats,
 
+    // log is the log configuration for dfdaemon.
+    #[validate]
+    pub log: Log,
+
     // tracing is the tracing configuration for dfdaemon.
     #[validate]
     pub tracing: Tracing,
@@ -1023,6 +1681,10 @@ pub struct Config {
     // network is the network configuration for dfdaemon.
     #[validate]
     pub network: Network,
+
+    // shutdown is the shutdown configuration for dfdaemon.
+    #[validate]
+    pub shutdown: Shutdown,
 }
 
 // Config implements the config operation of dfdaemon.
@@ -1044,6 +1706,10 @@ impl Config {
 
     // convert converts the configuration.
     fn convert(&mut self) {
+        // Convert scheduler announce interval.
+        self.scheduler.announce_interval =
+            clamp_scheduler_announce_interval(self.scheduler.announce_interval);
+
         // Convert advertise ip.
         if self.host.ip.is_none() {
             self.host.ip = if self.network.enable_ipv6 {
@@ -1053,6 +1719,25 @@ impl Config {
             }
         }
 
+        // Convert tracing resource attributes, the attributes of the host are added to the
+        // resource of the spans and the metrics, and the configured attributes override them.
+        let mut resource_attributes = HashMap::from([
+            ("host.name".to_string(), self.host.hostname.clone()),
+            (
+                "host.seed_peer".to_string(),
+                self.seed_peer.enable.to_string(),
+            ),
+        ]);
+        if let Some(ip) = self.host.ip {
+            resource_attributes.insert("host.ip".to_string(), ip.to_string());
+        }
+
+        if let Some(idc) = self.host.idc.as_ref() {
+            resource_attributes.insert("host.idc".to_string(), idc.clone());
+        }
+        resource_attributes.extend(std::mem::take(&mut self.tracing.resource_attributes));
+        self.tracing.resource_attributes = resource_attributes;
+
         // Convert upload grpc server listen ip.
         if self.upload.server.ip.is_none() {
             self.upload.server.ip = if self.network.enable_ipv6 {
@@ -1099,3 +1784,333 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_validate_scheduler_hashring_key_length() {
+        let scheduler: Scheduler = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(scheduler.hashring_key_length, 5);
+        assert!(scheduler.validate().is_ok());
+
+        let scheduler: Scheduler = serde_yaml::from_str("hashringKeyLength: 8").unwrap();
+        assert_eq!(scheduler.hashring_key_length, 8);
+        assert!(scheduler.validate().is_ok());
+
+        let scheduler: Scheduler = serde_yaml::from_str("hashringKeyLength: 0").unwrap();
+        assert!(scheduler.validate().is_err());
+    }
+
+    #[test]
+    fn should_parse_storage_buffer_sizes() {
+        let storage: Storage = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(storage.write_buffer_size, 128 * 1024);
+        assert_eq!(storage.read_buffer_size, 128 * 1024);
+        assert!(storage.validate().is_ok());
+
+        for (content, size) in [
+            ("65536", 64 * 1024),
+            ("4KiB", 4 * 1024),
+            ("'128 KB'", 128 * 1024),
+            ("4m", 4 * 1024 * 1024),
+            ("64MiB", 64 * 1024 * 1024),
+        ] {
+            let storage: Storage = serde_yaml::from_str(&format!(
+                "writeBufferSize: {}\nreadBufferSize: {}",
+                content, content
+            ))
+            .unwrap();
+            assert_eq!(storage.write_buffer_size, size);
+            assert_eq!(storage.read_buffer_size, size);
+            assert!(storage.validate().is_ok());
+        }
+
+        for content in ["foo", "4PiB", "-4KiB", "KiB"] {
+            assert!(
+                serde_yaml::from_str::<Storage>(&format!("writeBufferSize: {}", content)).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn should_deserialize_storage_metadata() {
+        let storage: Storage = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(storage.metadata.block_cache_size, 32 * 1024 * 1024);
+        assert_eq!(storage.metadata.write_buffer_size, 16 * 1024 * 1024);
+        assert_eq!(storage.metadata.max_write_buffer_number, 6);
+        assert_eq!(storage.metadata.max_background_jobs, num_cpus::get() as i32);
+        assert_eq!(
+            storage.metadata.compression_type,
+            StorageMetadataCompression::Lz4
+        );
+        assert!(!storage.metadata.bloom_filter);
+        assert!(storage.validate().is_ok());
+
+        let storage: Storage = serde_yaml::from_str(
+            r#"
+metadata:
+  blockCacheSize: 1GiB
+  writeBufferSize: 64MiB
+  maxWriteBufferNumber: 4
+  maxBackgroundJobs: 8
+  compressionType: zstd
+  bloomFilter: true
+"#,
+        )
+        .unwrap();
+        assert_eq!(storage.metadata.block_cache_size, 1024 * 1024 * 1024);
+        assert_eq!(storage.metadata.write_buffer_size, 64 * 1024 * 1024);
+        assert_eq!(storage.metadata.max_write_buffer_number, 4);
+        assert_eq!(storage.metadata.max_background_jobs, 8);
+        assert_eq!(
+            storage.metadata.compression_type,
+            StorageMetadataCompression::Zstd
+        );
+        assert!(storage.metadata.bloom_filter);
+        assert!(storage.validate().is_ok());
+
+        for content in [
+            "writeBufferSize: 4KiB",
+            "maxWriteBufferNumber: 1",
+            "maxBackgroundJobs: 0",
+        ] {
+            let storage: Storage =
+                serde_yaml::from_str(&format!("metadata:\n  {}", content)).unwrap();
+            assert!(storage.validate().is_err());
+        }
+
+        assert!(serde_yaml::from_str::<Storage>("metadata:\n  compressionType: gzip").is_err());
+    }
+
+    #[test]
+    fn should_validate_storage_buffer_sizes() {
+        for content in ["4095", "2KiB", "67108865", "128MiB"] {
+            let storage: Storage =
+                serde_yaml::from_str(&format!("writeBufferSize: {}", content)).unwrap();
+            assert!(storage.validate().is_err());
+
+            let storage: Storage =
+                serde_yaml::from_str(&format!("readBufferSize: {}", content)).unwrap();
+            assert!(storage.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn should_validate_scheduler_announce_interval() {
+        let scheduler: Scheduler = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(scheduler.announce_interval, Duration::from_secs(300));
+        assert!(scheduler.validate().is_ok());
+
+        // The zero interval is rejected.
+        let scheduler: Scheduler = serde_yaml::from_str("announceInterval: 0s").unwrap();
+        assert!(scheduler.validate().is_err());
+        assert_eq!(
+            clamp_scheduler_announce_interval(scheduler.announce_interval),
+            Duration::ZERO
+        );
+
+        // The interval less than 1s is clamped to 1s.
+        let scheduler: Scheduler = serde_yaml::from_str("announceInterval: 10ms").unwrap();
+        assert!(scheduler.validate().is_ok());
+        assert_eq!(
+            clamp_scheduler_announce_interval(scheduler.announce_interval),
+            Duration::from_secs(1)
+        );
+
+        let scheduler: Scheduler = serde_yaml::from_str("announceInterval: 1m").unwrap();
+        assert_eq!(
+            clamp_scheduler_announce_interval(scheduler.announce_interval),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn should_deserialize_storage_check_on_startup() {
+        let storage: Storage = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(storage.check_on_startup, StorageCheck::Off);
+
+        for (content, check) in [
+            ("off", StorageCheck::Off),
+            ("quick", StorageCheck::Quick),
+            ("full", StorageCheck::Full),
+        ] {
+            let storage: Storage =
+                serde_yaml::from_str(&format!("checkOnStartup: {}", content)).unwrap();
+            assert_eq!(storage.check_on_startup, check);
+            assert_eq!(check.to_string(), content);
+        }
+
+        assert!(serde_yaml::from_str::<Storage>("checkOnStartup: foo").is_err());
+    }
+
+    #[test]
+    fn should_deserialize_tracing_protocol() {
+        let tracing: Tracing = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(tracing.protocol, None);
+        assert!(tracing.headers.is_empty());
+
+        let tracing: Tracing = serde_yaml::from_str(
+            "addr: https://collector:4318\nprotocol: http\nheaders:\n  Authorization: Bearer foo",
+        )
+        .unwrap();
+        assert_eq!(tracing.protocol, Some(TracingProtocol::Http));
+        assert_eq!(
+            tracing.headers.get("Authorization"),
+            Some(&"Bearer foo".to_string())
+        );
+
+        let tracing: Tracing = serde_yaml::from_str("protocol: grpc").unwrap();
+        assert_eq!(tracing.protocol, Some(TracingProtocol::Grpc));
+        assert!(serde_yaml::from_str::<Tracing>("protocol: thrift").is_err());
+
+        assert_eq!(
+            TracingProtocol::from_str("http/protobuf").unwrap(),
+            TracingProtocol::Http
+        );
+        assert_eq!(
+            TracingProtocol::from_str("GRPC").unwrap(),
+            TracingProtocol::Grpc
+        );
+        assert!(TracingProtocol::from_str("http/json").is_err());
+    }
+
+    #[test]
+    fn should_convert_tracing_resource_attributes() {
+        let mut config: Config = serde_yaml::from_str(
+            "host:\n  hostname: foo\n  ip: 127.0.0.1\n  idc: bar\nseedPeer:\n  enable: true\ntracing:\n  metricsEndpoint: collector:4317\n  metricsInterval: 10s\n  resourceAttributes:\n    host.idc: baz\n    cluster: qux",
+        )
+        .unwrap();
+        config.convert();
+        assert_eq!(config.tracing.metrics_interval, Duration::from_secs(10));
+        assert_eq!(
+            config.tracing.metrics_endpoint,
+            Some("collector:4317".to_string())
+        );
+        assert_eq!(
+            config.tracing.resource_attributes,
+            HashMap::from([
+                ("host.name".to_string(), "foo".to_string()),
+                ("host.ip".to_string(), "127.0.0.1".to_string()),
+                ("host.idc".to_string(), "baz".to_string()),
+                ("host.seed_peer".to_string(), "true".to_string()),
+                ("cluster".to_string(), "qux".to_string()),
+            ])
+        );
+
+        let tracing: Tracing = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(tracing.metrics_interval, Duration::from_secs(30));
+        assert!(tracing.metrics_endpoint.is_none());
+    }
+
+    #[test]
+    fn should_validate_tracing_tls() {
+        let tracing: Tracing = serde_yaml::from_str("{}").unwrap();
+        assert!(tracing.tls.ca_cert.is_none());
+        assert!(tracing.validate().is_ok());
+
+        let tracing: Tracing = serde_yaml::from_str(
+            "tls:\n  caCert: /etc/ssl/ca.crt\n  cert: /etc/ssl/client.crt\n  key: /etc/ssl/client.key",
+        )
+        .unwrap();
+        assert_eq!(tracing.tls.ca_cert, Some(PathBuf::from("/etc/ssl/ca.crt")));
+        assert!(tracing.validate().is_ok());
+
+        // The client cert requires the key.
+        let tracing: Tracing = serde_yaml::from_str("tls:\n  cert: /etc/ssl/client.crt").unwrap();
+        assert!(tracing.validate().is_err());
+
+        // The insecure skip verify is not supported by the grpc protocol.
+        let tracing: Tracing =
+            serde_yaml::from_str("protocol: http\ntls:\n  insecureSkipVerify: true").unwrap();
+        assert!(tracing.tls.insecure_skip_verify);
+        assert!(tracing.validate().is_ok());
+
+        let tracing: Tracing =
+            serde_yaml::from_str("protocol: grpc\ntls:\n  insecureSkipVerify: true").unwrap();
+        assert!(tracing.validate().is_err());
+    }
+
+    #[test]
+    fn should_validate_gc_policy() {
+        let gc: GC = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(gc.policy.dist_high_threshold_percent, 80);
+        assert_eq!(gc.policy.dist_low_threshold_percent, 60);
+        assert!(gc.validate().is_ok());
+
+        for policy in [
+            "policy: {distHighThresholdPercent: 60, distLowThresholdPercent: 60}",
+            "policy: {distHighThresholdPercent: 50, distLowThresholdPercent: 60}",
+            "policy: {distHighThresholdPercent: 100}",
+        ] {
+            let gc: GC = serde_yaml::from_str(policy).unwrap();
+            assert!(gc.validate().is_err(), "{}", policy);
+        }
+    }
+
+    #[test]
+    fn should_validate_storage_digest_algorithm() {
+        let storage: Storage = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(storage.digest_algorithm, "blake3");
+        assert!(storage.validate().is_ok());
+
+        for algorithm in ["sha256", "sha512", "crc32c", "blake3"] {
+            let storage: Storage =
+                serde_yaml::from_str(&format!("digestAlgorithm: {}", algorithm)).unwrap();
+            assert!(storage.validate().is_ok(), "{}", algorithm);
+        }
+
+        let storage: Storage = serde_yaml::from_str("digestAlgorithm: md5").unwrap();
+        assert!(storage.validate().is_err());
+    }
+
+    #[test]
+    fn should_validate_tracing_sample_ratio() {
+        let tracing: Tracing = serde_yaml::from_str("addr: 127.0.0.1:6831").unwrap();
+        assert_eq!(tracing.sample_ratio, 1.0);
+        assert!(tracing.validate().is_ok());
+
+        for ratio in ["0", "0.5", "1.0"] {
+            let tracing: Tracing =
+                serde_yaml::from_str(&format!("sampleRatio: {}", ratio)).unwrap();
+            assert!(tracing.validate().is_ok());
+        }
+
+        for ratio in ["-0.1", "1.5"] {
+            let tracing: Tracing =
+                serde_yaml::from_str(&format!("sampleRatio: {}", ratio)).unwrap();
+            assert!(tracing.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn should_validate_log_filters() {
+        let log: Log =
+            serde_yaml::from_str("filters:\n  - h2=warn\n  - dragonfly_client_storage=debug")
+                .unwrap();
+        assert_eq!(
+            log.filters,
+            vec!["h2=warn", "dragonfly_client_storage=debug"]
+        );
+        assert!(log.validate().is_ok());
+
+        let log: Log = serde_yaml::from_str("filters:\n  - h2=warn\n  - h2=foo").unwrap();
+        let err = log.validate().unwrap_err().to_string();
+        assert!(err.contains("h2=foo"));
+        assert!(!err.contains("h2=warn"));
+    }
+
+    #[test]
+    fn should_deserialize_tracing_resource_attributes() {
+        let tracing: Tracing = serde_yaml::from_str("addr: 127.0.0.1:6831").unwrap();
+        assert!(tracing.resource_attributes.is_empty());
+        assert_eq!(tracing.redacted_headers, default_tracing_redacted_headers());
+
+        let tracing: Tracing =
+            serde_yaml::from_str("resourceAttributes:\n  cluster: foo\n  region: bar").unwrap();
+        assert_eq!(tracing.resource_attributes.len(), 2);
+        assert_eq!(tracing.resource_attributes["cluster"], "foo");
+        assert_eq!(tracing.resource_attributes["region"], "bar");
+    }
+}
@@ -16,13 +16,15 @@
 
 use crate::dfdaemon::default_proxy_server_port;
 use dragonfly_client_core::error::{ErrorType, OrErr};
-use dragonfly_client_core::Result;
+use dragonfly_client_core::{Error, Result};
 use serde::{ser::SerializeStruct, Deserialize, Serialize};
+use std::fmt;
 use std::fs;
 use std::net::Ipv4Addr;
 use std::path::PathBuf;
 use tracing::info;
-use validator::Validate;
+use url::Url;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 // NAME is the name of dfinit.
 pub const NAME: &str = "dfinit";
@@ -44,6 +46,13 @@ fn default_container_runtime_containerd_config_path() -> PathBuf {
     PathBuf::from("/etc/containerd/config.toml")
 }
 
+// default_container_runtime_containerd_certs_dir is the default directory of the containerd
+// registries configurations.
+#[inline]
+fn default_container_runtime_containerd_certs_dir() -> PathBuf {
+    PathBuf::from("/etc/containerd/certs.d")
+}
+
 // default_container_runtime_docker_config_path is the default docker configuration path.
 #[inline]
 fn default_container_runtime_docker_config_path() -> PathBuf {
@@ -56,6 +65,25 @@ fn default_container_runtime_crio_config_path() -> PathBuf {
     PathBuf::from("/etc/containers/registries.conf")
 }
 
+// default_container_runtime_apptainer_config_path is the default apptainer remote configuration path.
+#[inline]
+fn default_container_runtime_apptainer_config_path() -> PathBuf {
+    PathBuf::from("/usr/local/etc/apptainer/remote.yaml")
+}
+
+// default_container_runtime_apptainer_remote_name is the default name of the apptainer remote
+// of the dfdaemon's proxy.
+#[inline]
+fn default_container_runtime_apptainer_remote_name() -> String {
+    "dragonfly".to_string()
+}
+
+// default_container_runtime_crio_registry_insecure is the default insecure of the cri-o registry.
+#[inline]
+fn default_container_runtime_crio_registry_insecure() -> bool {
+    true
+}
+
 // default_container_runtime_crio_unqualified_search_registries is the default unqualified search registries of cri-o,
 // refer to https://github.com/containers/image/blob/main/docs/containers-registries.conf.5.md#global-settings.
 #[inline]
@@ -85,7 +113,7 @@ fn default_container_runtime_containerd_registry_capabilities() -> Vec<String> {
 }
 
 // Registry is the registry configuration for containerd.
-#[derive(Debug, Clone, Default, Validate, Deserialize, Serialize)]
+#[derive(Clone, Default, Validate, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ContainerdRegistry {
     // host_namespace is the location where container images and artifacts are sourced,
@@ -101,7 +129,71 @@ pub struct ContainerdRegistry {
     // capabilities is the list of capabilities in containerd configuration, refer to
     // https://github.com/containerd/containerd/blob/main/docs/hosts.md#capabilities-field.
     #[serde(default = "default_container_runtime_containerd_registry_capabilities")]
+    #[validate(custom = "validate_containerd_registry_capabilities")]
     pub capabilities: Vec<String>,
+
+    // skip_verify skips verifying the certificate of the registry, it is used for the
+    // insecure registries, refer to
+    // https://github.com/containerd/containerd/blob/main/docs/hosts.md#skip_verify-field.
+    pub skip_verify: Option<bool>,
+
+    // username is the username to authenticate to the registry, it is sent with the password
+    // in the Authorization header of the requests to the dfdaemon's proxy. The credentials
+    // are only supported in config_path mode, they are ignored with a warning in mirror mode.
+    pub username: Option<String>,
+
+    // password is the password to authenticate to the registry.
+    pub password: Option<String>,
+
+    // proxy_addr is the address of the dfdaemon's proxy for the registry, it overrides
+    // the address of the proxy configuration, e.g. the registry is pulled by another
+    // dfdaemon's proxy over a different network.
+    #[validate(custom = "validate_proxy_addr")]
+    pub proxy_addr: Option<String>,
+}
+
+// validate_containerd_registry_capabilities validates the capabilities of the containerd registry,
+// refer to https://github.com/containerd/containerd/blob/main/docs/hosts.md#capabilities-field.
+fn validate_containerd_registry_capabilities(
+    capabilities: &[String],
+) -> std::result::Result<(), ValidationError> {
+    if capabilities.is_empty() {
+        let mut err = ValidationError::new("capabilities_required");
+        err.message = Some("capabilities must not be empty".into());
+        return Err(err);
+    }
+
+    if let Some(capability) = capabilities
+        .iter()
+        .find(|capability| !matches!(capability.as_str(), "pull" | "resolve" | "push"))
+    {
+        let mut err = ValidationError::new("invalid_capability");
+        err.message = Some(
+            format!(
+                "invalid capability {}, expected pull, resolve or push",
+                capability
+            )
+            .into(),
+        );
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// ContainerdRegistry implements Debug, the password is redacted.
+impl fmt::Debug for ContainerdRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContainerdRegistry")
+            .field("host_namespace", &self.host_namespace)
+            .field("server_addr", &self.server_addr)
+            .field("capabilities", &self.capabilities)
+            .field("skip_verify", &self.skip_verify)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "[REDACTED]"))
+            .field("proxy_addr", &self.proxy_addr)
+            .finish()
+    }
 }
 
 // Containerd is the containerd configuration for dfinit.
@@ -113,12 +205,24 @@ pub struct Containerd {
     pub config_path: PathBuf,
 
     // registries is the list of containerd registries.
+    #[validate]
     pub registries: Vec<ContainerdRegistry>,
+
+    // certs_dir is the directory of the hosts.toml files of the registries, it is set to
+    // config_path of the containerd configuration if config_path is not set, refer to
+    // https://github.com/containerd/containerd/blob/main/docs/hosts.md.
+    #[serde(default = "default_container_runtime_containerd_certs_dir")]
+    pub certs_dir: PathBuf,
+
+    // restart_runtime restarts containerd with `systemctl restart containerd` after the
+    // configuration is changed, containerd does not reload the mirrors until it is restarted.
+    pub restart_runtime: bool,
 }
 
 // CRIORegistry is the registry configuration for cri-o.
-#[derive(Debug, Clone, Default, Validate, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Validate, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(default, rename_all = "camelCase")]
+#[validate(schema(function = "validate_crio_registry"))]
 pub struct CRIORegistry {
     // prefix is the prefix of the user-specified image name, refer to
     // https://github.com/containers/image/blob/main/docs/containers-registries.conf.5.md#choosing-a-registry-toml-table.
@@ -127,6 +231,63 @@ pub struct CRIORegistry {
     // location accepts the same format as the prefix field, and specifies the physical location of the prefix-rooted namespace,
     // refer to https://github.com/containers/image/blob/main/docs/containers-registries.conf.5.md#remapping-and-mirroring-registries.
     pub location: String,
+
+    // insecure is whether to skip the TLS verification of the dfdaemon's proxy mirror, refer to
+    // https://github.com/containers/image/blob/main/docs/containers-registries.conf.5.md#remapping-and-mirroring-registries.
+    #[serde(default = "default_container_runtime_crio_registry_insecure")]
+    pub insecure: bool,
+
+    // ca_cert_path is the path of the CA certificate to verify the dfdaemon's proxy mirror over TLS,
+    // it is installed to the certs.d directory next to the registries config, refer to
+    // https://github.com/containers/image/blob/main/docs/containers-certs.d.5.md.
+    pub ca_cert_path: Option<PathBuf>,
+
+    // proxy_addr is the address of the dfdaemon's proxy for the registry, it overrides
+    // the address of the proxy configuration.
+    #[validate(custom = "validate_proxy_addr")]
+    pub proxy_addr: Option<String>,
+}
+
+// CRIORegistry implements Default.
+impl Default for CRIORegistry {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            location: String::new(),
+            insecure: default_container_runtime_crio_registry_insecure(),
+            ca_cert_path: None,
+            proxy_addr: None,
+        }
+    }
+}
+
+// validate_crio_registry validates the cri-o registry, the CA certificate is required
+// to verify the dfdaemon's proxy mirror if the registry is not insecure.
+fn validate_crio_registry(registry: &CRIORegistry) -> std::result::Result<(), ValidationError> {
+    if !registry.insecure && registry.ca_cert_path.is_none() {
+        let mut err = ValidationError::new("ca_cert_path_required");
+        err.message = Some(
+            format!(
+                "caCertPath is required for the secure registry {}",
+                registry.prefix
+            )
+            .into(),
+        );
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// validate_proxy_addr validates the address of the dfdaemon's proxy of the registry.
+fn validate_proxy_addr(addr: &str) -> std::result::Result<(), ValidationError> {
+    if let Err(err) = parse_proxy_addr(addr) {
+        let mut validation_err = ValidationError::new("invalid_proxy_addr");
+        validation_err.message = Some(format!("invalid proxy address {}: {}", addr, err).into());
+        return Err(validation_err);
+    }
+
+    Ok(())
 }
 
 // CRIO is the cri-o configuration for dfinit.
@@ -144,7 +305,26 @@ pub struct CRIO {
 
     // registries is the list of cri-o registries, refer to
     // https://github.com/containers/image/blob/main/docs/containers-registries.conf.5.md#namespaced-registry-settings.
+    #[validate]
     pub registries: Vec<CRIORegistry>,
+
+    // force_overwrite overwrites the existing registries configuration file instead of
+    // merging the registries into it.
+    pub force_overwrite: bool,
+
+    // restart_runtime reloads cri-o with `systemctl reload crio` after the configuration is
+    // changed, cri-o reloads the registries configuration on SIGHUP.
+    pub restart_runtime: bool,
+
+    // use_dropin writes the registries to a drop-in file instead of the registries configuration
+    // file, which may be owned by the other tools. The unqualified search registries are not
+    // written to the drop-in file because it replaces the key instead of extending it, refer to
+    // https://github.com/containers/image/blob/main/docs/containers-registries.conf.d.5.md.
+    pub use_dropin: bool,
+
+    // dropin_path is the path of the drop-in file, it is registries.conf.d/99-dragonfly.conf
+    // in the directory of config_path if it is not set.
+    pub dropin_path: Option<PathBuf>,
 }
 
 // Docker is the docker configuration for dfinit.
@@ -154,6 +334,44 @@ pub struct Docker {
     // config_path is the path of docker configuration file.
     #[serde(default = "default_container_runtime_docker_config_path")]
     pub config_path: PathBuf,
+
+    // restart_runtime restarts dockerd with `systemctl restart docker` after the configuration
+    // is changed, dockerd does not reload the registry mirrors until it is restarted.
+    pub restart_runtime: bool,
+}
+
+// Apptainer is the apptainer configuration for dfinit.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Apptainer {
+    // config_path is the path of the apptainer remote configuration file, refer to
+    // https://apptainer.org/docs/user/main/endpoint.html.
+    #[serde(default = "default_container_runtime_apptainer_config_path")]
+    pub config_path: PathBuf,
+
+    // remote_name is the name of the remote endpoint of the dfdaemon's proxy.
+    #[serde(default = "default_container_runtime_apptainer_remote_name")]
+    #[validate(length(min = 1))]
+    pub remote_name: String,
+
+    // active indicates whether set the remote of the dfdaemon's proxy as the active remote.
+    // The apptainer remotes are the service discovery endpoints of the library, keyserver
+    // and build services, so only the requests of these services, e.g. pulling library://
+    // images, go through the dfdaemon's proxy if the remote is active. The docker:// and
+    // oras:// images are pulled from the OCI registries directly, the remote has no effect
+    // on them. The active remote of the user is kept by default.
+    pub active: bool,
+}
+
+// Apptainer implements Default.
+impl Default for Apptainer {
+    fn default() -> Self {
+        Self {
+            config_path: default_container_runtime_apptainer_config_path(),
+            remote_name: default_container_runtime_apptainer_remote_name(),
+            active: false,
+        }
+    }
 }
 
 // ContainerRuntime is the container runtime configuration for dfinit.
@@ -161,7 +379,15 @@ pub struct Docker {
 #[serde(default, rename_all = "camelCase")]
 pub struct ContainerRuntime {
     #[serde(flatten)]
+    #[validate]
     pub config: Option<ContainerRuntimeConfig>,
+
+    // auto detects the container runtimes installed on the host and configures every detected
+    // container runtime with the default configuration, the container runtime configured
+    // explicitly by config is not overridden by the detected one. Apptainer is not detected,
+    // it is configured only explicitly.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub auto: bool,
 }
 
 // ContainerRuntimeConfig is the container runtime configuration for dfinit.
@@ -170,6 +396,55 @@ pub enum ContainerRuntimeConfig {
     Containerd(Containerd),
     Docker(Docker),
     CRIO(CRIO),
+    Apptainer(Apptainer),
+}
+
+// ContainerRuntimeConfig implements the container runtime configuration.
+impl ContainerRuntimeConfig {
+    // name returns the name of the container runtime.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContainerRuntimeConfig::Containerd(_) => "containerd",
+            ContainerRuntimeConfig::Docker(_) => "docker",
+            ContainerRuntimeConfig::CRIO(_) => "crio",
+            ContainerRuntimeConfig::Apptainer(_) => "apptainer",
+        }
+    }
+
+    // defaults returns the default configurations of the container runtimes detected by dfinit.
+    // Apptainer is excluded, because its remote is a service discovery endpoint instead of an
+    // OCI mirror, and the remote configuration is present without apptainer being in use.
+    pub fn defaults() -> Vec<ContainerRuntimeConfig> {
+        vec![
+            ContainerRuntimeConfig::Containerd(Containerd {
+                config_path: default_container_runtime_containerd_config_path(),
+                certs_dir: default_container_runtime_containerd_certs_dir(),
+                ..Default::default()
+            }),
+            ContainerRuntimeConfig::Docker(Docker {
+                config_path: default_container_runtime_docker_config_path(),
+                ..Default::default()
+            }),
+            ContainerRuntimeConfig::CRIO(CRIO {
+                config_path: default_container_runtime_crio_config_path(),
+                unqualified_search_registries:
+                    default_container_runtime_crio_unqualified_search_registries(),
+                ..Default::default()
+            }),
+        ]
+    }
+}
+
+// Validate is the implementation of the Validate trait for ContainerRuntimeConfig.
+impl Validate for ContainerRuntimeConfig {
+    fn validate(&self) -> std::result::Result<(), ValidationErrors> {
+        match self {
+            ContainerRuntimeConfig::Containerd(cfg) => cfg.validate(),
+            ContainerRuntimeConfig::Docker(cfg) => cfg.validate(),
+            ContainerRuntimeConfig::CRIO(cfg) => cfg.validate(),
+            ContainerRuntimeConfig::Apptainer(cfg) => cfg.validate(),
+        }
+    }
 }
 
 // Serialize is the implementation of the Serialize trait for ContainerRuntimeConfig.
@@ -194,6 +469,11 @@ impl Serialize for ContainerRuntimeConfig {
                 state.serialize_field("crio", &cfg)?;
                 state.end()
             }
+            ContainerRuntimeConfig::Apptainer(ref cfg) => {
+                let mut state = serializer.serialize_struct("apptainer", 1)?;
+                state.serialize_field("apptainer", &cfg)?;
+                state.end()
+            }
         }
     }
 }
@@ -209,6 +489,7 @@ impl<'de> Deserialize<'de> for ContainerRuntimeConfig {
             containerd: Option<Containerd>,
             docker: Option<Docker>,
             crio: Option<CRIO>,
+            apptainer: Option<Apptainer>,
         }
 
         let helper = ContainerRuntimeHelper::deserialize(deserializer)?;
@@ -224,21 +505,37 @@ impl<'de> Deserialize<'de> for ContainerRuntimeConfig {
             ContainerRuntimeHelper {
                 crio: Some(crio), ..
             } => Ok(ContainerRuntimeConfig::CRIO(crio)),
+            ContainerRuntimeHelper {
+                apptainer: Some(apptainer),
+                ..
+            } => Ok(ContainerRuntimeConfig::Apptainer(apptainer)),
             _ => {
                 use serde::de::Error;
-                Err(D::Error::custom("expected containerd or docker or crio"))
+                Err(D::Error::custom(
+                    "expected containerd or docker or crio or apptainer",
+                ))
             }
         }
     }
 }
 
 // Proxy is the proxy server configuration for dfdaemon.
-#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[derive(Clone, Validate, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
+#[validate(schema(function = "validate_proxy_credentials"))]
 pub struct Proxy {
     // addr is the proxy server address of dfdaemon.
     #[serde(default = "default_proxy_addr")]
     pub addr: String,
+
+    // username is the username of the basic authentication of the dfdaemon's proxy, it is
+    // written to the container runtime configurations with the password.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    // password is the password of the basic authentication of the dfdaemon's proxy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
 }
 
 // Proxy implements Default.
@@ -246,10 +543,73 @@ impl Default for Proxy {
     fn default() -> Self {
         Self {
             addr: default_proxy_addr(),
+            username: None,
+            password: None,
         }
     }
 }
 
+// Proxy implements Debug, the password is redacted.
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("addr", &self.addr)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "[REDACTED]"))
+            .finish()
+    }
+}
+
+// Proxy implements the proxy server configuration.
+impl Proxy {
+    // credentials returns the username and the password of the dfdaemon's proxy if the
+    // basic authentication is enabled.
+    pub fn credentials(&self) -> Option<(&str, &str)> {
+        match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Some((username.as_str(), password.as_str())),
+            _ => None,
+        }
+    }
+}
+
+// validate_proxy_credentials validates the credentials of the dfdaemon's proxy, the username
+// and the password must be set together.
+fn validate_proxy_credentials(proxy: &Proxy) -> std::result::Result<(), ValidationError> {
+    if proxy.username.is_some() != proxy.password.is_some() {
+        let mut err = ValidationError::new("proxy_credentials_required");
+        err.message = Some("username and password of the proxy must be set together".into());
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+// parse_proxy_addr parses the address of the dfdaemon's proxy, the scheme must be http or https,
+// and the path, query and fragment are not allowed because the container runtimes ignore them.
+pub fn parse_proxy_addr(addr: &str) -> Result<Url> {
+    let proxy_url = Url::parse(addr).or_err(ErrorType::ParseError)?;
+    if !matches!(proxy_url.scheme(), "http" | "https") {
+        return Err(Error::InvalidURI(format!(
+            "{}: unsupported proxy scheme {}, expected http or https",
+            addr,
+            proxy_url.scheme()
+        )));
+    }
+
+    if proxy_url.host_str().is_none() {
+        return Err(Error::InvalidURI(format!("{}: proxy host not found", addr)));
+    }
+
+    if proxy_url.path() != "/" || proxy_url.query().is_some() || proxy_url.fragment().is_some() {
+        return Err(Error::InvalidURI(format!(
+            "{}: proxy address must not contain path, query or fragment",
+            addr
+        )));
+    }
+
+    Ok(proxy_url)
+}
+
 // Config is the configuration for dfinit.
 #[derive(Debug, Clone, Default, Validate, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -291,18 +651,23 @@ mod tests {
         let expected = r#"
 containerd:
   configPath: ''
-  registries: []"#;
+  registries: []
+  certsDir: ''
+  restartRuntime: false"#;
         assert_eq!(expected.trim(), res.trim());
 
         let runtime_cfg = ContainerRuntimeConfig::Docker(Docker {
             config_path: PathBuf::from("/root/.dragonfly/config/dfinit/yaml"),
+            ..Default::default()
         });
         let cfg = Config {
             container_runtime: ContainerRuntime {
                 config: Some(runtime_cfg),
+                ..Default::default()
             },
             proxy: Proxy {
                 addr: String::from("hello"),
+                ..Default::default()
             },
         };
 
@@ -312,7 +677,8 @@ proxy:
   addr: hello
 containerRuntime:
   docker:
-    configPath: /root/.dragonfly/config/dfinit/yaml"#;
+    configPath: /root/.dragonfly/config/dfinit/yaml
+    restartRuntime: false"#;
         assert_eq!(expected.trim(), res.trim());
 
         let runtime_cfg = ContainerRuntimeConfig::Containerd(Containerd {
@@ -322,9 +688,11 @@ containerRuntime:
         let cfg = Config {
             container_runtime: ContainerRuntime {
                 config: Some(runtime_cfg),
+                ..Default::default()
             },
             proxy: Proxy {
                 addr: String::from("hello"),
+                ..Default::default()
             },
         };
         let res = serde_yaml::to_string(&cfg).unwrap();
@@ -334,7 +702,9 @@ proxy:
 containerRuntime:
   containerd:
     configPath: /root/.dragonfly/config/dfinit/yaml
-    registries: []"#;
+    registries: []
+    certsDir: ''
+    restartRuntime: false"#;
         assert_eq!(expected.trim(), res.trim());
     }
 
@@ -344,7 +714,7 @@ containerRuntime:
             proxy: 
                 addr: "hello"
         "#;
-        let cfg: Config = serde_yaml::from_str(&raw_data).expect("failed to deserialize");
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
         assert!(cfg.container_runtime.config.is_none());
         assert_eq!("hello".to_string(), cfg.proxy.addr);
 
@@ -355,7 +725,7 @@ containerRuntime:
                 containerd:
                     configPath: "test_path"
         "#;
-        let cfg: Config = serde_yaml::from_str(&raw_data).expect("failed to deserialize");
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
         assert_eq!("hello".to_string(), cfg.proxy.addr);
         if let Some(ContainerRuntimeConfig::Containerd(c)) = cfg.container_runtime.config {
             assert_eq!(PathBuf::from("test_path"), c.config_path);
@@ -381,7 +751,7 @@ containerRuntime:
                         - prefix: "prefix2"
                           location: "location2"
         "#;
-        let cfg: Config = serde_yaml::from_str(&raw_data).expect("failed to deserialize");
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
         if let Some(ContainerRuntimeConfig::CRIO(c)) = cfg.container_runtime.config {
             assert_eq!(PathBuf::from("test_path"), c.config_path);
             assert_eq!(vec!["reg1", "reg2"], c.unqualified_search_registries);
@@ -389,11 +759,13 @@ containerRuntime:
                 vec![
                     CRIORegistry {
                         location: "location1".to_string(),
-                        prefix: "prefix1".to_string()
+                        prefix: "prefix1".to_string(),
+                        ..Default::default()
                     },
                     CRIORegistry {
                         location: "location2".to_string(),
-                        prefix: "prefix2".to_string()
+                        prefix: "prefix2".to_string(),
+                        ..Default::default()
                     },
                 ],
                 c.registries
@@ -402,4 +774,225 @@ containerRuntime:
             panic!("failed to deserialize");
         }
     }
+
+    #[test]
+    fn validate_crio_registry_ca_cert_path() {
+        let raw_data = r#"
+            containerRuntime:
+                crio:
+                    registries:
+                        - prefix: "prefix1"
+                          location: "location1"
+                        - prefix: "prefix2"
+                          location: "location2"
+                          insecure: false
+                          caCertPath: "/etc/dragonfly/ca.crt"
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_ok());
+        if let Some(ContainerRuntimeConfig::CRIO(c)) = &cfg.container_runtime.config {
+            assert!(c.registries[0].insecure);
+            assert!(!c.registries[1].insecure);
+        } else {
+            panic!("failed to deserialize");
+        }
+
+        let raw_data = r#"
+            containerRuntime:
+                crio:
+                    registries:
+                        - prefix: "prefix1"
+                          location: "location1"
+                          insecure: false
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn deserialize_container_runtime_auto_correctly() {
+        let raw_data = r#"
+            containerRuntime:
+                auto: true
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.container_runtime.auto);
+        assert!(cfg.container_runtime.config.is_none());
+
+        let raw_data = r#"
+            containerRuntime:
+                auto: true
+                docker:
+                    configPath: "/etc/docker/daemon.json"
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.container_runtime.auto);
+        assert_eq!(
+            cfg.container_runtime.config.as_ref().map(|c| c.name()),
+            Some("docker")
+        );
+
+        let defaults = ContainerRuntimeConfig::defaults();
+        assert_eq!(
+            defaults.iter().map(|c| c.name()).collect::<Vec<_>>(),
+            vec!["containerd", "docker", "crio"]
+        );
+        match &defaults[0] {
+            ContainerRuntimeConfig::Containerd(containerd) => {
+                assert_eq!(
+                    containerd.config_path,
+                    default_container_runtime_containerd_config_path()
+                );
+            }
+            _ => panic!("unexpected container runtime"),
+        }
+    }
+
+    #[test]
+    fn validate_proxy_credentials() {
+        let raw_data = r#"
+            proxy:
+                addr: "http://127.0.0.1:4001"
+                username: "foo"
+                password: "bar"
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_ok());
+        assert_eq!(cfg.proxy.credentials(), Some(("foo", "bar")));
+
+        // The password is redacted in the debug output.
+        let debug = format!("{:?}", cfg.proxy);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains("bar"));
+
+        let raw_data = r#"
+            proxy:
+                addr: "http://127.0.0.1:4001"
+                username: "foo"
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_err());
+        assert!(cfg.proxy.credentials().is_none());
+    }
+
+    #[test]
+    fn validate_containerd_registry_capabilities() {
+        let mut cfg = Containerd {
+            registries: vec![ContainerdRegistry {
+                host_namespace: "docker.io".to_string(),
+                server_addr: "https://index.docker.io".to_string(),
+                capabilities: vec!["pull".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(cfg.validate().is_ok());
+
+        cfg.registries[0].capabilities = vec!["pull".to_string(), "fetch".to_string()];
+        assert!(cfg.validate().is_err());
+
+        cfg.registries[0].capabilities = Vec::new();
+        assert!(cfg.validate().is_err());
+
+        // The default capabilities are pull and resolve.
+        let registry: ContainerdRegistry = serde_yaml::from_str(
+            r#"
+hostNamespace: docker.io
+serverAddr: https://index.docker.io
+"#,
+        )
+        .unwrap();
+        assert_eq!(registry.capabilities, vec!["pull", "resolve"]);
+        assert!(registry.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_registry_proxy_addr() {
+        let raw_data = r#"
+            containerRuntime:
+                containerd:
+                    registries:
+                        - hostNamespace: "docker.io"
+                          serverAddr: "https://index.docker.io"
+                          proxyAddr: "http://192.168.0.1:4001"
+                        - hostNamespace: "ghcr.io"
+                          serverAddr: "https://ghcr.io"
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_ok());
+        if let Some(ContainerRuntimeConfig::Containerd(c)) = &cfg.container_runtime.config {
+            assert_eq!(
+                c.registries[0].proxy_addr.as_deref(),
+                Some("http://192.168.0.1:4001")
+            );
+            assert!(c.registries[1].proxy_addr.is_none());
+        } else {
+            panic!("failed to deserialize");
+        }
+
+        for proxy_addr in [
+            "192.168.0.1:4001",
+            "ftp://192.168.0.1:4001",
+            "http://192.168.0.1:4001/v2",
+        ] {
+            let cfg = Containerd {
+                registries: vec![ContainerdRegistry {
+                    host_namespace: "docker.io".to_string(),
+                    server_addr: "https://index.docker.io".to_string(),
+                    capabilities: vec!["pull".to_string()],
+                    proxy_addr: Some(proxy_addr.to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            assert!(cfg.validate().is_err());
+
+            let cfg = CRIO {
+                registries: vec![CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    proxy_addr: Some(proxy_addr.to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            assert!(cfg.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn deserialize_container_runtime_apptainer_correctly() {
+        let raw_data = r#"
+            containerRuntime:
+                apptainer: {}
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_ok());
+        if let Some(ContainerRuntimeConfig::Apptainer(c)) = cfg.container_runtime.config {
+            assert_eq!(
+                PathBuf::from("/usr/local/etc/apptainer/remote.yaml"),
+                c.config_path
+            );
+            assert_eq!("dragonfly", c.remote_name);
+            assert!(!c.active);
+        } else {
+            panic!("failed to deserialize");
+        }
+
+        let raw_data = r#"
+            containerRuntime:
+                apptainer:
+                    configPath: "/root/.apptainer/remote.yaml"
+                    remoteName: ""
+                    active: true
+        "#;
+        let cfg: Config = serde_yaml::from_str(raw_data).expect("failed to deserialize");
+        assert!(cfg.validate().is_err());
+        if let Some(ContainerRuntimeConfig::Apptainer(c)) = cfg.container_runtime.config {
+            assert_eq!(PathBuf::from("/root/.apptainer/remote.yaml"), c.config_path);
+            assert!(c.active);
+        } else {
+            panic!("failed to deserialize");
+        }
+    }
 }
@@ -50,6 +50,10 @@ pub enum DFError {
     #[error{"task {0} not found"}]
     TaskNotFound(String),
 
+    // TaskPinned is the error when the pinned task is deleted without force.
+    #[error{"task {0} is pinned"}]
+    TaskPinned(String),
+
     // PieceNotFound is the error when the piece is not found.
     #[error{"piece {0} not found"}]
     PieceNotFound(String),
@@ -106,6 +110,10 @@ pub enum DFError {
     #[error{"piece digest mismatch"}]
     PieceDigestMismatch,
 
+    // PieceCorrupted is the error when the piece content in the local storage is corrupted.
+    #[error("piece {0} is corrupted")]
+    PieceCorrupted(String),
+
     // MaxScheduleCountExceeded is the error when the max schedule count is exceeded.
     #[error("max schedule count {0} exceeded")]
     MaxScheduleCountExceeded(u32),
@@ -118,6 +126,11 @@ pub enum DFError {
     #[error("invalid parameter")]
     InvalidParameter,
 
+    // ServiceRestartFailed is the error when the container runtime service fails to restart
+    // or reload, the fields are the action, the service and the cause.
+    #[error("failed to {0} container runtime service {1}: {2}")]
+    ServiceRestartFailed(String, String, String),
+
     #[error(transparent)]
     Utf8(#[from] std::str::Utf8Error),
 
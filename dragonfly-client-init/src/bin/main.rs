@@ -15,7 +15,7 @@
  */
 
 use clap::Parser;
-use dragonfly_client::tracing::init_tracing;
+use dragonfly_client::tracing::{init_tracing, LogFormat, LogRotation, TracingOptions};
 use dragonfly_client_config::dfinit;
 use dragonfly_client_init::container_runtime;
 use std::path::PathBuf;
@@ -55,6 +55,20 @@ struct Args {
     )]
     log_dir: PathBuf,
 
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to print the diffs of the configurations without writing them, exit with non-zero if the configurations would be changed"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to remove the Dragonfly configurations written by dfinit from the container runtime"
+    )]
+    uninstall: bool,
+
     #[arg(
         long,
         default_value_t = 24,
@@ -62,6 +76,28 @@ struct Args {
     )]
     log_max_files: usize,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LogFormat::Compact,
+        help = "Specify the format of the log [compact, json], the json format is also used for the console log if verbose"
+    )]
+    log_format: LogFormat,
+
+    #[arg(
+        long,
+        default_value = "hourly",
+        help = "Specify the rotation of the log file [hourly, daily, max size with unit, e.g. 100MB]"
+    )]
+    log_rotation: LogRotation,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to compress the rotated log files with gzip"
+    )]
+    log_compression: bool,
+
     #[arg(
         long = "verbose",
         default_value_t = false,
@@ -76,15 +112,17 @@ async fn main() -> Result<(), anyhow::Error> {
     let args = Args::parse();
 
     // Initialize tracing.
-    let _guards = init_tracing(
-        dfinit::NAME,
-        &args.log_dir,
-        args.log_level,
-        args.log_max_files,
-        None,
-        false,
-        args.verbose,
-    );
+    let (_guards, _) = init_tracing(TracingOptions {
+        name: dfinit::NAME.to_string(),
+        log_dir: args.log_dir.clone(),
+        log_level: args.log_level,
+        log_max_files: args.log_max_files,
+        log_format: args.log_format,
+        log_rotation: args.log_rotation,
+        log_compression: args.log_compression,
+        verbose: args.verbose,
+        ..Default::default()
+    });
 
     // Load config.
     let config = dfinit::Config::load(&args.config).map_err(|err| {
@@ -92,11 +130,29 @@ async fn main() -> Result<(), anyhow::Error> {
         err
     })?;
     // Handle features of the container runtime.
-    let container_runtime = container_runtime::ContainerRuntime::new(&config);
-    container_runtime.run().await.map_err(|err| {
+    let container_runtime = container_runtime::ContainerRuntime::new(&config, args.dry_run);
+    let result = if args.uninstall {
+        container_runtime.cleanup().await
+    } else {
+        container_runtime.run().await
+    };
+    if let Err(err) = result {
         error!("failed to run container runtime: {}", err);
-        err
-    })?;
+
+        // Restore the configurations changed by the container runtime.
+        if let Err(err) = container_runtime.rollback().await {
+            error!("failed to roll back container runtime: {}", err);
+        }
+
+        return Err(err.into());
+    }
+
+    // Exit with non-zero if the configurations would be changed in dry-run mode.
+    if args.dry_run && container_runtime.has_changes() {
+        return Err(anyhow::anyhow!(
+            "container runtime configurations would be changed"
+        ));
+    }
 
     Ok(())
 }
@@ -0,0 +1,472 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::backup::{Backup, Format};
+use dragonfly_client_config::dfinit;
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Error, Result,
+};
+use serde_yaml::{Mapping, Value};
+use std::sync::Arc;
+use tokio::{self, fs};
+use tracing::info;
+
+// ACTIVE_KEY is the key of the active remote in the apptainer remote configuration.
+const ACTIVE_KEY: &str = "Active";
+
+// REMOTES_KEY is the key of the remotes in the apptainer remote configuration.
+const REMOTES_KEY: &str = "Remotes";
+
+// Apptainer represents the apptainer runtime manager.
+#[derive(Debug, Clone)]
+pub struct Apptainer {
+    // config is the configuration for initializing
+    // runtime environment for the dfdaemon.
+    config: dfinit::Apptainer,
+
+    // proxy_config is the configuration for the dfdaemon's proxy server.
+    proxy_config: dfinit::Proxy,
+
+    // backup records the configurations changed by the runtime manager.
+    backup: Arc<Backup>,
+}
+
+// Apptainer implements the apptainer runtime manager.
+impl Apptainer {
+    // new creates a new apptainer runtime manager.
+    pub fn new(config: dfinit::Apptainer, proxy_config: dfinit::Proxy, dry_run: bool) -> Self {
+        Self {
+            config,
+            proxy_config,
+            backup: Arc::new(Backup::new(dry_run)),
+        }
+    }
+
+    // rollback restores the configurations changed by the apptainer runtime manager.
+    pub async fn rollback(&self) -> Result<()> {
+        self.backup.rollback().await
+    }
+
+    // has_changes returns whether the runtime manager changed the configurations, or would
+    // change them in dry-run mode.
+    pub fn has_changes(&self) -> bool {
+        self.backup.has_changes()
+    }
+
+    // run runs the apptainer runtime to initialize
+    // runtime environment for the dfdaemon.
+    pub async fn run(&self) -> Result<()> {
+        info!(
+            "apptainer feature is enabled, proxy_addr: {}, config_path: {:?}",
+            self.proxy_config.addr, self.config.config_path,
+        );
+
+        // Refuse to write the configuration instead of pulling through the proxy without the
+        // credentials, which fails with 407 Proxy Authentication Required.
+        if self.proxy_config.credentials().is_some() {
+            return Err(Error::Unknown(
+                "the remote URI of apptainer can not carry the credentials of the proxy"
+                    .to_string(),
+            ));
+        }
+
+        // The remote configuration file does not exist until a remote is added by the user.
+        let content = match fs::read_to_string(&self.config.config_path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let remote_config = self.add_remote(&content)?;
+
+        // Override apptainer remote configuration.
+        info!("override apptainer remote configuration");
+        self.backup
+            .write_config(
+                &self.config.config_path,
+                serde_yaml::to_string(&remote_config)
+                    .or_err(ErrorType::SerializeError)?
+                    .as_bytes(),
+                Format::Yaml,
+            )
+            .await
+    }
+
+    // add_remote adds the remote of the dfdaemon's proxy to the apptainer remote configuration,
+    // and sets it as the active remote only if the active is configured. The other remotes
+    // and keys are preserved.
+    pub fn add_remote(&self, content: &str) -> Result<Mapping> {
+        let mut remote_config = parse_remote_config(content)?;
+        let proxy_url = super::parse_proxy_addr(self.proxy_config.addr.as_str())?;
+        let proxy_host = proxy_url
+            .host_str()
+            .ok_or(Error::InvalidURI(self.proxy_config.addr.clone()))?;
+        let uri = match proxy_url.port() {
+            Some(port) => format!("{}:{}", proxy_host, port),
+            None => proxy_host.to_string(),
+        };
+        super::validate_registry_host(&uri).map_err(|reason| {
+            Error::InvalidURI(format!(
+                "remote uri {:?} of proxy {}: {}",
+                uri, self.proxy_config.addr, reason
+            ))
+        })?;
+
+        // The remote of the dfdaemon's proxy, refer to
+        // https://apptainer.org/docs/user/main/endpoint.html.
+        let mut remote = Mapping::new();
+        remote.insert("URI".into(), uri.into());
+        remote.insert("System".into(), true.into());
+        remote.insert("Exclusive".into(), false.into());
+        if proxy_url.scheme() == "http" {
+            remote.insert("Insecure".into(), true.into());
+        }
+
+        let remotes = remote_config
+            .entry(REMOTES_KEY.into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if remotes.is_null() {
+            *remotes = Value::Mapping(Mapping::new());
+        }
+
+        remotes
+            .as_mapping_mut()
+            .ok_or(Error::Unknown(format!("{} is not a mapping", REMOTES_KEY)))?
+            .insert(
+                self.config.remote_name.clone().into(),
+                Value::Mapping(remote),
+            );
+
+        // The remote is a service discovery endpoint rather than an OCI mirror, so the active
+        // remote of the user is not replaced unless it is configured explicitly.
+        if self.config.active {
+            remote_config.insert(ACTIVE_KEY.into(), self.config.remote_name.clone().into());
+        }
+
+        Ok(remote_config)
+    }
+
+    // cleanup removes the remote of the dfdaemon's proxy added by run from the apptainer remote
+    // configuration, and restores the active remote of the backup. If the backup only differs
+    // from the remote configuration by the removed remote, the backup is restored to keep the
+    // comments and the formatting of the original remote configuration.
+    pub async fn cleanup(&self) -> Result<()> {
+        let content = match fs::read_to_string(&self.config.config_path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("apptainer remote configuration is not found, skip cleanup");
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut remote_config = self.remove_remote(&content)?;
+        if let Some(backup) = self.backup.read_backup(&self.config.config_path).await? {
+            // The backup is not restored if it has the remote added by run.
+            if let Some(backup_config) = parse_remote_config(&backup)
+                .ok()
+                .filter(|backup_config| !self.has_remote(backup_config))
+            {
+                if let Some(active) = backup_config.get(ACTIVE_KEY) {
+                    if !remote_config.contains_key(ACTIVE_KEY) {
+                        remote_config.insert(ACTIVE_KEY.into(), active.clone());
+                    }
+                }
+
+                if backup_config == remote_config {
+                    info!("restore apptainer remote configuration from backup");
+                    return self
+                        .backup
+                        .write_config(&self.config.config_path, backup.as_bytes(), Format::Raw)
+                        .await;
+                }
+            }
+        }
+
+        // Keep the remote configuration untouched if nothing is removed.
+        if parse_remote_config(&content)? != remote_config {
+            info!("remove remote from apptainer remote configuration");
+            self.backup
+                .write_config(
+                    &self.config.config_path,
+                    serde_yaml::to_string(&remote_config)
+                        .or_err(ErrorType::SerializeError)?
+                        .as_bytes(),
+                    Format::Yaml,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // remove_remote removes the remote of the dfdaemon's proxy from the apptainer remote
+    // configuration, the active remote is removed if it is the remote of the dfdaemon's proxy.
+    pub fn remove_remote(&self, content: &str) -> Result<Mapping> {
+        let mut remote_config = parse_remote_config(content)?;
+        if let Some(remotes) = remote_config
+            .get_mut(REMOTES_KEY)
+            .and_then(|remotes| remotes.as_mapping_mut())
+        {
+            remotes.shift_remove(self.config.remote_name.as_str());
+        }
+
+        if remote_config
+            .get(ACTIVE_KEY)
+            .and_then(|active| active.as_str())
+            == Some(self.config.remote_name.as_str())
+        {
+            remote_config.shift_remove(ACTIVE_KEY);
+        }
+
+        Ok(remote_config)
+    }
+
+    // has_remote returns whether the apptainer remote configuration has the remote of the
+    // dfdaemon's proxy.
+    fn has_remote(&self, remote_config: &Mapping) -> bool {
+        remote_config
+            .get(REMOTES_KEY)
+            .and_then(|remotes| remotes.get(self.config.remote_name.as_str()))
+            .is_some()
+    }
+}
+
+// parse_remote_config parses the apptainer remote configuration, the empty content is an
+// empty mapping.
+fn parse_remote_config(content: &str) -> Result<Mapping> {
+    if content.trim().is_empty() {
+        return Ok(Mapping::new());
+    }
+
+    match serde_yaml::from_str(content).or_err(ErrorType::ParseError)? {
+        Value::Mapping(remote_config) => Ok(remote_config),
+        Value::Null => Ok(Mapping::new()),
+        _ => Err(Error::Unknown(
+            "apptainer remote configuration is not a mapping".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_create_remote_config() {
+        let dir = tempdir::TempDir::new("apptainer").unwrap();
+        let config_path = dir.path().join("apptainer").join("remote.yaml");
+        let apptainer = Apptainer::new(
+            dfinit::Apptainer {
+                config_path: config_path.clone(),
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        // The remote configuration and its directory are created if they do not exist.
+        apptainer.run().await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            r#"Remotes:
+  dragonfly:
+    URI: 127.0.0.1:4001
+    System: true
+    Exclusive: false
+    Insecure: true
+"#
+        );
+
+        // The remote is set as the active remote if it is configured.
+        let apptainer = Apptainer::new(
+            dfinit::Apptainer {
+                config_path: config_path.clone(),
+                active: true,
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+        apptainer.run().await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            r#"Remotes:
+  dragonfly:
+    URI: 127.0.0.1:4001
+    System: true
+    Exclusive: false
+    Insecure: true
+Active: dragonfly
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn should_merge_remote_config() {
+        let dir = tempdir::TempDir::new("apptainer").unwrap();
+        let config_path = dir.path().join("remote.yaml");
+        fs::write(
+            &config_path,
+            r#"Active: SylabsCloud
+Remotes:
+  SylabsCloud:
+    URI: cloud.sylabs.io
+    System: true
+    Exclusive: false
+  dragonfly:
+    URI: 10.0.0.1:4001
+    System: true
+    Exclusive: false
+Credentials:
+- URI: oras://registry.local
+  Insecure: false
+"#,
+        )
+        .await
+        .unwrap();
+
+        let apptainer = Apptainer::new(
+            dfinit::Apptainer {
+                config_path: config_path.clone(),
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        // The remotes and the active remote of the user are preserved, and the remote of the
+        // dfdaemon's proxy is updated.
+        apptainer.run().await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            r#"Active: SylabsCloud
+Remotes:
+  SylabsCloud:
+    URI: cloud.sylabs.io
+    System: true
+    Exclusive: false
+  dragonfly:
+    URI: dfdaemon.local
+    System: true
+    Exclusive: false
+Credentials:
+- URI: oras://registry.local
+  Insecure: false
+"#
+        );
+
+        // Re-running does not change anything.
+        let apptainer = Apptainer::new(
+            dfinit::Apptainer {
+                config_path: config_path.clone(),
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+        apptainer.run().await.unwrap();
+        assert!(!apptainer.has_changes());
+    }
+
+    #[test]
+    fn should_not_add_remote_to_invalid_config() {
+        let apptainer = Apptainer::new(Default::default(), Default::default(), false);
+        assert!(apptainer.add_remote("- foo").is_err());
+        assert!(apptainer.add_remote("Remotes: foo").is_err());
+        assert!(apptainer.add_remote("Remotes: [").is_err());
+    }
+
+    #[tokio::test]
+    async fn should_cleanup_remote_config() {
+        let dir = tempdir::TempDir::new("apptainer").unwrap();
+        let config_path = dir.path().join("remote.yaml");
+        let content = r#"# The remotes of the cluster.
+Active: SylabsCloud
+Remotes:
+  SylabsCloud:
+    URI: cloud.sylabs.io
+    System: true
+    Exclusive: false
+"#;
+        fs::write(&config_path, content).await.unwrap();
+        let config = dfinit::Apptainer {
+            config_path: config_path.clone(),
+            active: true,
+            ..Default::default()
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "http://127.0.0.1:4001".to_string(),
+            ..Default::default()
+        };
+
+        // The backup is restored with the comments if only the remote is added by run.
+        Apptainer::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        Apptainer::new(config.clone(), proxy_config.clone(), false)
+            .cleanup()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+
+        // The remote is removed and the active remote of the backup is restored if the remote
+        // configuration is changed after run.
+        Apptainer::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        let mut remote_config =
+            parse_remote_config(&fs::read_to_string(&config_path).await.unwrap()).unwrap();
+        remote_config
+            .get_mut(REMOTES_KEY)
+            .and_then(|remotes| remotes.as_mapping_mut())
+            .unwrap()
+            .insert("GitHub".into(), Value::Mapping(Mapping::new()));
+        fs::write(&config_path, serde_yaml::to_string(&remote_config).unwrap())
+            .await
+            .unwrap();
+        Apptainer::new(config, proxy_config, false)
+            .cleanup()
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            r#"Remotes:
+  SylabsCloud:
+    URI: cloud.sylabs.io
+    System: true
+    Exclusive: false
+  GitHub: {}
+Active: SylabsCloud
+"#
+        );
+    }
+}
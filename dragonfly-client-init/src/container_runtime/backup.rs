@@ -0,0 +1,608 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_client_core::{Error, Result};
+use similar::TextDiff;
+use std::ffi::OsString;
+use std::fs::Permissions;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+// BACKUP_SUFFIX is the suffix of the backup of the configuration.
+const BACKUP_SUFFIX: &str = ".dragonfly.bak";
+
+// TMP_SUFFIX is the suffix of the temporary configuration.
+const TMP_SUFFIX: &str = ".dragonfly.tmp";
+
+// DEFAULT_CONFIG_MODE is the mode of the configuration created by dfinit, the configurations
+// are readable by the container runtimes running as the other users, e.g. rootless podman.
+const DEFAULT_CONFIG_MODE: u32 = 0o644;
+
+// Format is the format of the configuration, it is used to compare the configurations
+// semantically, so the configuration is not rewritten if only the formatting differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // Toml is the TOML configuration, e.g. the configuration of containerd and cri-o.
+    Toml,
+
+    // Json is the JSON configuration, e.g. the configuration of docker.
+    Json,
+
+    // Yaml is the YAML configuration, e.g. the remote configuration of apptainer.
+    Yaml,
+
+    // Raw is the configuration compared byte by byte, e.g. the certificates.
+    Raw,
+}
+
+// Format implements the format of the configuration.
+impl Format {
+    // is_equal returns whether the contents are semantically equal, the contents that
+    // fail to parse are compared byte by byte.
+    pub fn is_equal(&self, a: &[u8], b: &[u8]) -> bool {
+        if a == b {
+            return true;
+        }
+
+        match self {
+            Format::Toml => {
+                let (Ok(a), Ok(b)) = (std::str::from_utf8(a), std::str::from_utf8(b)) else {
+                    return false;
+                };
+
+                match (a.parse::<toml::Value>(), b.parse::<toml::Value>()) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => false,
+                }
+            }
+            Format::Json => match (
+                serde_json::from_slice::<serde_json::Value>(a),
+                serde_json::from_slice::<serde_json::Value>(b),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            },
+            Format::Yaml => match (
+                serde_yaml::from_slice::<serde_yaml::Value>(a),
+                serde_yaml::from_slice::<serde_yaml::Value>(b),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            },
+            Format::Raw => false,
+        }
+    }
+}
+
+// Change is the change of the configuration written by the container runtime.
+#[derive(Debug)]
+struct Change {
+    // path is the path of the configuration.
+    path: PathBuf,
+
+    // original is the original content of the configuration, it is none
+    // if the configuration did not exist.
+    original: Option<Vec<u8>>,
+}
+
+// Backup records the changes of the configurations written by the container runtime,
+// and rolls back the changes if the container runtime fails to initialize. In dry-run
+// mode, the configurations are not written and the diffs are printed to stdout.
+#[derive(Debug, Default)]
+pub struct Backup {
+    // dry_run indicates whether to print the diffs instead of writing the configurations.
+    dry_run: bool,
+
+    // changed indicates whether any configuration is changed, or would be changed in dry-run mode.
+    changed: AtomicBool,
+
+    // changes is the changes of the configurations in the write order.
+    changes: Mutex<Vec<Change>>,
+}
+
+// Backup implements the backup of the configurations.
+impl Backup {
+    // new creates a new Backup.
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            ..Default::default()
+        }
+    }
+
+    // dry_run returns whether the configurations are not written in dry-run mode.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    // has_changes returns whether any configuration is changed, or would be changed in dry-run mode.
+    pub fn has_changes(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+
+    // write_config writes the configuration atomically, the content is written to a temporary
+    // file in the same directory and renamed to the path. The existing configuration is copied
+    // to <path>.dragonfly.bak if the backup does not exist, so the backup always keeps the
+    // original configuration before the first change of dfinit. The mode and the owner of the
+    // existing configuration are kept, and the new configuration is created with mode 0644.
+    // The configuration is not written if the content is semantically unchanged in the format,
+    // so the container runtime is not restarted by repeated runs. The parent directory of the
+    // path is created if it does not exist.
+    pub async fn write_config(&self, path: &Path, content: &[u8], format: Format) -> Result<()> {
+        let original = match fs::read(path).await {
+            Ok(original) => Some(original),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(original) = &original {
+            if format.is_equal(original, content) {
+                info!("config unchanged: {}", path.display());
+                return Ok(());
+            }
+        }
+
+        self.changed.store(true, Ordering::Relaxed);
+        if self.dry_run {
+            print!(
+                "{}",
+                diff(path, original.as_deref().unwrap_or_default(), content)
+            );
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let metadata = match original {
+            Some(_) => Some(fs::metadata(path).await?),
+            None => None,
+        };
+
+        let tmp_path = sibling_path(path, TMP_SUFFIX)?;
+        if let Err(err) = write_file(&tmp_path, content, metadata.as_ref()).await {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(err);
+        }
+
+        // Back up the existing configuration, the existing backup is never overwritten,
+        // because it is the original configuration before the changes of dfinit.
+        if let Some(original) = &original {
+            let backup_path = sibling_path(path, BACKUP_SUFFIX)?;
+            match fs::try_exists(&backup_path).await {
+                Ok(true) => info!(
+                    "backup {} exists, skip backing up {}",
+                    backup_path.display(),
+                    path.display()
+                ),
+                Ok(false) => {
+                    info!("back up {} to {}", path.display(), backup_path.display());
+                    if let Err(err) = write_file(&backup_path, original, metadata.as_ref()).await {
+                        let _ = fs::remove_file(&tmp_path).await;
+                        return Err(err);
+                    }
+                }
+                Err(err) => {
+                    let _ = fs::remove_file(&tmp_path).await;
+                    return Err(err.into());
+                }
+            }
+        }
+
+        fs::rename(&tmp_path, path).await?;
+
+        // Record the original content only for the first change of the path.
+        let mut changes = self.changes.lock().unwrap();
+        if !changes.iter().any(|change| change.path == path) {
+            changes.push(Change {
+                path: path.to_path_buf(),
+                original,
+            });
+        }
+
+        Ok(())
+    }
+
+    // remove_config removes the configuration, the removal is recorded to be rolled back
+    // like write_config. In dry-run mode, the diff of the removal is printed instead.
+    pub async fn remove_config(&self, path: &Path) -> Result<()> {
+        let original = match fs::read(path).await {
+            Ok(original) => original,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        self.changed.store(true, Ordering::Relaxed);
+        if self.dry_run {
+            print!("{}", diff(path, &original, &[]));
+            return Ok(());
+        }
+
+        info!("remove {}", path.display());
+        fs::remove_file(path).await?;
+
+        let mut changes = self.changes.lock().unwrap();
+        if !changes.iter().any(|change| change.path == path) {
+            changes.push(Change {
+                path: path.to_path_buf(),
+                original: Some(original),
+            });
+        }
+
+        Ok(())
+    }
+
+    // read_backup reads the backup of the configuration written by write_config, it returns
+    // none if the backup does not exist.
+    pub async fn read_backup(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read_to_string(sibling_path(path, BACKUP_SUFFIX)?).await {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // rollback restores the configurations changed by write_config and remove_config in the
+    // reverse order, the configurations created by write_config are removed.
+    pub async fn rollback(&self) -> Result<()> {
+        let changes = std::mem::take(&mut *self.changes.lock().unwrap());
+
+        let mut result = Ok(());
+        for change in changes.into_iter().rev() {
+            info!("roll back {}", change.path.display());
+            let restored = match change.original {
+                Some(original) => fs::write(&change.path, original).await,
+                None => fs::remove_file(&change.path).await,
+            };
+
+            // Continue to roll back the other configurations if one of them fails.
+            if let Err(err) = restored {
+                error!("failed to roll back {}: {}", change.path.display(), err);
+                result = Err(err.into());
+            }
+        }
+
+        result
+    }
+}
+
+// write_file writes the content to the path and syncs it to the disk. The mode and the owner
+// are copied from the metadata of the existing configuration, the mode is DEFAULT_CONFIG_MODE
+// if the metadata is none.
+async fn write_file(
+    path: &Path,
+    content: &[u8],
+    metadata: Option<&std::fs::Metadata>,
+) -> Result<()> {
+    let mut f = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(DEFAULT_CONFIG_MODE)
+        .open(path)
+        .await?;
+
+    // The mode of the open options is only applied to the new file and masked by the umask,
+    // so the permissions are set explicitly.
+    let permissions = match metadata {
+        Some(metadata) => metadata.permissions(),
+        None => Permissions::from_mode(DEFAULT_CONFIG_MODE),
+    };
+    f.set_permissions(permissions).await?;
+    if let Some(metadata) = metadata {
+        std::os::unix::fs::fchown(&f, Some(metadata.uid()), Some(metadata.gid()))?;
+    }
+
+    f.write_all(content).await?;
+    f.sync_all().await?;
+    Ok(())
+}
+
+// diff returns the unified diff of the configuration.
+fn diff(path: &Path, original: &[u8], content: &[u8]) -> String {
+    let original = String::from_utf8_lossy(original);
+    let content = String::from_utf8_lossy(content);
+    TextDiff::from_lines(original.as_ref(), content.as_ref())
+        .unified_diff()
+        .header(
+            &format!("a{}", path.display()),
+            &format!("b{}", path.display()),
+        )
+        .to_string()
+}
+
+// sibling_path returns the path in the same directory with the suffix appended to the file name.
+fn sibling_path(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let mut file_name = OsString::from(path.file_name().ok_or(Error::Unknown(format!(
+        "invalid config path {}",
+        path.display()
+    )))?);
+    file_name.push(suffix);
+    Ok(path.with_file_name(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_write_config() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("registries.conf");
+        let backup_path = dir.path().join("registries.conf.dragonfly.bak");
+        let backup = Backup::new(false);
+
+        // The configuration is created without backup if it does not exist.
+        backup
+            .write_config(&path, b"foo", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"foo");
+        assert!(!backup_path.exists());
+
+        // The existing configuration is backed up and replaced.
+        backup
+            .write_config(&path, b"bar", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"bar");
+        assert_eq!(fs::read(&backup_path).await.unwrap(), b"foo");
+        assert!(!dir.path().join("registries.conf.dragonfly.tmp").exists());
+
+        // The backup is not overridden if the configuration is unchanged.
+        backup
+            .write_config(&path, b"bar", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&backup_path).await.unwrap(), b"foo");
+
+        // The backup keeps the original configuration if the configuration is changed again.
+        backup
+            .write_config(&path, b"baz", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"baz");
+        assert_eq!(fs::read(&backup_path).await.unwrap(), b"foo");
+
+        // The existing backup of the previous runs is not overridden.
+        let backup = Backup::new(false);
+        backup
+            .write_config(&path, b"qux", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&backup_path).await.unwrap(), b"foo");
+    }
+
+    #[tokio::test]
+    async fn should_keep_mode_of_config() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("config.toml");
+        let created_path = dir.path().join("hosts.toml");
+        fs::write(&path, b"foo").await.unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640))
+            .await
+            .unwrap();
+
+        // The mode of the existing configuration is kept by the configuration and the backup.
+        let backup = Backup::new(false);
+        backup
+            .write_config(&path, b"bar", Format::Raw)
+            .await
+            .unwrap();
+        let mode = |metadata: std::fs::Metadata| metadata.permissions().mode() & 0o777;
+        assert_eq!(mode(fs::metadata(&path).await.unwrap()), 0o640);
+        assert_eq!(
+            mode(
+                fs::metadata(dir.path().join("config.toml.dragonfly.bak"))
+                    .await
+                    .unwrap()
+            ),
+            0o640
+        );
+
+        // The new configuration is created with the default mode, which is readable
+        // by the other users.
+        backup
+            .write_config(&created_path, b"foo", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(mode(fs::metadata(&created_path).await.unwrap()), 0o644);
+    }
+
+    #[tokio::test]
+    async fn should_not_write_config_if_semantically_unchanged() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let toml_path = dir.path().join("config.toml");
+        let json_path = dir.path().join("daemon.json");
+        fs::write(&toml_path, "b = 2\na = \"foo\"\n").await.unwrap();
+        fs::write(&json_path, r#"{"b":2,"a":"foo"}"#).await.unwrap();
+        let toml_modified = fs::metadata(&toml_path).await.unwrap().modified().unwrap();
+        let json_modified = fs::metadata(&json_path).await.unwrap().modified().unwrap();
+
+        let backup = Backup::new(false);
+        backup
+            .write_config(&toml_path, b"a = 'foo'\n\nb = 2\n", Format::Toml)
+            .await
+            .unwrap();
+        backup
+            .write_config(
+                &json_path,
+                b"{\n  \"a\": \"foo\",\n  \"b\": 2\n}",
+                Format::Json,
+            )
+            .await
+            .unwrap();
+        assert!(!backup.has_changes());
+        assert_eq!(
+            fs::metadata(&toml_path).await.unwrap().modified().unwrap(),
+            toml_modified
+        );
+        assert_eq!(
+            fs::metadata(&json_path).await.unwrap().modified().unwrap(),
+            json_modified
+        );
+        assert!(!dir.path().join("config.toml.dragonfly.bak").exists());
+        assert!(!dir.path().join("daemon.json.dragonfly.bak").exists());
+
+        // The configuration is written if the value is changed.
+        backup
+            .write_config(&json_path, br#"{"a":"bar","b":2}"#, Format::Json)
+            .await
+            .unwrap();
+        assert!(backup.has_changes());
+        assert_eq!(fs::read(&json_path).await.unwrap(), br#"{"a":"bar","b":2}"#);
+    }
+
+    #[test]
+    fn should_compare_config_by_format() {
+        assert!(Format::Toml.is_equal(b"a = 1\nb = 2", b"b = 2\n\na = 1\n"));
+        assert!(!Format::Toml.is_equal(b"a = 1", b"a = 2"));
+        assert!(!Format::Toml.is_equal(b"a = ", b"a =  "));
+        assert!(Format::Json.is_equal(br#"{"a":[1,2]}"#, b"{ \"a\": [1, 2] }"));
+        assert!(!Format::Json.is_equal(br#"{"a":[1,2]}"#, br#"{"a":[2,1]}"#));
+        assert!(Format::Yaml.is_equal(b"a: 1\nb: [2]\n", b"b:\n  - 2\na: 1"));
+        assert!(!Format::Yaml.is_equal(b"a: 1", b"a: 2"));
+        assert!(Format::Raw.is_equal(b"foo", b"foo"));
+        assert!(!Format::Raw.is_equal(b"{}", b"{ }"));
+    }
+
+    #[tokio::test]
+    async fn should_keep_config_if_write_failed() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("registries.conf");
+        fs::write(&path, b"foo").await.unwrap();
+
+        // The temporary file can not be written if it is a directory.
+        fs::create_dir(dir.path().join("registries.conf.dragonfly.tmp"))
+            .await
+            .unwrap();
+        assert!(Backup::new(false)
+            .write_config(&path, b"bar", Format::Raw)
+            .await
+            .is_err());
+        assert_eq!(fs::read(&path).await.unwrap(), b"foo");
+    }
+
+    #[tokio::test]
+    async fn should_rollback() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("registries.conf");
+        let created_path = dir.path().join("hosts.toml");
+        fs::write(&path, b"foo").await.unwrap();
+
+        let backup = Backup::new(false);
+        backup
+            .write_config(&path, b"bar", Format::Raw)
+            .await
+            .unwrap();
+        backup
+            .write_config(&path, b"baz", Format::Raw)
+            .await
+            .unwrap();
+        backup
+            .write_config(&created_path, b"foo", Format::Raw)
+            .await
+            .unwrap();
+
+        // The original configuration is restored and the created one is removed.
+        backup.rollback().await.unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"foo");
+        assert!(!created_path.exists());
+    }
+
+    #[tokio::test]
+    async fn should_not_write_config_in_dry_run() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("certs.d").join("ca.crt");
+        let backup = Backup::new(true);
+
+        // The configuration and its parent directory are not created.
+        backup
+            .write_config(&path, b"foo", Format::Raw)
+            .await
+            .unwrap();
+        assert!(backup.has_changes());
+        assert!(!dir.path().join("certs.d").exists());
+        backup.rollback().await.unwrap();
+
+        // The configuration is not changed if the content is unchanged.
+        let path = dir.path().join("registries.conf");
+        fs::write(&path, b"foo").await.unwrap();
+        let backup = Backup::new(true);
+        backup
+            .write_config(&path, b"foo", Format::Raw)
+            .await
+            .unwrap();
+        assert!(!backup.has_changes());
+    }
+
+    #[test]
+    fn should_diff_config() {
+        assert_eq!(
+            diff(Path::new("/etc/docker/daemon.json"), b"foo\nbar\n", b"foo\nbaz\n"),
+            "--- a/etc/docker/daemon.json\n+++ b/etc/docker/daemon.json\n@@ -1,2 +1,2 @@\n foo\n-bar\n+baz\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_remove_config() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("hosts.toml");
+        fs::write(&path, b"foo").await.unwrap();
+
+        // The removal is only printed in dry-run mode.
+        let backup = Backup::new(true);
+        backup.remove_config(&path).await.unwrap();
+        assert!(backup.has_changes());
+        assert!(path.exists());
+
+        // The removed configuration is restored by the rollback.
+        let backup = Backup::new(false);
+        backup.remove_config(&path).await.unwrap();
+        assert!(!path.exists());
+        backup.rollback().await.unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"foo");
+
+        // The missing configuration is not changed.
+        let backup = Backup::new(false);
+        backup
+            .remove_config(&dir.path().join("config.toml"))
+            .await
+            .unwrap();
+        assert!(!backup.has_changes());
+    }
+
+    #[tokio::test]
+    async fn should_read_backup() {
+        let dir = tempdir::TempDir::new("backup").unwrap();
+        let path = dir.path().join("daemon.json");
+        let backup = Backup::new(false);
+        assert!(backup.read_backup(&path).await.unwrap().is_none());
+
+        fs::write(&path, b"{}").await.unwrap();
+        backup
+            .write_config(&path, b"[]", Format::Raw)
+            .await
+            .unwrap();
+        assert_eq!(backup.read_backup(&path).await.unwrap().unwrap(), "{}");
+    }
+}
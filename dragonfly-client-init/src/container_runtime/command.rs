@@ -0,0 +1,96 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_client_core::{Error, Result};
+use std::fmt::Debug;
+use std::process::Command;
+use tracing::{error, info};
+
+// CommandRunner runs the commands to manage the service of the container runtime,
+// it is injectable so that the commands are not run on the host in tests.
+pub trait CommandRunner: Debug + Send + Sync {
+    // run runs the program with the args, and returns an error if the program
+    // exits with non-zero.
+    fn run(&self, program: &str, args: &[&str]) -> Result<()>;
+}
+
+// SystemCommandRunner runs the commands on the host.
+#[derive(Debug, Default)]
+pub struct SystemCommandRunner;
+
+// SystemCommandRunner implements the CommandRunner trait.
+impl CommandRunner for SystemCommandRunner {
+    // run runs the program on the host.
+    fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+        let output = Command::new(program).args(args).output()?;
+        if !output.status.success() {
+            return Err(Error::Unknown(format!(
+                "{} {} exited with {}: {}",
+                program,
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// restart_service restarts or reloads the systemd unit of the container runtime to apply the
+// changed configuration, the action is restart or reload.
+pub fn restart_service(runner: &dyn CommandRunner, action: &str, unit: &str) -> Result<()> {
+    info!("{} container runtime service {}", action, unit);
+    runner.run("systemctl", &[action, unit]).map_err(|err| {
+        error!("failed to {} {}: {}", action, unit, err);
+        Error::ServiceRestartFailed(action.to_string(), unit.to_string(), err.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_run_command() {
+        assert!(SystemCommandRunner.run("true", &[]).is_ok());
+        assert!(SystemCommandRunner.run("false", &[]).is_err());
+        assert!(SystemCommandRunner
+            .run("dragonfly-command-not-found", &[])
+            .is_err());
+    }
+
+    #[test]
+    fn should_return_service_restart_failed_error() {
+        // FailedCommandRunner fails to run all the commands.
+        #[derive(Debug)]
+        struct FailedCommandRunner;
+
+        impl CommandRunner for FailedCommandRunner {
+            fn run(&self, _program: &str, _args: &[&str]) -> Result<()> {
+                Err(Error::Unknown("exited with 1".to_string()))
+            }
+        }
+
+        match restart_service(&FailedCommandRunner, "restart", "containerd") {
+            Err(Error::ServiceRestartFailed(action, unit, _)) => {
+                assert_eq!(action, "restart");
+                assert_eq!(unit, "containerd");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -14,15 +14,25 @@
  * limitations under the License.
  */
 
+use super::backup::{Backup, Format};
+use super::command::{restart_service, CommandRunner, SystemCommandRunner};
 use dragonfly_client_config::dfinit;
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error, Result,
 };
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::{self, fs};
-use toml_edit::{value, Array, ArrayOfTables, Item, Table, Value};
-use tracing::info;
-use url::Url;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table, Value};
+use tracing::{info, warn};
+
+// DROPIN_DIR is the directory of the drop-in files next to the registries config.
+const DROPIN_DIR: &str = "registries.conf.d";
+
+// DROPIN_FILE_NAME is the name of the drop-in file written by dfinit, it is loaded after
+// the other drop-in files in the alphanumerical order.
+const DROPIN_FILE_NAME: &str = "99-dragonfly.conf";
 
 // CRIO represents the cri-o runtime manager.
 #[derive(Debug, Clone)]
@@ -33,79 +43,1120 @@ pub struct CRIO {
 
     // proxy_config is the configuration for the dfdaemon's proxy server.
     proxy_config: dfinit::Proxy,
+
+    // backup records the configurations changed by the runtime manager.
+    backup: Arc<Backup>,
+
+    // command_runner runs the commands to restart the cri-o service.
+    command_runner: Arc<dyn CommandRunner>,
 }
 
 // CRIO implements the cri-o runtime manager.
 impl CRIO {
     // new creates a new cri-o runtime manager.
-    pub fn new(config: dfinit::CRIO, proxy_config: dfinit::Proxy) -> Self {
+    pub fn new(config: dfinit::CRIO, proxy_config: dfinit::Proxy, dry_run: bool) -> Self {
         Self {
             config,
             proxy_config,
+            backup: Arc::new(Backup::new(dry_run)),
+            command_runner: Arc::new(SystemCommandRunner),
         }
     }
 
+    // with_command_runner sets the command runner to restart the cri-o service.
+    pub fn with_command_runner(mut self, command_runner: Arc<dyn CommandRunner>) -> Self {
+        self.command_runner = command_runner;
+        self
+    }
+
+    // rollback restores the configurations changed by the cri-o runtime manager.
+    pub async fn rollback(&self) -> Result<()> {
+        self.backup.rollback().await
+    }
+
+    // has_changes returns whether the runtime manager changed the configurations, or would
+    // change them in dry-run mode.
+    pub fn has_changes(&self) -> bool {
+        self.backup.has_changes()
+    }
+
     // run runs the cri-o runtime to initialize
     // runtime environment for the dfdaemon.
     pub async fn run(&self) -> Result<()> {
-        let mut registries_config_table = toml_edit::DocumentMut::new();
+        let registries_config_path = self.registries_config_path()?;
+        if self.config.use_dropin && !self.config.unqualified_search_registries.is_empty() {
+            warn!(
+                "unqualified-search-registries is not written to the drop-in file {}",
+                registries_config_path.display()
+            );
+        }
+
+        // Merge the registries into the existing registries config if it exists.
+        let content = if self.config.force_overwrite {
+            None
+        } else {
+            match fs::read_to_string(&registries_config_path).await {
+                Ok(content) => Some(content),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        let registries_config_table = self.registries_config(content.as_deref())?;
+        let registries_config = registries_config_table.to_string();
+        self.validate_registries_config(&registries_config)?;
+
+        let registries_config_dir = self
+            .config
+            .config_path
+            .parent()
+            .ok_or(Error::Unknown("invalid config path".to_string()))?;
+        self.backup
+            .write_config(
+                &registries_config_path,
+                registries_config.as_bytes(),
+                Format::Toml,
+            )
+            .await?;
+
+        // Install the CA certificates to verify the dfdaemon's proxy mirror.
+        self.install_ca_certs(registries_config_dir.join("certs.d").as_path())
+            .await?;
+
+        self.restart_runtime()
+    }
+
+    // registries_config_path returns the path of the registries config written by the runtime
+    // manager, it is the drop-in file in drop-in mode.
+    fn registries_config_path(&self) -> Result<PathBuf> {
+        if !self.config.use_dropin {
+            return Ok(self.config.config_path.clone());
+        }
+
+        if let Some(dropin_path) = &self.config.dropin_path {
+            return Ok(dropin_path.clone());
+        }
+
+        Ok(self
+            .config
+            .config_path
+            .parent()
+            .ok_or(Error::Unknown("invalid config path".to_string()))?
+            .join(DROPIN_DIR)
+            .join(DROPIN_FILE_NAME))
+    }
+
+    // restart_runtime reloads the cri-o service to apply the changed configurations if
+    // restart_runtime is enabled, it does nothing in dry-run mode.
+    fn restart_runtime(&self) -> Result<()> {
+        if !self.config.restart_runtime || self.backup.dry_run() || !self.backup.has_changes() {
+            return Ok(());
+        }
+
+        restart_service(self.command_runner.as_ref(), "reload", "crio")
+    }
+
+    // install_ca_certs copies the CA certificates of the registries to the certs.d directory
+    // of the dfdaemon's proxy, refer to
+    // https://github.com/containers/image/blob/main/docs/containers-certs.d.5.md.
+    pub async fn install_ca_certs(&self, certs_dir: &Path) -> Result<()> {
+        for registry in &self.config.registries {
+            let Some(ca_cert_path) = registry.ca_cert_path.as_ref() else {
+                continue;
+            };
+
+            let proxy_certs_dir = certs_dir.join(self.proxy_location(registry)?);
+            let file_name = ca_cert_path.file_name().ok_or(Error::Unknown(format!(
+                "invalid CA certificate path {}",
+                ca_cert_path.display()
+            )))?;
+
+            info!("install CA certificate: {}", ca_cert_path.display());
+            let content = fs::read(ca_cert_path).await?;
+            self.backup
+                .write_config(&proxy_certs_dir.join(file_name), &content, Format::Raw)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // proxy_location returns the host and port of the dfdaemon's proxy of the registry, it is
+    // the location of the mirror in the registries config. The proxy address of the registry
+    // overrides the address of the proxy configuration.
+    fn proxy_location(&self, registry: &dfinit::CRIORegistry) -> Result<String> {
+        parse_proxy_location(
+            registry
+                .proxy_addr
+                .as_deref()
+                .unwrap_or(self.proxy_config.addr.as_str()),
+        )
+    }
+
+    // proxy_locations returns the locations of the dfdaemon's proxies, including the location
+    // of the proxy configuration and the overrides of the registries.
+    fn proxy_locations(&self) -> Result<Vec<String>> {
+        let mut proxy_locations = vec![parse_proxy_location(self.proxy_config.addr.as_str())?];
+        for registry in &self.config.registries {
+            let proxy_location = self.proxy_location(registry)?;
+            if !proxy_locations.contains(&proxy_location) {
+                proxy_locations.push(proxy_location);
+            }
+        }
+
+        Ok(proxy_locations)
+    }
+
+    // registries_config returns the registries config with the dfdaemon's proxy as the first mirror
+    // of the registries. If the content of the existing registries config is given, the registries
+    // are merged into it, the registry tables whose prefix matches the configured registry are
+    // updated and the others are preserved, and the existing unqualified-search-registries is kept.
+    pub fn registries_config(&self, content: Option<&str>) -> Result<DocumentMut> {
+        let mut registries_config_table = match content {
+            Some(content) => content
+                .parse::<DocumentMut>()
+                .or_err(ErrorType::ParseError)?,
+            None => DocumentMut::new(),
+        };
         registries_config_table.set_implicit(true);
 
-        // Add unqualified-search-registries to registries config.
-        let mut unqualified_search_registries = Array::default();
-        for unqualified_search_registry in self.config.unqualified_search_registries.clone() {
-            unqualified_search_registries.push(Value::from(unqualified_search_registry));
+        // Add unqualified-search-registries to registries config, it is not added to the
+        // drop-in file to keep the unqualified-search-registries of the main file.
+        if !self.config.use_dropin
+            && !registries_config_table.contains_key("unqualified-search-registries")
+        {
+            let mut unqualified_search_registries = Array::default();
+            for unqualified_search_registry in self.config.unqualified_search_registries.clone() {
+                unqualified_search_registries.push(Value::from(unqualified_search_registry));
+            }
+            registries_config_table.insert(
+                "unqualified-search-registries",
+                value(unqualified_search_registries),
+            );
         }
-        registries_config_table.insert(
-            "unqualified-search-registries",
-            value(unqualified_search_registries),
-        );
-
-        // Parse proxy address to get host and port.
-        let proxy_url =
-            Url::parse(self.proxy_config.addr.as_str()).or_err(ErrorType::ParseError)?;
-        let proxy_host = proxy_url
-            .host_str()
-            .ok_or(Error::Unknown("host not found".to_string()))?;
-        let proxy_port = proxy_url
-            .port_or_known_default()
-            .ok_or(Error::Unknown("port not found".to_string()))?;
-        let proxy_location = format!("{}:{}", proxy_host, proxy_port);
+
+        let proxy_locations = self.proxy_locations()?;
+        let proxy_userinfo = super::proxy_userinfo(&self.proxy_config)?;
 
         // Add registries to the registries config.
-        let mut registries_table = ArrayOfTables::new();
+        let registries_table = registries_config_table
+            .entry("registry")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or(Error::Unknown(
+                "registry is not an array of tables".to_string(),
+            ))?;
         for registry in self.config.registries.clone() {
             info!("add registry: {:?}", registry);
+            let proxy_location = self.proxy_location(&registry)?;
             let mut registry_mirror_table = Table::new();
             registry_mirror_table.set_implicit(true);
-            registry_mirror_table.insert("insecure", value(true));
-            registry_mirror_table.insert("location", value(proxy_location.as_str()));
+            registry_mirror_table.insert("insecure", value(registry.insecure));
+            registry_mirror_table.insert(
+                "location",
+                value(super::embed_userinfo(
+                    &proxy_location,
+                    proxy_userinfo.as_deref(),
+                )),
+            );
+
+            // Update the registry table with the same prefix, or add a new one.
+            let position = registries_table.iter().position(|registry_table| {
+                registry_table
+                    .get("prefix")
+                    .and_then(|prefix| prefix.as_str())
+                    == Some(registry.prefix.as_str())
+            });
+            let index = match position {
+                Some(index) => index,
+                None => {
+                    let mut registry_table = Table::new();
+                    registry_table.set_implicit(true);
+                    registry_table.insert("prefix", value(registry.prefix.as_str()));
+                    registries_table.push(registry_table);
+                    registries_table.len() - 1
+                }
+            };
+            let registry_table = registries_table
+                .get_mut(index)
+                .ok_or(Error::Unknown("registry not found".to_string()))?;
+            registry_table.insert("location", value(registry.location));
 
+            // Add the dfdaemon's proxy as the first mirror, and preserve the other mirrors.
             let mut registry_mirrors_table = ArrayOfTables::new();
             registry_mirrors_table.push(registry_mirror_table);
-
-            let mut registry_table = Table::new();
-            registry_table.set_implicit(true);
-            registry_table.insert("prefix", value(registry.prefix));
-            registry_table.insert("location", value(registry.location));
+            if let Some(mirrors) = registry_table
+                .get("mirror")
+                .and_then(|mirrors| mirrors.as_array_of_tables())
+            {
+                for mirror in mirrors.iter().filter(|mirror| {
+                    !mirror
+                        .get("location")
+                        .and_then(|location| location.as_str())
+                        .is_some_and(|location| {
+                            proxy_locations
+                                .iter()
+                                .any(|proxy| *proxy == super::strip_userinfo(location))
+                        })
+                }) {
+                    registry_mirrors_table.push(mirror.clone());
+                }
+            }
             registry_table.insert("mirror", Item::ArrayOfTables(registry_mirrors_table));
+        }
+
+        Ok(registries_config_table)
+    }
 
-            registries_table.push(registry_table);
+    // validate_registries_config validates the rendered registries config before it is written, the
+    // prefixes and the locations of the registries must be valid registry addresses, the prefixes
+    // must not be duplicated, and the mirror locations must be the host and port of the proxies.
+    pub fn validate_registries_config(&self, content: &str) -> Result<()> {
+        let registries_config = content
+            .parse::<DocumentMut>()
+            .or_err(ErrorType::ParseError)?;
+
+        for (index, registry) in self.config.registries.iter().enumerate() {
+            if self.config.registries[..index]
+                .iter()
+                .any(|other| other.prefix == registry.prefix)
+            {
+                return Err(Error::Unknown(format!(
+                    "duplicate prefix of registry {}",
+                    registry.prefix
+                )));
+            }
+
+            super::validate_registry_prefix(&registry.prefix).map_err(|reason| {
+                Error::InvalidURI(format!(
+                    "prefix of registry {:?}: {}",
+                    registry.prefix, reason
+                ))
+            })?;
+            super::validate_registry_prefix(&registry.location).map_err(|reason| {
+                Error::InvalidURI(format!(
+                    "location {:?} of registry {}: {}",
+                    registry.location, registry.prefix, reason
+                ))
+            })?;
+        }
+
+        let Some(registries_table) = registries_config
+            .get("registry")
+            .and_then(|registries| registries.as_array_of_tables())
+        else {
+            return Ok(());
+        };
+
+        let proxy_locations = self.proxy_locations()?;
+        let mut prefixes = Vec::new();
+        for registry_table in registries_table.iter() {
+            let Some(prefix) = registry_table
+                .get("prefix")
+                .and_then(|prefix| prefix.as_str())
+            else {
+                continue;
+            };
+
+            if prefixes.contains(&prefix) {
+                return Err(Error::Unknown(format!(
+                    "duplicate prefix of registry {} in registries config",
+                    prefix
+                )));
+            }
+            prefixes.push(prefix);
+
+            for location in registry_table
+                .get("mirror")
+                .and_then(|mirrors| mirrors.as_array_of_tables())
+                .into_iter()
+                .flat_map(|mirrors| mirrors.iter())
+                .filter_map(|mirror| {
+                    mirror
+                        .get("location")
+                        .and_then(|location| location.as_str())
+                })
+                .map(super::strip_userinfo)
+                .filter(|location| proxy_locations.contains(location))
+            {
+                super::validate_registry_host(&location).map_err(|reason| {
+                    Error::InvalidURI(format!(
+                        "mirror location {:?} of registry {}: {}",
+                        location, prefix, reason
+                    ))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // cleanup removes the dfdaemon's proxy mirrors and the CA certificates installed by run. If
+    // the backup only differs from the registries config by the removed mirrors, the backup is
+    // restored to keep the registries and the formatting of the original registries config.
+    pub async fn cleanup(&self) -> Result<()> {
+        let registries_config_path = self.registries_config_path()?;
+        match fs::read_to_string(&registries_config_path).await {
+            Ok(content) => {
+                let registries_config = self.remove_registries(&content)?.to_string();
+                let backup = self.backup.read_backup(&registries_config_path).await?;
+                match backup {
+                    // The drop-in file is created by run, it is removed if there is no backup.
+                    None if self.config.use_dropin => {
+                        info!("remove drop-in file {}", registries_config_path.display());
+                        self.backup.remove_config(&registries_config_path).await?;
+                    }
+                    // The backup is not restored if it has the mirrors added by run.
+                    Some(backup)
+                        if self
+                            .remove_registries(&backup)
+                            .map(|backup_config| backup_config.to_string())
+                            .ok()
+                            .filter(|backup_config| *backup_config == backup)
+                            .as_ref()
+                            == Some(&registries_config) =>
+                    {
+                        info!("restore registries config from backup");
+                        self.backup
+                            .write_config(&registries_config_path, backup.as_bytes(), Format::Raw)
+                            .await?;
+                    }
+                    _ => {
+                        self.backup
+                            .write_config(
+                                &registries_config_path,
+                                registries_config.as_bytes(),
+                                Format::Toml,
+                            )
+                            .await?;
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("registries config is not found, skip cleanup");
+            }
+            Err(err) => return Err(err.into()),
         }
-        registries_config_table.insert("registry", Item::ArrayOfTables(registries_table));
 
         let registries_config_dir = self
             .config
             .config_path
             .parent()
             .ok_or(Error::Unknown("invalid config path".to_string()))?;
-        fs::create_dir_all(registries_config_dir.as_os_str()).await?;
+        self.remove_ca_certs(registries_config_dir.join("certs.d").as_path())
+            .await?;
+
+        self.restart_runtime()
+    }
+
+    // remove_registries removes the mirrors of the dfdaemon's proxy from the registries config,
+    // the mirror key of the registry is removed if no mirror is left.
+    pub fn remove_registries(&self, content: &str) -> Result<DocumentMut> {
+        let mut registries_config_table = content
+            .parse::<DocumentMut>()
+            .or_err(ErrorType::ParseError)?;
+        let proxy_locations = self.proxy_locations()?;
+
+        let Some(registries_table) = registries_config_table
+            .get_mut("registry")
+            .and_then(|registries| registries.as_array_of_tables_mut())
+        else {
+            return Ok(registries_config_table);
+        };
+
+        for registry_table in registries_table.iter_mut() {
+            let Some(mirrors) = registry_table
+                .get_mut("mirror")
+                .and_then(|mirrors| mirrors.as_array_of_tables_mut())
+            else {
+                continue;
+            };
+
+            let len = mirrors.len();
+            mirrors.retain(|mirror| {
+                !mirror
+                    .get("location")
+                    .and_then(|location| location.as_str())
+                    .is_some_and(|location| {
+                        proxy_locations.contains(&super::strip_userinfo(location))
+                    })
+            });
+            if mirrors.is_empty() && len > 0 {
+                registry_table.remove("mirror");
+            }
+        }
+
+        Ok(registries_config_table)
+    }
+
+    // remove_ca_certs removes the CA certificates installed by install_ca_certs, the CA certificate
+    // is removed only if it is the same as the configured one, and the backup is restored if it exists.
+    pub async fn remove_ca_certs(&self, certs_dir: &Path) -> Result<()> {
+        for registry in &self.config.registries {
+            let Some(ca_cert_path) = registry.ca_cert_path.as_ref() else {
+                continue;
+            };
+
+            let proxy_certs_dir = certs_dir.join(self.proxy_location(registry)?);
+            let file_name = ca_cert_path.file_name().ok_or(Error::Unknown(format!(
+                "invalid CA certificate path {}",
+                ca_cert_path.display()
+            )))?;
+            let installed_ca_cert_path = proxy_certs_dir.join(file_name);
+
+            let (Ok(ca_cert), Ok(installed_ca_cert)) = (
+                fs::read(ca_cert_path).await,
+                fs::read(&installed_ca_cert_path).await,
+            ) else {
+                continue;
+            };
+
+            if ca_cert != installed_ca_cert {
+                warn!(
+                    "CA certificate {} is not installed by dfinit, skip removing it",
+                    installed_ca_cert_path.display()
+                );
+                continue;
+            }
+
+            match self.backup.read_backup(&installed_ca_cert_path).await? {
+                Some(backup) => {
+                    self.backup
+                        .write_config(&installed_ca_cert_path, backup.as_bytes(), Format::Raw)
+                        .await?
+                }
+                None => self.backup.remove_config(&installed_ca_cert_path).await?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// parse_proxy_location parses the host and port of the dfdaemon's proxy address.
+fn parse_proxy_location(addr: &str) -> Result<String> {
+    let proxy_url = super::parse_proxy_addr(addr)?;
+    let proxy_host = proxy_url
+        .host_str()
+        .ok_or(Error::Unknown("host not found".to_string()))?;
+    let proxy_port = proxy_url
+        .port_or_known_default()
+        .ok_or(Error::Unknown("port not found".to_string()))?;
+    Ok(format!("{}:{}", proxy_host, proxy_port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_make_registries_config() {
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                unqualified_search_registries: vec!["docker.io".to_string()],
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let registries_config = crio.registries_config(None).unwrap();
+        assert_eq!(
+            registries_config.to_string(),
+            r#"unqualified-search-registries = ["docker.io"]
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+
+[[registry.mirror]]
+insecure = true
+location = "127.0.0.1:4001"
+"#
+        );
+    }
+
+    #[test]
+    fn should_make_registries_config_with_proxy_addr() {
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                unqualified_search_registries: vec!["docker.io".to_string()],
+                registries: vec![
+                    dfinit::CRIORegistry {
+                        prefix: "registry.internal".to_string(),
+                        location: "registry.internal".to_string(),
+                        proxy_addr: Some("http://10.0.0.1:4001".to_string()),
+                        ..Default::default()
+                    },
+                    dfinit::CRIORegistry {
+                        prefix: "docker.io".to_string(),
+                        location: "registry-1.docker.io".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        // The proxy address of the registry overrides the address of the proxy configuration,
+        // and the address of the proxy configuration is used if the registry has no proxy address.
+        let registries_config = crio.registries_config(None).unwrap();
+        let expected = r#"unqualified-search-registries = ["docker.io"]
+
+[[registry]]
+prefix = "registry.internal"
+location = "registry.internal"
+
+[[registry.mirror]]
+insecure = true
+location = "10.0.0.1:4001"
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+
+[[registry.mirror]]
+insecure = true
+location = "127.0.0.1:4001"
+"#;
+        assert_eq!(registries_config.to_string(), expected);
+
+        // The mirrors are not duplicated on re-run.
+        let registries_config = crio.registries_config(Some(expected)).unwrap();
+        assert_eq!(registries_config.to_string(), expected);
+
+        // The mirrors of both proxies are removed by cleanup.
+        assert_eq!(
+            crio.remove_registries(expected).unwrap().to_string(),
+            r#"unqualified-search-registries = ["docker.io"]
+
+[[registry]]
+prefix = "registry.internal"
+location = "registry.internal"
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+"#
+        );
+    }
+
+    #[test]
+    fn should_make_registries_config_with_proxy_credentials() {
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                username: Some("foo".to_string()),
+                password: Some("b@r".to_string()),
+            },
+            false,
+        );
+
+        // The credentials are embedded in the mirror location, and the password is redacted
+        // in the log of the runtime manager.
+        let crio_log = format!("{:?}", crio);
+        assert!(crio_log.contains("[REDACTED]"));
+        assert!(!crio_log.contains("b@r"));
+
+        let registries_config = crio.registries_config(None).unwrap().to_string();
+        let registries_config_table: toml::Value = toml::from_str(&registries_config).unwrap();
+        assert_eq!(
+            registries_config_table["registry"][0]["mirror"][0]["location"].as_str(),
+            Some("foo:b%40r@127.0.0.1:4001")
+        );
+        assert!(crio.validate_registries_config(&registries_config).is_ok());
+
+        // The mirror with the credentials is removed by cleanup.
+        let registries_config = crio.remove_registries(&registries_config).unwrap();
+        assert!(registries_config["registry"][0].get("mirror").is_none());
+    }
+
+    #[test]
+    fn should_merge_registries_config() {
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                unqualified_search_registries: vec!["docker.io".to_string()],
+                registries: vec![
+                    dfinit::CRIORegistry {
+                        prefix: "docker.io".to_string(),
+                        location: "registry-1.docker.io".to_string(),
+                        ..Default::default()
+                    },
+                    dfinit::CRIORegistry {
+                        prefix: "ghcr.io".to_string(),
+                        location: "ghcr.io".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let content = r#"unqualified-search-registries = ["registry.internal"]
+short-name-mode = "enforcing"
+
+[[registry]]
+prefix = "docker.io"
+location = "docker.io"
+
+[[registry.mirror]]
+location = "mirror.internal"
+
+[[registry]]
+prefix = "quay.io"
+blocked = true
+"#;
+        let expected = r#"unqualified-search-registries = ["registry.internal"]
+short-name-mode = "enforcing"
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+
+[[registry.mirror]]
+insecure = true
+location = "127.0.0.1:4001"
+
+[[registry.mirror]]
+location = "mirror.internal"
+
+[[registry]]
+prefix = "quay.io"
+blocked = true
+
+[[registry]]
+prefix = "ghcr.io"
+location = "ghcr.io"
+
+[[registry.mirror]]
+insecure = true
+location = "127.0.0.1:4001"
+"#;
+        let registries_config = crio.registries_config(Some(content)).unwrap();
+        assert_eq!(registries_config.to_string(), expected);
+
+        // The registries config is not changed if the registries are merged again.
+        let registries_config = crio
+            .registries_config(Some(registries_config.to_string().as_str()))
+            .unwrap();
+        assert_eq!(registries_config.to_string(), expected);
+    }
+
+    #[tokio::test]
+    async fn should_make_registries_config_with_ca_cert() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let ca_cert_path = dir.path().join("dragonfly-ca.crt");
+        fs::write(&ca_cert_path, "ca").await.unwrap();
+
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                unqualified_search_registries: vec!["docker.io".to_string()],
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    insecure: false,
+                    ca_cert_path: Some(ca_cert_path),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let registries_config = crio.registries_config(None).unwrap();
+        assert_eq!(
+            registries_config.to_string(),
+            r#"unqualified-search-registries = ["docker.io"]
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+
+[[registry.mirror]]
+insecure = false
+location = "dfdaemon.local:443"
+"#
+        );
+
+        let certs_dir = dir.path().join("certs.d");
+        crio.install_ca_certs(&certs_dir).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(
+                certs_dir
+                    .join("dfdaemon.local:443")
+                    .join("dragonfly-ca.crt")
+            )
+            .await
+            .unwrap(),
+            "ca"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_rollback_if_run_failed() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let config_path = dir.path().join("registries.conf");
         fs::write(
-            self.config.config_path.as_os_str(),
-            registries_config_table.to_string().as_bytes(),
+            &config_path,
+            "unqualified-search-registries = [\"quay.io\"]\n",
         )
-        .await?;
+        .await
+        .unwrap();
 
-        Ok(())
+        // The CA certificate does not exist, so the run fails after the registries
+        // config is written.
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                config_path: config_path.clone(),
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    insecure: false,
+                    ca_cert_path: Some(dir.path().join("missing.crt")),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+        assert!(crio.run().await.is_err());
+        assert_ne!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            "unqualified-search-registries = [\"quay.io\"]\n"
+        );
+
+        crio.rollback().await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).await.unwrap(),
+            "unqualified-search-registries = [\"quay.io\"]\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_write_config_in_dry_run() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let config_path = dir.path().join("containers").join("registries.conf");
+        let ca_cert_path = dir.path().join("dragonfly-ca.crt");
+        fs::write(&ca_cert_path, "ca").await.unwrap();
+
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                config_path: config_path.clone(),
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    insecure: false,
+                    ca_cert_path: Some(ca_cert_path),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            true,
+        );
+
+        // Neither the registries config nor the certs.d directory is created.
+        crio.run().await.unwrap();
+        assert!(crio.has_changes());
+        assert!(!dir.path().join("containers").exists());
+    }
+
+    #[tokio::test]
+    async fn should_cleanup_config() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let config_path = dir.path().join("registries.conf");
+        let ca_cert_path = dir.path().join("dragonfly-ca.crt");
+        fs::write(&ca_cert_path, "ca").await.unwrap();
+        let content = r#"# Managed by the node provisioner.
+unqualified-search-registries = ["docker.io"]
+
+[[registry]]
+prefix = "docker.io"
+location = "registry-1.docker.io"
+
+[[registry.mirror]]
+location = "mirror.internal"
+"#;
+        fs::write(&config_path, content).await.unwrap();
+
+        let config = dfinit::CRIO {
+            config_path: config_path.clone(),
+            registries: vec![dfinit::CRIORegistry {
+                prefix: "docker.io".to_string(),
+                location: "registry-1.docker.io".to_string(),
+                insecure: false,
+                ca_cert_path: Some(ca_cert_path),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "https://dfdaemon.local".to_string(),
+            ..Default::default()
+        };
+        let installed_ca_cert_path = dir
+            .path()
+            .join("certs.d")
+            .join("dfdaemon.local:443")
+            .join("dragonfly-ca.crt");
+
+        CRIO::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        assert!(installed_ca_cert_path.exists());
+
+        // The original registries config is restored and the CA certificate is removed.
+        CRIO::new(config.clone(), proxy_config.clone(), false)
+            .cleanup()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+        assert!(!installed_ca_cert_path.exists());
+
+        // The mirrors of the dfdaemon's proxy are removed surgically without the backup.
+        CRIO::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        fs::remove_file(dir.path().join("registries.conf.dragonfly.bak"))
+            .await
+            .unwrap();
+        CRIO::new(config, proxy_config, false)
+            .cleanup()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn should_not_remove_ca_cert_not_installed_by_dfinit() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let ca_cert_path = dir.path().join("dragonfly-ca.crt");
+        fs::write(&ca_cert_path, "ca").await.unwrap();
+        let certs_dir = dir.path().join("certs.d");
+        let installed_ca_cert_path = certs_dir
+            .join("dfdaemon.local:443")
+            .join("dragonfly-ca.crt");
+        fs::create_dir_all(installed_ca_cert_path.parent().unwrap())
+            .await
+            .unwrap();
+        fs::write(&installed_ca_cert_path, "other").await.unwrap();
+
+        let crio = CRIO::new(
+            dfinit::CRIO {
+                registries: vec![dfinit::CRIORegistry {
+                    prefix: "docker.io".to_string(),
+                    location: "registry-1.docker.io".to_string(),
+                    insecure: false,
+                    ca_cert_path: Some(ca_cert_path),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "https://dfdaemon.local".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+        crio.remove_ca_certs(&certs_dir).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&installed_ca_cert_path).await.unwrap(),
+            "other"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_write_invalid_registries_config() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let config_path = dir.path().join("registries.conf");
+        let content = "unqualified-search-registries = [\"docker.io\"]\n";
+        let registry = dfinit::CRIORegistry {
+            prefix: "docker.io".to_string(),
+            location: "registry-1.docker.io".to_string(),
+            ..Default::default()
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "http://127.0.0.1:4001".to_string(),
+            ..Default::default()
+        };
+
+        let cases = vec![
+            // The prefix is not a valid registry.
+            (
+                vec![dfinit::CRIORegistry {
+                    prefix: "docker..io".to_string(),
+                    ..registry.clone()
+                }],
+                proxy_config.clone(),
+                content,
+            ),
+            // The location is not a valid registry.
+            (
+                vec![dfinit::CRIORegistry {
+                    location: "https://registry-1.docker.io".to_string(),
+                    ..registry.clone()
+                }],
+                proxy_config.clone(),
+                content,
+            ),
+            // The prefix is duplicated in the dfinit configuration.
+            (
+                vec![registry.clone(), registry.clone()],
+                proxy_config.clone(),
+                content,
+            ),
+            // The prefix is duplicated in the existing registries config.
+            (
+                vec![dfinit::CRIORegistry {
+                    prefix: "quay.io".to_string(),
+                    location: "quay.io".to_string(),
+                    ..Default::default()
+                }],
+                proxy_config.clone(),
+                "[[registry]]\nprefix = \"docker.io\"\n\n[[registry]]\nprefix = \"docker.io\"\n",
+            ),
+            // The mirror location of the proxy is not a valid host and port.
+            (
+                vec![registry.clone()],
+                dfinit::Proxy {
+                    addr: "http://dfdaemon_proxy:4001".to_string(),
+                    ..Default::default()
+                },
+                content,
+            ),
+            // The proxy address has a path.
+            (
+                vec![registry.clone()],
+                dfinit::Proxy {
+                    addr: "http://127.0.0.1:4001/v2".to_string(),
+                    ..Default::default()
+                },
+                content,
+            ),
+        ];
+
+        for (registries, proxy_config, content) in cases {
+            fs::write(&config_path, content).await.unwrap();
+            let crio = CRIO::new(
+                dfinit::CRIO {
+                    config_path: config_path.clone(),
+                    registries: registries.clone(),
+                    ..Default::default()
+                },
+                proxy_config,
+                false,
+            );
+
+            // The existing registries config is left untouched.
+            assert!(crio.run().await.is_err(), "{:?}", registries);
+            assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+            assert!(!dir.path().join("registries.conf.dragonfly.bak").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_write_registries_to_dropin() {
+        let dir = tempdir::TempDir::new("crio").unwrap();
+        let config_path = dir.path().join("registries.conf");
+        let content = "unqualified-search-registries = [\"quay.io\"]\n";
+        fs::write(&config_path, content).await.unwrap();
+
+        let config = dfinit::CRIO {
+            config_path: config_path.clone(),
+            unqualified_search_registries: vec!["docker.io".to_string()],
+            registries: vec![dfinit::CRIORegistry {
+                prefix: "docker.io".to_string(),
+                location: "registry-1.docker.io".to_string(),
+                ..Default::default()
+            }],
+            use_dropin: true,
+            ..Default::default()
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "http://127.0.0.1:4001".to_string(),
+            ..Default::default()
+        };
+
+        // The registries are written to the drop-in file next to the registries config and
+        // the registries config is left alone.
+        let dropin_path = dir
+            .path()
+            .join("registries.conf.d")
+            .join("99-dragonfly.conf");
+        CRIO::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+        let dropin: toml::Value =
+            toml::from_str(&fs::read_to_string(&dropin_path).await.unwrap()).unwrap();
+        assert!(dropin.get("unqualified-search-registries").is_none());
+        assert_eq!(
+            dropin["registry"][0]["mirror"][0]["location"].as_str(),
+            Some("127.0.0.1:4001")
+        );
+
+        // The drop-in file created by dfinit is removed by cleanup.
+        CRIO::new(config.clone(), proxy_config.clone(), false)
+            .cleanup()
+            .await
+            .unwrap();
+        assert!(!dropin_path.exists());
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+
+        // The drop-in path is used if it is configured explicitly.
+        let dropin_path = dir.path().join("dragonfly.conf");
+        CRIO::new(
+            dfinit::CRIO {
+                dropin_path: Some(dropin_path.clone()),
+                ..config
+            },
+            proxy_config,
+            false,
+        )
+        .run()
+        .await
+        .unwrap();
+        assert!(dropin_path.exists());
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
     }
 }
@@ -14,9 +14,18 @@
  * limitations under the License.
  */
 
+use super::backup::{Backup, Format};
+use super::command::{restart_service, CommandRunner, SystemCommandRunner};
 use dragonfly_client_config::dfinit;
-use dragonfly_client_core::{Error, Result};
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Error, Result,
+};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::{self, fs};
 use tracing::info;
+use url::Url;
 
 // Docker represents the docker runtime manager.
 #[derive(Debug, Clone)]
@@ -27,20 +36,43 @@ pub struct Docker {
 
     // proxy_config is the configuration for the dfdaemon's proxy server.
     proxy_config: dfinit::Proxy,
+
+    // backup records the configurations changed by the runtime manager.
+    backup: Arc<Backup>,
+
+    // command_runner runs the commands to restart the docker service.
+    command_runner: Arc<dyn CommandRunner>,
 }
 
 // Docker implements the docker runtime manager.
 impl Docker {
     // new creates a new docker runtime manager.
-    pub fn new(config: dfinit::Docker, proxy_config: dfinit::Proxy) -> Self {
+    pub fn new(config: dfinit::Docker, proxy_config: dfinit::Proxy, dry_run: bool) -> Self {
         Self {
             config,
             proxy_config,
+            backup: Arc::new(Backup::new(dry_run)),
+            command_runner: Arc::new(SystemCommandRunner),
         }
     }
 
-    // TODO: Implement the run method for Docker.
-    //
+    // with_command_runner sets the command runner to restart the docker service.
+    pub fn with_command_runner(mut self, command_runner: Arc<dyn CommandRunner>) -> Self {
+        self.command_runner = command_runner;
+        self
+    }
+
+    // rollback restores the configurations changed by the docker runtime manager.
+    pub async fn rollback(&self) -> Result<()> {
+        self.backup.rollback().await
+    }
+
+    // has_changes returns whether the runtime manager changed the configurations, or would
+    // change them in dry-run mode.
+    pub fn has_changes(&self) -> bool {
+        self.backup.has_changes()
+    }
+
     // run runs the docker runtime to initialize
     // runtime environment for the dfdaemon.
     pub async fn run(&self) -> Result<()> {
@@ -48,6 +80,573 @@ impl Docker {
             "docker feature is enabled, proxy_addr: {}, config_path: {:?}",
             self.proxy_config.addr, self.config.config_path,
         );
-        Err(Error::Unimplemented)
+
+        // Refuse to write the configuration instead of pulling through the proxy without the
+        // credentials, which fails with 407 Proxy Authentication Required.
+        if self.proxy_config.credentials().is_some() {
+            return Err(Error::Unknown(
+                "the registry mirrors of daemon.json can not carry the credentials of the proxy"
+                    .to_string(),
+            ));
+        }
+
+        // The docker configuration file may not exist if the operator has not set it.
+        let content = match fs::read_to_string(&self.config.config_path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let docker_config = self.add_registries(&content)?;
+
+        // Override docker configuration.
+        info!("override docker configuration");
+        self.backup
+            .write_config(
+                &self.config.config_path,
+                &serde_json::to_vec_pretty(&docker_config).or_err(ErrorType::SerializeError)?,
+                Format::Json,
+            )
+            .await?;
+
+        self.restart_runtime()
+    }
+
+    // restart_runtime restarts the docker service to apply the changed configurations if
+    // restart_runtime is enabled, it does nothing in dry-run mode.
+    fn restart_runtime(&self) -> Result<()> {
+        if !self.config.restart_runtime || self.backup.dry_run() || !self.backup.has_changes() {
+            return Ok(());
+        }
+
+        restart_service(self.command_runner.as_ref(), "restart", "docker")
+    }
+
+    // add_registries adds the dfdaemon's proxy to the registry-mirrors and the
+    // insecure-registries of the docker configuration, the other keys of the
+    // docker configuration are preserved.
+    pub fn add_registries(&self, content: &str) -> Result<Value> {
+        let mut docker_config = parse_docker_config(content)?;
+
+        // Add the proxy to the registry mirrors with the highest priority.
+        let proxy_url = super::parse_proxy_addr(self.proxy_config.addr.as_str())?;
+        let proxy_addr = self.proxy_config.addr.trim_end_matches('/').to_string();
+        let registry_mirrors = array_mut(&mut docker_config, "registry-mirrors")?;
+        if !registry_mirrors.iter().any(|mirror| {
+            mirror.as_str().map(|mirror| mirror.trim_end_matches('/')) == Some(proxy_addr.as_str())
+        }) {
+            registry_mirrors.insert(0, Value::from(proxy_addr.clone()));
+        }
+
+        // Add the proxy to the insecure registries if the proxy is served over http.
+        if let Some(insecure_registry) = insecure_registry(&proxy_url)? {
+            super::validate_registry_host(&insecure_registry).map_err(|reason| {
+                Error::InvalidURI(format!(
+                    "insecure registry {:?} of proxy {}: {}",
+                    insecure_registry, self.proxy_config.addr, reason
+                ))
+            })?;
+
+            let insecure_registries = array_mut(&mut docker_config, "insecure-registries")?;
+            if !insecure_registries
+                .iter()
+                .any(|registry| registry.as_str() == Some(insecure_registry.as_str()))
+            {
+                insecure_registries.push(Value::from(insecure_registry));
+            }
+        }
+
+        Ok(Value::Object(docker_config))
+    }
+
+    // cleanup removes the dfdaemon's proxy added by run from the docker configuration. If the
+    // backup only differs from the docker configuration by the removed entries, the backup is
+    // restored to keep the original formatting of the docker configuration.
+    pub async fn cleanup(&self) -> Result<()> {
+        let content = match fs::read_to_string(&self.config.config_path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("docker configuration is not found, skip cleanup");
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let docker_config = self.remove_registries(&content)?;
+        if let Some(backup) = self.backup.read_backup(&self.config.config_path).await? {
+            // The backup is not restored if it has the entries added by run.
+            let backup_config = self.remove_registries(&backup).ok();
+            if backup_config.as_ref() == Some(&docker_config)
+                && parse_docker_config(&backup).ok().map(Value::Object) == backup_config
+            {
+                info!("restore docker configuration from backup");
+                self.backup
+                    .write_config(&self.config.config_path, backup.as_bytes(), Format::Raw)
+                    .await?;
+                return self.restart_runtime();
+            }
+        }
+
+        // Keep the docker configuration untouched if nothing is removed.
+        if Value::Object(parse_docker_config(&content)?) != docker_config {
+            info!("remove registries from docker configuration");
+            self.backup
+                .write_config(
+                    &self.config.config_path,
+                    &serde_json::to_vec_pretty(&docker_config).or_err(ErrorType::SerializeError)?,
+                    Format::Json,
+                )
+                .await?;
+        }
+
+        self.restart_runtime()
+    }
+
+    // remove_registries removes the dfdaemon's proxy from the registry-mirrors and the
+    // insecure-registries of the docker configuration, the entries which are not added
+    // by add_registries are preserved.
+    pub fn remove_registries(&self, content: &str) -> Result<Value> {
+        let mut docker_config = parse_docker_config(content)?;
+        let proxy_url = super::parse_proxy_addr(self.proxy_config.addr.as_str())?;
+        let proxy_addr = self.proxy_config.addr.trim_end_matches('/').to_string();
+        remove_from_array(&mut docker_config, "registry-mirrors", |mirror| {
+            mirror.as_str().map(|mirror| mirror.trim_end_matches('/')) == Some(proxy_addr.as_str())
+        })?;
+
+        if let Some(insecure_registry) = insecure_registry(&proxy_url)? {
+            remove_from_array(&mut docker_config, "insecure-registries", |registry| {
+                registry.as_str() == Some(insecure_registry.as_str())
+            })?;
+        }
+
+        Ok(Value::Object(docker_config))
+    }
+}
+
+// parse_docker_config parses the docker configuration, the empty content is an empty object.
+fn parse_docker_config(content: &str) -> Result<Map<String, Value>> {
+    if content.trim().is_empty() {
+        return Ok(Map::new());
+    }
+
+    match serde_json::from_str(content).or_err(ErrorType::ParseError)? {
+        Value::Object(docker_config) => Ok(docker_config),
+        _ => Err(Error::Unknown(
+            "docker configuration is not an object".to_string(),
+        )),
+    }
+}
+
+// insecure_registry returns the host and port of the proxy to add to the insecure
+// registries, it returns none if the proxy is not served over http.
+fn insecure_registry(proxy_url: &Url) -> Result<Option<String>> {
+    if proxy_url.scheme() != "http" {
+        return Ok(None);
+    }
+
+    let host = proxy_url
+        .host_str()
+        .ok_or(Error::InvalidURI(proxy_url.to_string()))?;
+    Ok(Some(match proxy_url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }))
+}
+
+// remove_from_array removes the values matched by the predicate from the array of the key
+// in the docker configuration, the key is removed if the array becomes empty.
+fn remove_from_array<F>(
+    docker_config: &mut Map<String, Value>,
+    key: &str,
+    predicate: F,
+) -> Result<()>
+where
+    F: Fn(&Value) -> bool,
+{
+    let Some(array) = docker_config.get_mut(key) else {
+        return Ok(());
+    };
+
+    let array = array
+        .as_array_mut()
+        .ok_or(Error::Unknown(format!("{} is not an array", key)))?;
+    let len = array.len();
+    array.retain(|value| !predicate(value));
+    if array.is_empty() && len > 0 {
+        docker_config.remove(key);
+    }
+
+    Ok(())
+}
+
+// array_mut returns the array of the key in the docker configuration, the array
+// is created if the key does not exist.
+fn array_mut<'a>(
+    docker_config: &'a mut Map<String, Value>,
+    key: &str,
+) -> Result<&'a mut Vec<Value>> {
+    docker_config
+        .entry(key)
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or(Error::Unknown(format!("{} is not an array", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // MockCommandRunner records the commands instead of running them on the host.
+    #[derive(Debug, Default)]
+    struct MockCommandRunner {
+        // commands is the commands run by the runner.
+        commands: Mutex<Vec<String>>,
+
+        // fail indicates whether the commands fail.
+        fail: bool,
+    }
+
+    // MockCommandRunner implements the CommandRunner trait.
+    impl CommandRunner for MockCommandRunner {
+        fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+            self.commands
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", program, args.join(" ")));
+            if self.fail {
+                return Err(Error::Unknown("exited with 1".to_string()));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_add_registries_to_empty_config() {
+        let docker = Docker::new(
+            Default::default(),
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let docker_config = docker.add_registries("").unwrap();
+        assert_eq!(
+            docker_config,
+            serde_json::json!({
+                "registry-mirrors": ["http://127.0.0.1:4001"],
+                "insecure-registries": ["127.0.0.1:4001"],
+            })
+        );
+    }
+
+    #[test]
+    fn should_add_registries_to_existing_config() {
+        let docker = Docker::new(
+            Default::default(),
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        let content = r#"{
+            "log-driver": "json-file",
+            "registry-mirrors": ["https://mirror.example.com"],
+            "insecure-registries": ["registry.local:5000"]
+        }"#;
+        let docker_config = docker.add_registries(content).unwrap();
+        let expected = serde_json::json!({
+            "log-driver": "json-file",
+            "registry-mirrors": ["http://127.0.0.1:4001", "https://mirror.example.com"],
+            "insecure-registries": ["registry.local:5000", "127.0.0.1:4001"],
+        });
+        assert_eq!(docker_config, expected);
+
+        // The registries are not duplicated if they are already added.
+        let docker_config = docker.add_registries(&docker_config.to_string()).unwrap();
+        assert_eq!(docker_config, expected);
+    }
+
+    #[test]
+    fn should_not_add_registries_to_invalid_config() {
+        let docker = Docker::new(Default::default(), Default::default(), false);
+        assert!(docker.add_registries("[]").is_err());
+        assert!(docker
+            .add_registries(r#"{"registry-mirrors": "foo"}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn should_not_add_registries_with_invalid_proxy_host() {
+        let docker = Docker::new(
+            Default::default(),
+            dfinit::Proxy {
+                addr: "http://dfdaemon_proxy:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+        assert!(docker.add_registries("").is_err());
+    }
+
+    #[test]
+    fn should_not_add_registries_to_malformed_config() {
+        let docker = Docker::new(Default::default(), Default::default(), false);
+        assert!(docker.add_registries(r#"{"registry-mirrors": ["#).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_write_config() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        let docker = Docker::new(
+            dfinit::Docker {
+                config_path: config_path.clone(),
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        );
+
+        // The docker configuration is created if it does not exist.
+        docker.run().await.unwrap();
+        let docker_config: Value =
+            serde_json::from_slice(&fs::read(&config_path).await.unwrap()).unwrap();
+        assert_eq!(
+            docker_config,
+            serde_json::json!({
+                "registry-mirrors": ["http://127.0.0.1:4001"],
+                "insecure-registries": ["127.0.0.1:4001"],
+            })
+        );
+
+        // The other keys are preserved and the registries are not duplicated on re-run.
+        fs::write(
+            &config_path,
+            r#"{"log-driver": "json-file", "storage-driver": "overlay2", "registry-mirrors": ["http://127.0.0.1:4001"]}"#,
+        )
+        .await
+        .unwrap();
+        docker.run().await.unwrap();
+        docker.run().await.unwrap();
+        let docker_config: Value =
+            serde_json::from_slice(&fs::read(&config_path).await.unwrap()).unwrap();
+        assert_eq!(
+            docker_config,
+            serde_json::json!({
+                "log-driver": "json-file",
+                "storage-driver": "overlay2",
+                "registry-mirrors": ["http://127.0.0.1:4001"],
+                "insecure-registries": ["127.0.0.1:4001"],
+            })
+        );
+
+        // The malformed docker configuration is not overwritten.
+        fs::write(&config_path, "{").await.unwrap();
+        assert!(docker.run().await.is_err());
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), "{");
+    }
+
+    #[tokio::test]
+    async fn should_not_write_config_if_semantically_unchanged() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        let content = r#"{"insecure-registries":["127.0.0.1:4001"],"registry-mirrors":["http://127.0.0.1:4001"]}"#;
+        fs::write(&config_path, content).await.unwrap();
+        let modified = fs::metadata(&config_path)
+            .await
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let command_runner = Arc::new(MockCommandRunner::default());
+        let docker = Docker::new(
+            dfinit::Docker {
+                config_path: config_path.clone(),
+                restart_runtime: true,
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        )
+        .with_command_runner(command_runner.clone());
+
+        // The configuration is not rewritten and the docker service is not restarted.
+        docker.run().await.unwrap();
+        assert!(!docker.has_changes());
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+        assert_eq!(
+            fs::metadata(&config_path)
+                .await
+                .unwrap()
+                .modified()
+                .unwrap(),
+            modified
+        );
+        assert!(!dir.path().join("daemon.json.dragonfly.bak").exists());
+        assert!(command_runner.commands.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_restart_runtime() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        let command_runner = Arc::new(MockCommandRunner::default());
+        let config = dfinit::Docker {
+            config_path: config_path.clone(),
+            restart_runtime: true,
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "http://127.0.0.1:4001".to_string(),
+            ..Default::default()
+        };
+
+        // The docker service is restarted after the configuration is changed.
+        Docker::new(config.clone(), proxy_config.clone(), false)
+            .with_command_runner(command_runner.clone())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(
+            *command_runner.commands.lock().unwrap(),
+            vec!["systemctl restart docker".to_string()]
+        );
+
+        // The docker service is not restarted if the configuration is unchanged.
+        Docker::new(config.clone(), proxy_config.clone(), false)
+            .with_command_runner(command_runner.clone())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(command_runner.commands.lock().unwrap().len(), 1);
+
+        // The docker service is not restarted in dry-run mode.
+        fs::remove_file(&config_path).await.unwrap();
+        Docker::new(config, proxy_config, true)
+            .with_command_runner(command_runner.clone())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(command_runner.commands.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_rollback_if_restart_failed() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        fs::write(&config_path, "{}").await.unwrap();
+
+        let docker = Docker::new(
+            dfinit::Docker {
+                config_path: config_path.clone(),
+                restart_runtime: true,
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                ..Default::default()
+            },
+            false,
+        )
+        .with_command_runner(Arc::new(MockCommandRunner {
+            fail: true,
+            ..Default::default()
+        }));
+
+        let err = docker.run().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("failed to restart container runtime service docker"));
+
+        docker.rollback().await.unwrap();
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), "{}");
+    }
+
+    #[tokio::test]
+    async fn should_not_write_config_with_proxy_credentials() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        let docker = Docker::new(
+            dfinit::Docker {
+                config_path: config_path.clone(),
+                ..Default::default()
+            },
+            dfinit::Proxy {
+                addr: "http://127.0.0.1:4001".to_string(),
+                username: Some("foo".to_string()),
+                password: Some("bar".to_string()),
+            },
+            false,
+        );
+
+        let err = docker.run().await.unwrap_err();
+        assert!(err.to_string().contains("credentials"));
+        assert!(!err.to_string().contains("bar"));
+        assert!(!config_path.exists());
+    }
+
+    #[tokio::test]
+    async fn should_cleanup_config() {
+        let dir = tempdir::TempDir::new("docker").unwrap();
+        let config_path = dir.path().join("daemon.json");
+        let content = "{\n    \"log-driver\": \"json-file\",\n    \"registry-mirrors\": [\"https://mirror.example.com\"]\n}\n";
+        fs::write(&config_path, content).await.unwrap();
+
+        let config = dfinit::Docker {
+            config_path: config_path.clone(),
+            ..Default::default()
+        };
+        let proxy_config = dfinit::Proxy {
+            addr: "http://127.0.0.1:4001".to_string(),
+            ..Default::default()
+        };
+        Docker::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        assert_ne!(fs::read_to_string(&config_path).await.unwrap(), content);
+
+        // The original docker configuration is restored from the backup.
+        let docker = Docker::new(config.clone(), proxy_config.clone(), false);
+        docker.cleanup().await.unwrap();
+        assert!(docker.has_changes());
+        assert_eq!(fs::read_to_string(&config_path).await.unwrap(), content);
+
+        // The entries are removed surgically without the backup, and the entries
+        // which are not added by dfinit are preserved.
+        Docker::new(config.clone(), proxy_config.clone(), false)
+            .run()
+            .await
+            .unwrap();
+        fs::remove_file(dir.path().join("daemon.json.dragonfly.bak"))
+            .await
+            .unwrap();
+        Docker::new(config.clone(), proxy_config.clone(), false)
+            .cleanup()
+            .await
+            .unwrap();
+        let docker_config: Value =
+            serde_json::from_slice(&fs::read(&config_path).await.unwrap()).unwrap();
+        assert_eq!(
+            docker_config,
+            serde_json::json!({
+                "log-driver": "json-file",
+                "registry-mirrors": ["https://mirror.example.com"],
+            })
+        );
+
+        // Nothing is changed if the entries are already removed.
+        let docker = Docker::new(config, proxy_config, false);
+        docker.cleanup().await.unwrap();
+        assert!(!docker.has_changes());
     }
 }
@@ -0,0 +1,94 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// buffer_size benchmarks the piece I/O of the content storage with the different
+// read and write buffer sizes, run it by `cargo bench -p dragonfly-client-storage`.
+
+use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_storage::content::Content;
+use dragonfly_client_util::digest::Algorithm;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+
+// PIECE_LENGTH is the length of the piece in the benchmark.
+const PIECE_LENGTH: u64 = 4 * 1024 * 1024;
+
+// PIECE_COUNT is the number of the pieces in the benchmark.
+const PIECE_COUNT: u64 = 16;
+
+// BUFFER_SIZES are the buffer sizes compared in the benchmark.
+const BUFFER_SIZES: [usize; 4] = [4 * 1024, 128 * 1024, 1024 * 1024, 8 * 1024 * 1024];
+
+// bench_buffer_size writes and reads the pieces of a task with the buffer size, and
+// returns the durations of writing and reading.
+async fn bench_buffer_size(buffer_size: usize) -> (Duration, Duration) {
+    let dir = tempdir::TempDir::new("content").unwrap();
+    let mut config = Config::default();
+    config.storage.write_buffer_size = buffer_size;
+    config.storage.read_buffer_size = buffer_size;
+    let content = Content::new(Arc::new(config), dir.path()).await.unwrap();
+    let piece = vec![0xa5; PIECE_LENGTH as usize];
+
+    let start = Instant::now();
+    for number in 0..PIECE_COUNT {
+        content
+            .write_piece(
+                "foo",
+                number * PIECE_LENGTH,
+                Algorithm::Crc32c,
+                &mut piece.as_slice(),
+            )
+            .await
+            .unwrap();
+    }
+    let write_duration = start.elapsed();
+
+    let start = Instant::now();
+    let mut buf = Vec::with_capacity(PIECE_LENGTH as usize);
+    for number in 0..PIECE_COUNT {
+        buf.clear();
+        content
+            .read_piece("foo", number * PIECE_LENGTH, PIECE_LENGTH, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+    }
+    let read_duration = start.elapsed();
+
+    (write_duration, read_duration)
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // Throughput in MiB/s of the content in the benchmark.
+    let mib = (PIECE_LENGTH * PIECE_COUNT) as f64 / (1024.0 * 1024.0);
+    for buffer_size in BUFFER_SIZES {
+        let (write_duration, read_duration) = runtime.block_on(bench_buffer_size(buffer_size));
+        println!(
+            "buffer size {:>8} bytes: write {:>8.1} MiB/s, read {:>8.1} MiB/s",
+            buffer_size,
+            mib / write_duration.as_secs_f64(),
+            mib / read_duration.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// digest benchmarks the digest algorithms of the pieces, run it by
+// `cargo bench -p dragonfly-client-storage --bench digest`.
+
+use dragonfly_client_util::digest::{Algorithm, Hasher};
+use std::time::{Duration, Instant};
+
+// PIECE_LENGTH is the length of the piece in the benchmark.
+const PIECE_LENGTH: usize = 4 * 1024 * 1024;
+
+// PIECE_COUNT is the number of the pieces in the benchmark.
+const PIECE_COUNT: usize = 64;
+
+// CHUNK_LENGTH is the length of the chunk updated to the hasher, which is the default
+// buffer size of the piece I/O.
+const CHUNK_LENGTH: usize = 128 * 1024;
+
+// ALGORITHMS are the digest algorithms compared in the benchmark.
+const ALGORITHMS: [Algorithm; 4] = [
+    Algorithm::Crc32c,
+    Algorithm::Blake3,
+    Algorithm::Sha256,
+    Algorithm::Sha512,
+];
+
+// bench_digest calculates the digests of the pieces by the algorithm chunk by chunk,
+// and returns the duration.
+fn bench_digest(algorithm: Algorithm, piece: &[u8]) -> Duration {
+    let start = Instant::now();
+    for _ in 0..PIECE_COUNT {
+        let mut hasher = Hasher::new(algorithm);
+        for chunk in piece.chunks(CHUNK_LENGTH) {
+            hasher.update(chunk);
+        }
+
+        std::hint::black_box(hasher.finalize());
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let piece: Vec<u8> = (0..PIECE_LENGTH).map(|i| (i * 31 % 251) as u8).collect();
+
+    // Throughput in MiB/s of the content in the benchmark.
+    let mib = (PIECE_LENGTH * PIECE_COUNT) as f64 / (1024.0 * 1024.0);
+    for algorithm in ALGORITHMS {
+        let name = algorithm.to_string();
+        let duration = bench_digest(algorithm, &piece);
+        println!(
+            "digest {:>6}: {:>8.1} MiB/s",
+            name,
+            mib / duration.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::metadata::{Piece, Task};
+use dragonfly_client_core::{
+    error::{ErrorType, ExternalError, OrErr},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// MAGIC is the magic number at the beginning of the task archive.
+pub const MAGIC: &[u8; 8] = b"DFTASK\0\0";
+
+// VERSION is the version of the task archive format. The version is bumped if the layout of
+// the archive or the header changes incompatibly, and the older versions are still readable.
+pub const VERSION: u32 = 1;
+
+// EXTENSION is the file extension of the task archive.
+pub const EXTENSION: &str = "dftask";
+
+// MAX_HEADER_LENGTH is the max length of the archive header, it prevents the corrupted
+// archive from allocating unbounded memory.
+const MAX_HEADER_LENGTH: u64 = 64 * 1024 * 1024;
+
+// TASK_ID_LENGTH is the length of the task id, which is the hex encoded sha256 or blake3
+// generated by the id generator.
+const TASK_ID_LENGTH: usize = 64;
+
+// Header is the header of the task archive, the layout of the archive is:
+//
+// | magic (8 bytes) | version (u32, big endian) | header length (u64, big endian) |
+// | header (json) | content of the pieces in the order of the header pieces |
+//
+// The tag and the application of the task are derived into the task id, so that the imported
+// task is found by the same download request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    // task is the metadata of the task.
+    pub task: Task,
+
+    // pieces is the metadata of the finished pieces, the pieces are ordered by the number.
+    pub pieces: Vec<Piece>,
+}
+
+// Header implements the header of the task archive.
+impl Header {
+    // validate validates the header before the task is imported. The task id is used as the
+    // path of the task content, so the task id not generated by the id generator is rejected,
+    // e.g. the task id with the path separators. The pieces must be within the content length
+    // of the task.
+    pub fn validate(&self) -> Result<()> {
+        let id = self.task.id.as_str();
+        if id.len() != TASK_ID_LENGTH || !id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Err(ExternalError::new(ErrorType::ValidationError)
+                .with_context(format!("invalid task id {:?}", id))
+                .into());
+        }
+
+        let content_length = self.task.content_length().unwrap_or_default();
+        for piece in self.pieces.iter() {
+            match piece.offset.checked_add(piece.length) {
+                Some(end) if end <= content_length => {}
+                _ => {
+                    return Err(ExternalError::new(ErrorType::ValidationError)
+                        .with_context(format!(
+                            "piece {} is out of the content length {}",
+                            piece.number, content_length
+                        ))
+                        .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// write_header writes the magic, the version and the header of the task archive.
+pub async fn write_header<W: AsyncWrite + Unpin>(writer: &mut W, header: &Header) -> Result<()> {
+    let header = serde_json::to_vec(header).or_err(ErrorType::SerializeError)?;
+
+    writer.write_all(MAGIC).await?;
+    writer.write_u32(VERSION).await?;
+    writer.write_u64(header.len() as u64).await?;
+    writer.write_all(&header).await?;
+    Ok(())
+}
+
+// read_header reads the header of the task archive, the archive with the unknown magic or the
+// unsupported version is rejected before the header is parsed.
+pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Header> {
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic).await?;
+    if &magic != MAGIC {
+        return Err(ExternalError::new(ErrorType::ParseError)
+            .with_context("invalid task archive magic")
+            .into());
+    }
+
+    let version = reader.read_u32().await?;
+    if version == 0 || version > VERSION {
+        return Err(ExternalError::new(ErrorType::ParseError)
+            .with_context(format!("unsupported task archive version {}", version))
+            .into());
+    }
+
+    let length = reader.read_u64().await?;
+    if length > MAX_HEADER_LENGTH {
+        return Err(ExternalError::new(ErrorType::ParseError)
+            .with_context(format!(
+                "task archive header length {} is too large",
+                length
+            ))
+            .into());
+    }
+
+    let mut header = vec![0; length as usize];
+    reader.read_exact(&mut header).await?;
+    Ok(serde_json::from_slice(&header).or_err(ErrorType::SerializeError)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_read_written_header() {
+        let header = Header {
+            task: Task {
+                id: "foo".to_string(),
+                piece_length: 4,
+                content_length: Some(4),
+                ..Default::default()
+            },
+            pieces: vec![Piece {
+                number: 0,
+                length: 4,
+                digest: "crc32c:e4ede1d6".to_string(),
+                ..Default::default()
+            }],
+        };
+
+        let mut archive = Vec::new();
+        write_header(&mut archive, &header).await.unwrap();
+        assert_eq!(&archive[..MAGIC.len()], MAGIC);
+        assert_eq!(read_header(&mut &archive[..]).await.unwrap(), header);
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_header() {
+        let mut archive = Vec::new();
+        write_header(
+            &mut archive,
+            &Header {
+                task: Task::default(),
+                pieces: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        // The unknown magic is rejected.
+        let mut invalid = archive.clone();
+        invalid[0] = b'X';
+        assert!(read_header(&mut &invalid[..]).await.is_err());
+
+        // The unsupported version is rejected.
+        let mut invalid = archive.clone();
+        invalid[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_be_bytes());
+        assert!(read_header(&mut &invalid[..]).await.is_err());
+
+        // The too large header is rejected.
+        let mut invalid = archive.clone();
+        invalid[MAGIC.len() + 4..MAGIC.len() + 12]
+            .copy_from_slice(&(MAX_HEADER_LENGTH + 1).to_be_bytes());
+        assert!(read_header(&mut &invalid[..]).await.is_err());
+
+        // The truncated header is rejected.
+        assert!(read_header(&mut &archive[..archive.len() - 1])
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn should_validate_header() {
+        let id = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+        let header = Header {
+            task: Task {
+                id: id.to_string(),
+                piece_length: 4,
+                content_length: Some(7),
+                ..Default::default()
+            },
+            pieces: vec![
+                Piece {
+                    number: 0,
+                    length: 4,
+                    ..Default::default()
+                },
+                Piece {
+                    number: 1,
+                    offset: 4,
+                    length: 3,
+                    ..Default::default()
+                },
+            ],
+        };
+        assert!(header.validate().is_ok());
+
+        // The task id that is not generated by the id generator is rejected.
+        for invalid_id in [
+            "",
+            "foo",
+            "../../../../etc/cron.d/foo",
+            format!("{}/..", &id[..61]).as_str(),
+            id.to_uppercase().as_str(),
+        ] {
+            let mut invalid = header.clone();
+            invalid.task.id = invalid_id.to_string();
+            assert!(
+                invalid.validate().is_err(),
+                "{} should be rejected",
+                invalid_id
+            );
+        }
+
+        // The piece out of the content length is rejected.
+        let mut invalid = header.clone();
+        invalid.pieces[1].length = 4;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = header.clone();
+        invalid.pieces[1].offset = u64::MAX;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = header.clone();
+        invalid.task.content_length = None;
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -16,12 +16,19 @@
 
 use dragonfly_api::common::v2::Range;
 use dragonfly_client_config::dfdaemon::Config;
-use dragonfly_client_core::Result;
+use dragonfly_client_core::{Error, Result};
+use dragonfly_client_util::digest::{Algorithm, Digest, Hasher};
 use std::cmp::{max, min};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{ready, Context, Poll};
 use tokio::fs::{self, File, OpenOptions};
-use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader, SeekFrom};
+use tokio::io::{
+    self, AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader, BufWriter, ReadBuf, SeekFrom,
+};
 use tokio_util::io::InspectReader;
 use tracing::{error, info, warn};
 
@@ -42,8 +49,97 @@ pub struct WritePieceResponse {
     // length is the length of the piece.
     pub length: u64,
 
-    // hash is the hash of the piece.
-    pub hash: String,
+    // digest is the digest of the piece.
+    pub digest: Digest,
+}
+
+// VerifyReader verifies the digest of the piece while the piece is read, then the piece
+// is hashed as it is read instead of a separate pass. If the digest is mismatched at
+// the end of the piece, the read fails with the corruption error.
+pub struct VerifyReader<R> {
+    // reader is the reader of the piece content.
+    reader: R,
+
+    // hasher is used to calculate the digest of the read content by the algorithm
+    // of the expected digest.
+    hasher: Option<Hasher>,
+
+    // piece_id is the id of the verified piece.
+    piece_id: String,
+
+    // expected_digest is the digest of the piece in the metadata.
+    expected_digest: Digest,
+
+    // on_corrupted is called once if the piece is corrupted.
+    on_corrupted: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+// VerifyReader implements the verify reader.
+impl<R> VerifyReader<R> {
+    // new returns a new verify reader.
+    pub fn new(
+        reader: R,
+        piece_id: String,
+        expected_digest: Digest,
+        on_corrupted: impl FnOnce() + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            reader,
+            hasher: Some(Hasher::new(expected_digest.algorithm())),
+            piece_id,
+            expected_digest,
+            on_corrupted: Some(Box::new(on_corrupted)),
+        }
+    }
+}
+
+// VerifyReader implements the AsyncRead.
+impl<R: AsyncRead + Unpin> AsyncRead for VerifyReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        // The empty read of the full buffer is not the end of the piece.
+        if buf.remaining() == 0 {
+            return Pin::new(&mut this.reader).poll_read(cx, buf);
+        }
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+        let bytes = &buf.filled()[filled..];
+        if !bytes.is_empty() {
+            if let Some(hasher) = this.hasher.as_mut() {
+                hasher.update(bytes);
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        // Verify the digest at the end of the piece, the truncated piece is
+        // also detected by the mismatched digest.
+        if let Some(hasher) = this.hasher.take() {
+            let digest = hasher.finalize();
+            if digest != this.expected_digest {
+                error!(
+                    "piece {} is corrupted, expected digest {}, but got {}",
+                    this.piece_id, this.expected_digest, digest
+                );
+
+                if let Some(on_corrupted) = this.on_corrupted.take() {
+                    on_corrupted();
+                }
+
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    Error::PieceCorrupted(this.piece_id.clone()),
+                )));
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
 }
 
 // Content implements the content storage.
@@ -65,6 +161,61 @@ impl Content {
         Ok(Content { config, dir })
     }
 
+    // preallocate_task preallocates the task content to the content length, then the
+    // lack of disk space is reported before the pieces are written. If the filesystem
+    // does not support the preallocation, the task content is extended without allocating.
+    pub fn preallocate_task(&self, task_id: &str, content_length: u64) -> Result<()> {
+        let task_path = self.dir.join(task_id);
+
+        let f = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(task_path.as_path())
+            .map_err(|err| {
+                error!("open {:?} failed: {}", task_path, err);
+                err
+            })?;
+
+        // The task content is not shrunk, it may have the written pieces.
+        if f.metadata()?.len() >= content_length {
+            return Ok(());
+        }
+
+        allocate_or_extend(&f, content_length, fallocate(&f, content_length)).map_err(|err| {
+            error!("preallocate {:?} failed: {}", task_path, err);
+            err
+        })?;
+
+        info!("preallocate {:?} to {} bytes", task_path, content_length);
+        Ok(())
+    }
+
+    // truncate_task truncates the task content to the length, it is used to remove the
+    // preallocated space beyond the downloaded content.
+    pub fn truncate_task(&self, task_id: &str, length: u64) -> Result<()> {
+        let task_path = self.dir.join(task_id);
+
+        let f = std::fs::OpenOptions::new()
+            .write(true)
+            .open(task_path.as_path())
+            .map_err(|err| {
+                error!("open {:?} failed: {}", task_path, err);
+                err
+            })?;
+
+        if f.metadata()?.len() > length {
+            f.set_len(length).map_err(|err| {
+                error!("truncate {:?} failed: {}", task_path, err);
+                err
+            })?;
+
+            info!("truncate {:?} to {} bytes", task_path, length);
+        }
+
+        Ok(())
+    }
+
     // hard_link_or_copy_task hard links or copies the task content to the destination.
     pub async fn hard_link_or_copy_task(
         &self,
@@ -184,8 +335,26 @@ impl Content {
                 err
             })?;
 
+        // Use a buffer to read the range.
         let range_reader = from_f.take(range.length);
-        Ok(range_reader)
+        Ok(BufReader::with_capacity(
+            self.config.storage.read_buffer_size,
+            range_reader,
+        ))
+    }
+
+    // task_size returns the size of the task content, it returns none if the task
+    // content does not exist.
+    pub async fn task_size(&self, task_id: &str) -> Result<Option<u64>> {
+        let task_path = self.dir.join(task_id);
+        match fs::metadata(task_path.as_path()).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                error!("stat {:?} failed: {}", task_path, err);
+                Err(err.into())
+            }
+        }
     }
 
     // delete_task deletes the task content.
@@ -225,7 +394,12 @@ impl Content {
                     error!("seek {:?} failed: {}", task_path, err);
                     err
                 })?;
-            return Ok(f.take(target_length));
+
+            // Use a buffer to read the piece.
+            return Ok(BufReader::with_capacity(
+                self.config.storage.read_buffer_size,
+                f.take(target_length),
+            ));
         }
 
         let mut f = File::open(task_path.as_path()).await.map_err(|err| {
@@ -237,14 +411,21 @@ impl Content {
             error!("seek {:?} failed: {}", task_path, err);
             err
         })?;
-        Ok(f.take(length))
+
+        // Use a buffer to read the piece.
+        Ok(BufReader::with_capacity(
+            self.config.storage.read_buffer_size,
+            f.take(length),
+        ))
     }
 
-    // write_piece writes the piece to the content.
+    // write_piece writes the piece to the content, and calculates the digest
+    // of the piece by the algorithm.
     pub async fn write_piece<R: AsyncRead + Unpin + ?Sized>(
         &self,
         task_id: &str,
         offset: u64,
+        algorithm: Algorithm,
         reader: &mut R,
     ) -> Result<WritePieceResponse> {
         let task_path = self.dir.join(task_id);
@@ -252,10 +433,10 @@ impl Content {
         // Use a buffer to read the piece.
         let reader = BufReader::with_capacity(self.config.storage.write_buffer_size, reader);
 
-        // Blake3 is used to calculate the hash of the piece.
-        let mut hasher = blake3::Hasher::new();
+        // Hasher is used to calculate the digest of the piece.
+        let mut hasher = Hasher::new(algorithm);
 
-        // InspectReader is used to calculate the hash of the piece.
+        // InspectReader is used to calculate the digest of the piece.
         let mut tee = InspectReader::new(reader, |bytes| {
             hasher.update(bytes);
         });
@@ -277,18 +458,84 @@ impl Content {
             err
         })?;
 
+        // Use a buffer to write the piece, the buffer is flushed after copying.
+        let mut f = BufWriter::with_capacity(self.config.storage.write_buffer_size, f);
+
         // Copy the piece to the file.
         let length = io::copy(&mut tee, &mut f).await.map_err(|err| {
             error!("copy {:?} failed: {}", task_path, err);
             err
         })?;
 
-        // Calculate the hash of the piece.
-        let hash = hasher.finalize();
-
+        // Calculate the digest of the piece.
         Ok(WritePieceResponse {
             length,
-            hash: base16ct::lower::encode_string(hash.as_bytes()),
+            digest: hasher.finalize(),
         })
     }
 }
+
+// fallocate allocates the disk space of the file to the length.
+#[cfg(target_os = "linux")]
+fn fallocate(f: &std::fs::File, length: u64) -> io::Result<()> {
+    let length = libc::off_t::try_from(length)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if unsafe { libc::fallocate(f.as_raw_fd(), 0, 0, length) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// fallocate is not supported on the platform other than linux.
+#[cfg(not(target_os = "linux"))]
+fn fallocate(_f: &std::fs::File, _length: u64) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// allocate_or_extend extends the file to the length by ftruncate if the filesystem
+// does not support fallocate, the other errors of fallocate are returned.
+fn allocate_or_extend(f: &std::fs::File, length: u64, allocated: io::Result<()>) -> io::Result<()> {
+    match allocated {
+        Err(err)
+            if err.kind() == io::ErrorKind::Unsupported
+                || matches!(
+                    err.raw_os_error(),
+                    Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS)
+                ) =>
+        {
+            warn!(
+                "fallocate is not supported, extend the file instead: {}",
+                err
+            );
+            f.set_len(length)
+        }
+        allocated => allocated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_extend_file_if_fallocate_is_not_supported() {
+        let dir = tempdir::TempDir::new("content").unwrap();
+        let f = std::fs::File::create(dir.path().join("foo")).unwrap();
+
+        // The file is extended if the filesystem returns EOPNOTSUPP.
+        allocate_or_extend(
+            &f,
+            4096,
+            Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP)),
+        )
+        .unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 4096);
+
+        // The other errors are returned, such as the lack of disk space.
+        let err = allocate_or_extend(&f, 8192, Err(io::Error::from_raw_os_error(libc::ENOSPC)))
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+        assert_eq!(f.metadata().unwrap().len(), 4096);
+    }
+}
@@ -15,16 +15,19 @@
  */
 
 use dragonfly_api::common::v2::Range;
-use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_config::dfdaemon::{Config, StorageCheck};
 use dragonfly_client_core::{Error, Result};
 use dragonfly_client_util::digest::{Algorithm, Digest};
 use reqwest::header::HeaderMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::AsyncRead;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tokio::fs::{self, File};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio_util::either::Either;
+use tracing::{error, info, warn};
 
+pub mod archive;
 pub mod content;
 pub mod metadata;
 pub mod storage_engine;
@@ -32,28 +35,54 @@ pub mod storage_engine;
 // DEFAULT_WAIT_FOR_PIECE_FINISHED_INTERVAL is the default interval for waiting for the piece to be finished.
 pub const DEFAULT_WAIT_FOR_PIECE_FINISHED_INTERVAL: Duration = Duration::from_millis(500);
 
+// CheckReport is the report of checking the integrity of the storage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CheckReport {
+    // checked_tasks is the number of the checked tasks.
+    pub checked_tasks: usize,
+
+    // deleted_tasks is the number of the inconsistent tasks deleted.
+    pub deleted_tasks: usize,
+
+    // deleted_pieces is the number of the inconsistent pieces deleted from the unfinished tasks.
+    pub deleted_pieces: usize,
+}
+
 // Storage is the storage of the task.
 pub struct Storage {
     // config is the configuration of the dfdaemon.
     config: Arc<Config>,
 
     // metadata implements the metadata storage.
-    metadata: metadata::Metadata,
+    metadata: Arc<metadata::Metadata>,
 
     // content implements the content storage.
     content: content::Content,
+
+    // digest_algorithm is the algorithm to calculate the digest of the downloaded piece.
+    digest_algorithm: Algorithm,
 }
 
 // Storage implements the storage.
 impl Storage {
     // new returns a new storage.
     pub async fn new(config: Arc<Config>, dir: &Path) -> Result<Self> {
-        let metadata = metadata::Metadata::new(config.clone(), dir)?;
+        let digest_algorithm = config
+            .storage
+            .digest_algorithm
+            .parse::<Algorithm>()
+            .map_err(|err| {
+                error!("parse digest algorithm failed: {}", err);
+                Error::Unknown(err)
+            })?;
+
+        let metadata = Arc::new(metadata::Metadata::new(config.clone(), dir)?);
         let content = content::Content::new(config.clone(), dir).await?;
         Ok(Storage {
             config,
             metadata,
             content,
+            digest_algorithm,
         })
     }
 
@@ -84,12 +113,43 @@ impl Storage {
         content_length: Option<u64>,
         response_header: Option<HeaderMap>,
     ) -> Result<metadata::Task> {
-        self.metadata
-            .download_task_started(id, piece_length, content_length, response_header)
+        let task = self.metadata.download_task_started(
+            id,
+            piece_length,
+            content_length,
+            response_header,
+        )?;
+
+        // Preallocate the task content as soon as the content length is known.
+        if self.config.storage.preallocate && !task.is_finished() {
+            if let Some(content_length) = task.content_length {
+                self.content.preallocate_task(id, content_length)?;
+            }
+        }
+
+        Ok(task)
     }
 
     // download_task_finished updates the metadata of the task when the task downloads finished.
     pub fn download_task_finished(&self, id: &str) -> Result<metadata::Task> {
+        // If the downloaded content is smaller than the preallocated content length,
+        // truncate the task content to the end of the last piece.
+        let preallocated = self.config.storage.preallocate
+            && self
+                .metadata
+                .get_task(id)?
+                .is_some_and(|task| task.content_length.is_some());
+        if preallocated {
+            let length = self
+                .metadata
+                .get_pieces(id)?
+                .iter()
+                .map(|piece| piece.offset + piece.length)
+                .max()
+                .unwrap_or_default();
+            self.content.truncate_task(id, length)?;
+        }
+
         self.metadata.download_task_finished(id)
     }
 
@@ -113,6 +173,16 @@ impl Storage {
         self.metadata.upload_task_finished(id)
     }
 
+    // pin_task protects the task from gc.
+    pub fn pin_task(&self, id: &str) -> Result<metadata::Task> {
+        self.metadata.pin_task(id)
+    }
+
+    // unpin_task removes the protection of the task from gc.
+    pub fn unpin_task(&self, id: &str) -> Result<metadata::Task> {
+        self.metadata.unpin_task(id)
+    }
+
     // get_task returns the task metadata.
     pub fn get_task(&self, id: &str) -> Result<Option<metadata::Task>> {
         self.metadata.get_task(id)
@@ -131,6 +201,290 @@ impl Storage {
         Ok(())
     }
 
+    // delete_task_with_force deletes the task like delete_task, but the pinned task is
+    // deleted only if force is true.
+    pub async fn delete_task_with_force(&self, id: &str, force: bool) -> Result<()> {
+        let task = self
+            .metadata
+            .get_task(id)?
+            .ok_or_else(|| Error::TaskNotFound(id.to_string()))?;
+
+        if task.is_pinned() && !force {
+            return Err(Error::TaskPinned(id.to_string()));
+        }
+
+        self.delete_task(id).await
+    }
+
+    // check checks the integrity of the tasks, it is used at startup to repair the storage
+    // after the crash. The finished task is deleted if its content is shorter than the content
+    // length, and the finished piece of the unfinished task is deleted if it is beyond the end of
+    // the content. In full mode, the digests of the finished pieces are also verified, the piece
+    // with the mismatched digest deletes the finished task or itself of the unfinished task.
+    pub async fn check(&self, mode: StorageCheck) -> Result<CheckReport> {
+        let mut report = CheckReport::default();
+        if mode == StorageCheck::Off {
+            return Ok(report);
+        }
+
+        let start = Instant::now();
+        for task in self.metadata.get_tasks()? {
+            report.checked_tasks += 1;
+
+            // The empty task may have no content.
+            let size = self.content.task_size(task.id.as_str()).await?.unwrap_or(0);
+            if task.is_finished() {
+                let truncated = task
+                    .content_length
+                    .is_some_and(|content_length| size < content_length);
+                if truncated {
+                    warn!("task {} content is truncated to {} bytes", task.id, size);
+                    self.delete_inconsistent_task(task.id.as_str()).await?;
+                    report.deleted_tasks += 1;
+                    continue;
+                }
+
+                // The quick check of the finished task only stats the content.
+                if mode == StorageCheck::Quick {
+                    continue;
+                }
+            }
+
+            for piece in self.metadata.get_pieces(task.id.as_str())? {
+                if !piece.is_finished() {
+                    continue;
+                }
+
+                let consistent = piece.offset + piece.length <= size
+                    && (mode == StorageCheck::Quick
+                        || self.verify_piece(task.id.as_str(), &piece).await);
+                if consistent {
+                    continue;
+                }
+
+                warn!(
+                    "piece {} is inconsistent with the content",
+                    self.piece_id(task.id.as_str(), piece.number)
+                );
+
+                // The inconsistent finished task is deleted, then it is downloaded
+                // again by the next download of the task.
+                if task.is_finished() {
+                    self.delete_inconsistent_task(task.id.as_str()).await?;
+                    report.deleted_tasks += 1;
+                    break;
+                }
+
+                self.metadata.delete_piece(task.id.as_str(), piece.number)?;
+                report.deleted_pieces += 1;
+            }
+        }
+
+        info!(
+            "storage check {} finished in {:?}, checked {} tasks, deleted {} tasks and {} pieces",
+            mode,
+            start.elapsed(),
+            report.checked_tasks,
+            report.deleted_tasks,
+            report.deleted_pieces
+        );
+        Ok(report)
+    }
+
+    // verify_piece verifies the digest of the finished piece by reading the piece content,
+    // the piece with the invalid digest is skipped.
+    async fn verify_piece(&self, task_id: &str, piece: &metadata::Piece) -> bool {
+        let expected_digest = match piece.digest.parse::<Digest>() {
+            Ok(expected_digest) => expected_digest,
+            Err(err) => {
+                warn!("skip verifying piece {}: {}", piece.number, err);
+                return true;
+            }
+        };
+
+        let reader = match self
+            .content
+            .read_piece(task_id, piece.offset, piece.length, None)
+            .await
+        {
+            Ok(reader) => reader,
+            Err(_) => return false,
+        };
+
+        let mut reader = content::VerifyReader::new(
+            reader,
+            self.piece_id(task_id, piece.number),
+            expected_digest,
+            || {},
+        );
+        io::copy(&mut reader, &mut io::sink()).await.is_ok()
+    }
+
+    // delete_inconsistent_task deletes the task metadatas, piece metadatas and task content,
+    // the content of the inconsistent task may not exist.
+    async fn delete_inconsistent_task(&self, id: &str) -> Result<()> {
+        self.metadata.delete_task(id)?;
+        self.metadata.delete_pieces(id)?;
+        if let Err(err) = self.content.delete_task(id).await {
+            warn!("delete inconsistent task {} content failed: {}", id, err);
+        }
+
+        Ok(())
+    }
+
+    // export_task exports the finished task to the task archive in the directory, the archive
+    // contains the metadata of the task and its pieces, followed by the content of the pieces.
+    // It returns the path of the task archive.
+    pub async fn export_task(&self, id: &str, dir: &Path) -> Result<PathBuf> {
+        let task = self
+            .metadata
+            .get_task(id)?
+            .ok_or_else(|| Error::TaskNotFound(id.to_string()))?;
+        if !task.is_finished() {
+            return Err(Error::InvalidState("task is not finished".to_string()));
+        }
+
+        let mut pieces = self.metadata.get_pieces(id)?;
+        pieces.retain(|piece| piece.is_finished());
+        pieces.sort_by_key(|piece| piece.number);
+
+        // The archive is written to the temporary path and renamed after it is complete,
+        // then the incomplete archive is never imported.
+        fs::create_dir_all(dir).await?;
+        let path = dir.join(format!("{}.{}", id, archive::EXTENSION));
+        let temp_path = dir.join(format!("{}.{}.tmp", id, archive::EXTENSION));
+        let header = archive::Header { task, pieces };
+        if let Err(err) = self.write_task_archive(&header, temp_path.as_path()).await {
+            error!("export task {} failed: {}", id, err);
+            fs::remove_file(temp_path.as_path())
+                .await
+                .unwrap_or_else(|err| warn!("remove {:?} failed: {}", temp_path, err));
+            return Err(err);
+        }
+
+        fs::rename(temp_path.as_path(), path.as_path()).await?;
+        info!("export task {} to {:?}", id, path);
+        Ok(path)
+    }
+
+    // write_task_archive writes the header and the content of the pieces to the task archive.
+    async fn write_task_archive(&self, header: &archive::Header, path: &Path) -> Result<()> {
+        let task_id = header.task.id.as_str();
+        let mut writer = BufWriter::with_capacity(
+            self.config.storage.write_buffer_size,
+            File::create(path).await?,
+        );
+
+        archive::write_header(&mut writer, header).await?;
+        for piece in header.pieces.iter() {
+            let mut reader = self
+                .content
+                .read_piece(task_id, piece.offset, piece.length, None)
+                .await?;
+
+            // The piece content is truncated if it is shorter than the piece length.
+            if io::copy(&mut reader, &mut writer).await? != piece.length {
+                error!(
+                    "piece {} content is truncated",
+                    self.piece_id(task_id, piece.number)
+                );
+                return Err(Error::InvalidContentLength);
+            }
+        }
+
+        writer.flush().await?;
+        Ok(())
+    }
+
+    // import_task imports the task from the task archive, and the content of the pieces is
+    // verified by the digests of the pieces. If the task is finished in the storage, the
+    // task is not imported again, and the unfinished task is replaced by the imported task.
+    pub async fn import_task(&self, path: &Path) -> Result<metadata::Task> {
+        let mut reader = BufReader::with_capacity(
+            self.config.storage.read_buffer_size,
+            File::open(path).await.map_err(|err| {
+                error!("open {:?} failed: {}", path, err);
+                err
+            })?,
+        );
+
+        let header = archive::read_header(&mut reader).await?;
+        header.validate()?;
+        let task_id = header.task.id.as_str();
+        if let Some(task) = self.metadata.get_task(task_id)? {
+            if task.is_finished() {
+                info!("task {} is already finished, skip importing", task_id);
+                return Ok(task);
+            }
+
+            self.delete_inconsistent_task(task_id).await?;
+        }
+
+        // The task is finished after all the pieces are imported, and the imported task
+        // is deleted if any piece fails.
+        self.metadata.import_task_started(&header.task)?;
+        for piece in header.pieces.iter() {
+            if let Err(err) = self.import_piece(task_id, piece, &mut reader).await {
+                error!(
+                    "import piece {} failed: {}",
+                    self.piece_id(task_id, piece.number),
+                    err
+                );
+                self.delete_inconsistent_task(task_id).await?;
+                return Err(err);
+            }
+        }
+
+        let task = self.metadata.download_task_finished(task_id)?;
+        info!("import task {} from {:?}", task_id, path);
+        Ok(task)
+    }
+
+    // import_piece writes the content of the piece from the task archive, the digest of the
+    // piece is calculated by the algorithm declared in the digest of the exported piece.
+    async fn import_piece<R: AsyncRead + Unpin>(
+        &self,
+        task_id: &str,
+        piece: &metadata::Piece,
+        reader: &mut R,
+    ) -> Result<()> {
+        let expected_digest = piece.digest.parse::<Digest>().map_err(|err| {
+            error!("parse piece digest failed: {}", err);
+            Error::PieceDigestMismatch
+        })?;
+
+        self.metadata
+            .download_piece_started(task_id, piece.number)?;
+        let response = self
+            .content
+            .write_piece(
+                task_id,
+                piece.offset,
+                expected_digest.algorithm(),
+                &mut (&mut *reader).take(piece.length),
+            )
+            .await?;
+
+        // Check the length and the digest of the piece.
+        if response.length != piece.length {
+            return Err(Error::InvalidContentLength);
+        }
+
+        if expected_digest != response.digest {
+            return Err(Error::PieceDigestMismatch);
+        }
+
+        self.metadata.download_piece_finished(
+            task_id,
+            piece.number,
+            piece.offset,
+            piece.length,
+            response.digest.to_string().as_str(),
+            None,
+        )?;
+        Ok(())
+    }
+
     // create_persistent_cache_task creates a new persistent cache task.
     pub fn create_persistent_cache_task(
         &self,
@@ -212,15 +566,17 @@ impl Storage {
         length: u64,
         reader: &mut R,
     ) -> Result<metadata::Piece> {
-        let response = self.content.write_piece(task_id, offset, reader).await?;
-        let digest = Digest::new(Algorithm::Blake3, response.hash);
+        let response = self
+            .content
+            .write_piece(task_id, offset, self.digest_algorithm, reader)
+            .await?;
 
         self.metadata.download_piece_finished(
             task_id,
             number,
             offset,
             length,
-            digest.to_string().as_str(),
+            response.digest.to_string().as_str(),
             None,
         )
     }
@@ -235,12 +591,21 @@ impl Storage {
         parent_id: &str,
         reader: &mut R,
     ) -> Result<metadata::Piece> {
-        let response = self.content.write_piece(task_id, offset, reader).await?;
+        // The digest of the piece is calculated by the algorithm declared in the
+        // expected digest, then the peers with different algorithms interoperate.
+        let expected_digest = expected_digest.parse::<Digest>().map_err(|err| {
+            error!("parse piece digest failed: {}", err);
+            Error::PieceDigestMismatch
+        })?;
+
+        let response = self
+            .content
+            .write_piece(task_id, offset, expected_digest.algorithm(), reader)
+            .await?;
         let length = response.length;
-        let digest = Digest::new(Algorithm::Blake3, response.hash);
 
         // Check the digest of the piece.
-        if expected_digest != digest.to_string() {
+        if expected_digest != response.digest {
             return Err(Error::PieceDigestMismatch);
         }
 
@@ -249,7 +614,7 @@ impl Storage {
             number,
             offset,
             length,
-            digest.to_string().as_str(),
+            response.digest.to_string().as_str(),
             Some(parent_id.to_string()),
         )
     }
@@ -260,7 +625,9 @@ impl Storage {
     }
 
     // upload_piece updates the metadata of the piece and
-    // returns the data of the piece.
+    // returns the data of the piece. If the verification on upload is enabled, the digest
+    // of the whole piece is verified while the piece is read, and the corrupted piece is
+    // invalidated.
     pub async fn upload_piece(
         &self,
         task_id: &str,
@@ -280,6 +647,10 @@ impl Storage {
             return Err(err);
         }
 
+        // The range of the piece can not be verified, because the digest is
+        // calculated by the whole piece.
+        let verify = self.config.storage.verify_on_upload && range.is_none();
+
         // Get the piece metadata and return the content of the piece.
         match self.metadata.get_piece(task_id, number)? {
             Some(piece) => {
@@ -294,7 +665,35 @@ impl Storage {
 
                         // Finish uploading the piece.
                         self.metadata.upload_piece_finished(task_id, number)?;
-                        Ok(reader)
+                        if !verify {
+                            return Ok(Either::Left(reader));
+                        }
+
+                        // If the digest in the metadata is invalid, the piece is uploaded
+                        // without the verification.
+                        let expected_digest = match piece.digest.parse::<Digest>() {
+                            Ok(expected_digest) => expected_digest,
+                            Err(err) => {
+                                warn!("skip verifying piece {}: {}", piece.number, err);
+                                return Ok(Either::Left(reader));
+                            }
+                        };
+
+                        // Invalidate the corrupted piece, then the piece is not uploaded
+                        // anymore and is downloaded again by the next download of the task.
+                        let metadata = self.metadata.clone();
+                        let task_id = task_id.to_string();
+                        Ok(Either::Right(content::VerifyReader::new(
+                            reader,
+                            self.piece_id(task_id.as_str(), number),
+                            expected_digest,
+                            move || {
+                                if let Err(err) = metadata.upload_piece_corrupted(&task_id, number)
+                                {
+                                    error!("invalidate corrupted piece failed: {}", err);
+                                }
+                            },
+                        )))
                     }
                     Err(err) => {
                         // Failed uploading the task.
@@ -332,6 +731,13 @@ impl Storage {
         self.metadata.piece_id(task_id, number)
     }
 
+    // metadata_statistics returns the statistics of the metadata store.
+    pub fn metadata_statistics(
+        &self,
+    ) -> Result<Vec<storage_engine::rocksdb::ColumnFamilyStatistics>> {
+        self.metadata.statistics()
+    }
+
     // wait_for_piece_finished waits for the piece to be finished.
     async fn wait_for_piece_finished(&self, task_id: &str, number: u32) -> Result<metadata::Piece> {
         // Initialize the timeout of piece.
@@ -367,3 +773,577 @@ impl Storage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    // FOO_TASK_ID, BAR_TASK_ID, BAZ_TASK_ID and QUX_TASK_ID are the task ids generated by the
+    // id generator, which are accepted by the task archive.
+    const FOO_TASK_ID: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+    const BAR_TASK_ID: &str = "fcde2b2edba56bf408601fb721fe9b5c338d10ee429ea04fae5511b68fbf8fb9";
+    const BAZ_TASK_ID: &str = "baa5a0964d3320fbc0c6a922140453c8513ea24ab8fd0577034804a967248096";
+    const QUX_TASK_ID: &str = "21f58d27f827d295ffcd860c65045685e3baf1ad4506caa0140113b316647534";
+
+    #[tokio::test]
+    async fn should_invalidate_corrupted_piece_on_upload() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let mut config = Config::default();
+        config.storage.verify_on_upload = true;
+        let storage = Storage::new(Arc::new(config), dir.path()).await.unwrap();
+
+        // Download the piece of the task.
+        storage
+            .download_task_started("foo", 4, Some(4), None)
+            .unwrap();
+        storage.download_piece_started("foo", 0).await.unwrap();
+        storage
+            .download_piece_from_source_finished("foo", 0, 0, 4, &mut &b"data"[..])
+            .await
+            .unwrap();
+
+        // The intact piece is uploaded.
+        let mut content = Vec::new();
+        storage
+            .upload_piece("foo", 0, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, b"data");
+
+        // Corrupt the piece on disk, then the upload fails with the corruption error.
+        tokio::fs::write(dir.path().join("content").join("foo"), b"dmta")
+            .await
+            .unwrap();
+        let err = storage
+            .upload_piece("foo", 0, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref::<Error>()),
+            Some(Error::PieceCorrupted(id)) if id == "foo-0"
+        ));
+
+        // The corrupted piece is invalidated.
+        assert!(storage.get_piece("foo", 0).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_fail_finished_task_with_corrupted_piece_on_upload() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let mut config = Config::default();
+        config.storage.verify_on_upload = true;
+        let storage = Storage::new(Arc::new(config), dir.path()).await.unwrap();
+
+        // Download the task.
+        storage
+            .download_task_started("foo", 4, Some(4), None)
+            .unwrap();
+        storage.download_piece_started("foo", 0).await.unwrap();
+        storage
+            .download_piece_from_source_finished("foo", 0, 0, 4, &mut &b"data"[..])
+            .await
+            .unwrap();
+        storage.download_task_finished("foo").unwrap();
+        assert!(storage.get_task("foo").unwrap().unwrap().is_finished());
+
+        // Corrupt the piece on disk, then upload the piece.
+        tokio::fs::write(dir.path().join("content").join("foo"), b"dmta")
+            .await
+            .unwrap();
+        assert!(storage
+            .upload_piece("foo", 0, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .await
+            .is_err());
+
+        // The task is not finished anymore, and it is failed.
+        let task = storage.get_task("foo").unwrap().unwrap();
+        assert!(!task.is_finished());
+        assert!(task.is_failed());
+        assert!(storage.get_piece("foo", 0).unwrap().is_none());
+
+        // The task is started again by the next download.
+        let task = storage
+            .download_task_started("foo", 4, Some(4), None)
+            .unwrap();
+        assert!(!task.is_finished());
+        assert!(!task.is_failed());
+    }
+
+    #[tokio::test]
+    async fn should_calculate_piece_digest_by_algorithm() {
+        for algorithm in ["sha256", "sha512", "crc32c", "blake3"] {
+            let dir = tempdir::TempDir::new("storage").unwrap();
+            let mut config = Config::default();
+            config.storage.digest_algorithm = algorithm.to_string();
+            config.storage.verify_on_upload = true;
+            let storage = Storage::new(Arc::new(config), dir.path()).await.unwrap();
+
+            storage
+                .download_task_started("foo", 4, Some(8), None)
+                .unwrap();
+            storage.download_piece_started("foo", 0).await.unwrap();
+            let piece = storage
+                .download_piece_from_source_finished("foo", 0, 0, 4, &mut &b"data"[..])
+                .await
+                .unwrap();
+            let expected_digest = dragonfly_client_util::digest::calculate(
+                algorithm.parse::<Algorithm>().unwrap(),
+                b"data",
+            );
+            assert_eq!(piece.digest, expected_digest.to_string());
+
+            // The piece is verified by the algorithm of its digest when uploading.
+            let mut content = Vec::new();
+            storage
+                .upload_piece("foo", 0, None)
+                .await
+                .unwrap()
+                .read_to_end(&mut content)
+                .await
+                .unwrap();
+            assert_eq!(content, b"data");
+        }
+    }
+
+    #[tokio::test]
+    async fn should_verify_remote_piece_by_declared_algorithm() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        storage
+            .download_task_started("foo", 4, Some(8), None)
+            .unwrap();
+
+        // The digest of the remote peer declares an algorithm different from the local one.
+        let expected_digest =
+            dragonfly_client_util::digest::calculate(Algorithm::Crc32c, b"data").to_string();
+        storage.download_piece_started("foo", 0).await.unwrap();
+        let piece = storage
+            .download_piece_from_remote_peer_finished(
+                "foo",
+                0,
+                0,
+                expected_digest.as_str(),
+                "bar",
+                &mut &b"data"[..],
+            )
+            .await
+            .unwrap();
+        assert_eq!(piece.digest, expected_digest);
+
+        // The mismatched and the invalid digests are rejected.
+        for expected_digest in ["crc32c:00000000", "foo"] {
+            storage.download_piece_started("foo", 1).await.unwrap();
+            assert!(matches!(
+                storage
+                    .download_piece_from_remote_peer_finished(
+                        "foo",
+                        1,
+                        4,
+                        expected_digest,
+                        "bar",
+                        &mut &b"data"[..],
+                    )
+                    .await,
+                Err(Error::PieceDigestMismatch)
+            ));
+        }
+
+        // The invalid algorithm of the storage is rejected.
+        let mut config = Config::default();
+        config.storage.digest_algorithm = "md5".to_string();
+        assert!(Storage::new(Arc::new(config), dir.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_delete_pinned_task_without_force() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        storage
+            .download_task_started("foo", 4, Some(4), None)
+            .unwrap();
+        storage.pin_task("foo").unwrap();
+
+        // The pinned task is not deleted without force.
+        assert!(matches!(
+            storage.delete_task_with_force("foo", false).await,
+            Err(Error::TaskPinned(id)) if id == "foo"
+        ));
+        assert!(storage.get_task("foo").unwrap().is_some());
+
+        // The pinned task is deleted with force.
+        storage.delete_task_with_force("foo", true).await.unwrap();
+        assert!(storage.get_task("foo").unwrap().is_none());
+
+        // The unpinned task is deleted without force.
+        storage
+            .download_task_started("bar", 4, Some(4), None)
+            .unwrap();
+        storage.delete_task_with_force("bar", false).await.unwrap();
+        assert!(storage.get_task("bar").unwrap().is_none());
+        assert!(matches!(
+            storage.delete_task_with_force("bar", false).await,
+            Err(Error::TaskNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_preallocate_task_content() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let mut config = Config::default();
+        config.storage.preallocate = true;
+        let storage = Storage::new(Arc::new(config), dir.path()).await.unwrap();
+        let task_path = dir.path().join("content").join("foo");
+
+        // The task content is not preallocated without the content length.
+        storage.download_task_started("foo", 4, None, None).unwrap();
+        assert!(!task_path.exists());
+
+        // The task content is preallocated to the content length.
+        storage
+            .download_task_started("foo", 4, Some(12), None)
+            .unwrap();
+        assert_eq!(std::fs::metadata(&task_path).unwrap().len(), 12);
+
+        // The task content is truncated to the downloaded pieces when the task finished.
+        for (number, offset) in [(0, 0), (1, 4)] {
+            storage.download_piece_started("foo", number).await.unwrap();
+            storage
+                .download_piece_from_source_finished("foo", number, offset, 4, &mut &b"data"[..])
+                .await
+                .unwrap();
+        }
+        storage.download_task_finished("foo").unwrap();
+        assert_eq!(std::fs::read(&task_path).unwrap(), b"datadata");
+
+        // The finished task content is not preallocated again.
+        storage
+            .download_task_started("foo", 4, Some(12), None)
+            .unwrap();
+        assert_eq!(std::fs::metadata(&task_path).unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn should_not_preallocate_task_content_by_default() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+
+        storage
+            .download_task_started("foo", 4, Some(12), None)
+            .unwrap();
+        assert!(!dir.path().join("content").join("foo").exists());
+    }
+
+    // download_task downloads the pieces of the task, the task is finished if
+    // all the pieces are downloaded.
+    async fn download_task(storage: &Storage, id: &str, pieces: &[&[u8]], finished: bool) {
+        let content_length = pieces.iter().map(|piece| piece.len() as u64).sum();
+        storage
+            .download_task_started(id, 4, Some(content_length), None)
+            .unwrap();
+
+        let mut offset = 0;
+        for (number, piece) in pieces.iter().enumerate() {
+            storage
+                .download_piece_started(id, number as u32)
+                .await
+                .unwrap();
+            storage
+                .download_piece_from_source_finished(
+                    id,
+                    number as u32,
+                    offset,
+                    piece.len() as u64,
+                    &mut &piece[..],
+                )
+                .await
+                .unwrap();
+            offset += piece.len() as u64;
+        }
+
+        if finished {
+            storage.download_task_finished(id).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn should_repair_truncated_tasks_by_quick_check() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        let content_dir = dir.path().join("content");
+
+        download_task(&storage, "foo", &[b"data", b"data"], true).await;
+        download_task(&storage, "bar", &[b"data", b"data"], true).await;
+        download_task(&storage, "baz", &[b"data", b"data"], false).await;
+
+        // Truncate the finished task and the unfinished task.
+        std::fs::File::options()
+            .write(true)
+            .open(content_dir.join("foo"))
+            .unwrap()
+            .set_len(6)
+            .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(content_dir.join("baz"))
+            .unwrap()
+            .set_len(6)
+            .unwrap();
+
+        // Nothing is checked if the check is off.
+        assert_eq!(
+            storage.check(StorageCheck::Off).await.unwrap(),
+            CheckReport::default()
+        );
+        assert!(storage.get_task("foo").unwrap().is_some());
+
+        assert_eq!(
+            storage.check(StorageCheck::Quick).await.unwrap(),
+            CheckReport {
+                checked_tasks: 3,
+                deleted_tasks: 1,
+                deleted_pieces: 1,
+            }
+        );
+
+        // The truncated finished task is deleted with its pieces and content.
+        assert!(storage.get_task("foo").unwrap().is_none());
+        assert!(storage.get_pieces("foo").unwrap().is_empty());
+        assert!(!content_dir.join("foo").exists());
+
+        // The intact finished task is kept.
+        assert!(storage.get_task("bar").unwrap().is_some());
+        assert_eq!(storage.get_pieces("bar").unwrap().len(), 2);
+
+        // The truncated piece of the unfinished task is deleted.
+        assert!(storage.get_task("baz").unwrap().is_some());
+        assert!(storage.get_piece("baz", 0).unwrap().is_some());
+        assert!(storage.get_piece("baz", 1).unwrap().is_none());
+
+        // The repaired storage is consistent.
+        assert_eq!(
+            storage.check(StorageCheck::Quick).await.unwrap(),
+            CheckReport {
+                checked_tasks: 2,
+                deleted_tasks: 0,
+                deleted_pieces: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn should_repair_corrupted_tasks_by_full_check() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        let content_dir = dir.path().join("content");
+
+        download_task(&storage, "foo", &[b"data", b"data"], true).await;
+        download_task(&storage, "bar", &[b"data", b"data"], false).await;
+        download_task(&storage, "baz", &[], true).await;
+
+        // Corrupt the contents without changing the sizes.
+        std::fs::write(content_dir.join("foo"), b"datadmta").unwrap();
+        std::fs::write(content_dir.join("bar"), b"dmtadata").unwrap();
+
+        // The corrupted contents are not detected by the quick check.
+        assert_eq!(
+            storage.check(StorageCheck::Quick).await.unwrap(),
+            CheckReport {
+                checked_tasks: 3,
+                deleted_tasks: 0,
+                deleted_pieces: 0,
+            }
+        );
+
+        assert_eq!(
+            storage.check(StorageCheck::Full).await.unwrap(),
+            CheckReport {
+                checked_tasks: 3,
+                deleted_tasks: 1,
+                deleted_pieces: 1,
+            }
+        );
+
+        // The corrupted finished task is deleted.
+        assert!(storage.get_task("foo").unwrap().is_none());
+        assert!(!content_dir.join("foo").exists());
+
+        // The corrupted piece of the unfinished task is deleted.
+        assert!(storage.get_piece("bar", 0).unwrap().is_none());
+        assert!(storage.get_piece("bar", 1).unwrap().is_some());
+
+        // The empty finished task without content is kept.
+        assert!(storage.get_task("baz").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn should_import_exported_task() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        download_task(&storage, FOO_TASK_ID, &[b"data", b"dat"], true).await;
+        download_task(&storage, BAR_TASK_ID, &[b"data"], false).await;
+        download_task(&storage, BAZ_TASK_ID, &[], true).await;
+        storage.pin_task(FOO_TASK_ID).unwrap();
+
+        // The unfinished task is not exported.
+        let archive_dir = tempdir::TempDir::new("archive").unwrap();
+        assert!(matches!(
+            storage.export_task(BAR_TASK_ID, archive_dir.path()).await,
+            Err(Error::InvalidState(_))
+        ));
+        assert!(matches!(
+            storage.export_task(QUX_TASK_ID, archive_dir.path()).await,
+            Err(Error::TaskNotFound(_))
+        ));
+
+        let foo_path = storage
+            .export_task(FOO_TASK_ID, archive_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(
+            foo_path,
+            archive_dir.path().join(format!("{}.dftask", FOO_TASK_ID))
+        );
+        let baz_path = storage
+            .export_task(BAZ_TASK_ID, archive_dir.path())
+            .await
+            .unwrap();
+
+        // The exported tasks are imported into another storage.
+        let other_dir = tempdir::TempDir::new("storage").unwrap();
+        let other = Storage::new(Arc::new(Config::default()), other_dir.path())
+            .await
+            .unwrap();
+        let task = other.import_task(foo_path.as_path()).await.unwrap();
+        let expected_task = storage.get_task(FOO_TASK_ID).unwrap().unwrap();
+        assert!(task.is_finished());
+        assert!(task.is_pinned());
+        assert_eq!(task.piece_length, expected_task.piece_length);
+        assert_eq!(task.content_length, Some(7));
+        assert_eq!(
+            std::fs::read(other_dir.path().join("content").join(FOO_TASK_ID)).unwrap(),
+            b"datadat"
+        );
+
+        let pieces = other.get_pieces(FOO_TASK_ID).unwrap();
+        let expected_pieces = storage.get_pieces(FOO_TASK_ID).unwrap();
+        assert_eq!(pieces.len(), 2);
+        for (piece, expected_piece) in pieces.iter().zip(expected_pieces.iter()) {
+            assert!(piece.is_finished());
+            assert_eq!(piece.number, expected_piece.number);
+            assert_eq!(piece.offset, expected_piece.offset);
+            assert_eq!(piece.length, expected_piece.length);
+            assert_eq!(piece.digest, expected_piece.digest);
+        }
+
+        // The imported task is readable by the piece.
+        let mut content = Vec::new();
+        other
+            .upload_piece(FOO_TASK_ID, 1, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, b"dat");
+
+        // The empty task is imported without content.
+        let task = other.import_task(baz_path.as_path()).await.unwrap();
+        assert!(task.is_finished());
+        assert!(task.is_empty());
+
+        // The finished task is not imported again.
+        let created_at = other.get_task(FOO_TASK_ID).unwrap().unwrap().created_at;
+        let task = other.import_task(foo_path.as_path()).await.unwrap();
+        assert_eq!(task.created_at, created_at);
+    }
+
+    #[tokio::test]
+    async fn should_not_import_corrupted_task() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+        download_task(&storage, FOO_TASK_ID, &[b"data", b"data"], true).await;
+        let path = storage.export_task(FOO_TASK_ID, dir.path()).await.unwrap();
+
+        // Corrupt the content of the last piece in the archive.
+        let mut archive = std::fs::read(path.as_path()).unwrap();
+        let length = archive.len();
+        archive[length - 1] = b'b';
+        std::fs::write(path.as_path(), &archive).unwrap();
+
+        let other_dir = tempdir::TempDir::new("storage").unwrap();
+        let other = Storage::new(Arc::new(Config::default()), other_dir.path())
+            .await
+            .unwrap();
+        assert!(matches!(
+            other.import_task(path.as_path()).await,
+            Err(Error::PieceDigestMismatch)
+        ));
+
+        // The partially imported task is deleted.
+        assert!(other.get_task(FOO_TASK_ID).unwrap().is_none());
+        assert!(other.get_pieces(FOO_TASK_ID).unwrap().is_empty());
+        assert!(!other_dir.path().join("content").join(FOO_TASK_ID).exists());
+
+        // The truncated archive is rejected.
+        std::fs::write(path.as_path(), &archive[..length - 2]).unwrap();
+        assert!(other.import_task(path.as_path()).await.is_err());
+        assert!(other.get_task(FOO_TASK_ID).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_not_verify_piece_on_upload_by_default() {
+        let dir = tempdir::TempDir::new("storage").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+
+        storage
+            .download_task_started("foo", 4, Some(4), None)
+            .unwrap();
+        storage.download_piece_started("foo", 0).await.unwrap();
+        storage
+            .download_piece_from_source_finished("foo", 0, 0, 4, &mut &b"data"[..])
+            .await
+            .unwrap();
+
+        // The corrupted piece is uploaded without the verification.
+        tokio::fs::write(dir.path().join("content").join("foo"), b"dmta")
+            .await
+            .unwrap();
+        let mut content = Vec::new();
+        storage
+            .upload_piece("foo", 0, None)
+            .await
+            .unwrap()
+            .read_to_end(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, b"dmta");
+        assert!(storage.get_piece("foo", 0).unwrap().is_some());
+    }
+}
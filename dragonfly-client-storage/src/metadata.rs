@@ -26,7 +26,10 @@ use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info};
 
-use crate::storage_engine::{rocksdb::RocksdbStorageEngine, DatabaseObject, StorageEngineOwned};
+use crate::storage_engine::{
+    rocksdb::{ColumnFamilyStatistics, RocksdbStorageEngine},
+    DatabaseObject, StorageEngineOwned,
+};
 
 // Task is the metadata of the task.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -64,6 +67,11 @@ pub struct Task {
 
     // finished_at is the time when the task downloads finished.
     pub finished_at: Option<NaiveDateTime>,
+
+    // pinned indicates whether the task is protected from gc, the pinned task is not evicted
+    // by the task ttl or the disk usage, e.g. the base images preheated on the seed peers.
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 // Task implements the task database object.
@@ -104,6 +112,11 @@ impl Task {
         self.finished_at.is_some()
     }
 
+    // is_pinned returns whether the task is protected from gc.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
     // is_empty returns whether the task is empty.
     pub fn is_empty(&self) -> bool {
         if let Some(content_length) = self.content_length() {
@@ -466,6 +479,50 @@ impl<E: StorageEngineOwned> Metadata<E> {
         Ok(task)
     }
 
+    // pin_task protects the task from gc, the updated_at of the task is not changed because
+    // the task is not accessed.
+    pub fn pin_task(&self, id: &str) -> Result<Task> {
+        self.set_task_pinned(id, true)
+    }
+
+    // unpin_task removes the protection of the task from gc.
+    pub fn unpin_task(&self, id: &str) -> Result<Task> {
+        self.set_task_pinned(id, false)
+    }
+
+    // set_task_pinned updates whether the task is protected from gc.
+    fn set_task_pinned(&self, id: &str, pinned: bool) -> Result<Task> {
+        let task = match self.db.get::<Task>(id.as_bytes())? {
+            Some(mut task) => {
+                task.pinned = pinned;
+                task
+            }
+            None => return Err(Error::TaskNotFound(id.to_string())),
+        };
+
+        self.db.put(id.as_bytes(), &task)?;
+        Ok(task)
+    }
+
+    // import_task_started creates the metadata of the task imported from the task archive,
+    // the counters and the timestamps of the exported task are reset, then the task is
+    // finished by download_task_finished after the pieces are imported.
+    pub fn import_task_started(&self, task: &Task) -> Result<Task> {
+        let task = Task {
+            id: task.id.clone(),
+            piece_length: task.piece_length,
+            content_length: task.content_length,
+            response_header: task.response_header.clone(),
+            updated_at: Utc::now().naive_utc(),
+            created_at: Utc::now().naive_utc(),
+            pinned: task.pinned,
+            ..Default::default()
+        };
+
+        self.db.put(task.id.as_bytes(), &task)?;
+        Ok(task)
+    }
+
     // get_task gets the task metadata.
     pub fn get_task(&self, id: &str) -> Result<Option<Task>> {
         self.db.get(id.as_bytes())
@@ -748,6 +805,27 @@ impl<E: StorageEngineOwned> Metadata<E> {
         Ok(piece)
     }
 
+    // upload_piece_corrupted deletes the piece metadata when the uploaded piece is corrupted,
+    // then the piece is downloaded again by the next download of the task. The finished task
+    // is marked as failed because its content is incomplete, then it is not served as a
+    // finished task and it can be evicted by gc.
+    pub fn upload_piece_corrupted(&self, task_id: &str, number: u32) -> Result<()> {
+        self.delete_piece(task_id, number)?;
+
+        let Some(mut task) = self.db.get::<Task>(task_id.as_bytes())? else {
+            return Ok(());
+        };
+
+        if task.is_finished() {
+            task.updated_at = Utc::now().naive_utc();
+            task.failed_at = Some(Utc::now().naive_utc());
+            task.finished_at = None;
+            self.db.put(task_id.as_bytes(), &task)?;
+        }
+
+        Ok(())
+    }
+
     // get_piece gets the piece metadata.
     pub fn get_piece(&self, task_id: &str, number: u32) -> Result<Option<Piece>> {
         self.db.get(self.piece_id(task_id, number).as_bytes())
@@ -790,10 +868,19 @@ impl Metadata<RocksdbStorageEngine> {
             dir,
             &[Task::NAMESPACE, Piece::NAMESPACE],
             config.storage.keep,
+            &config.storage.metadata,
         )?;
 
         Ok(Metadata { db })
     }
+
+    // statistics returns the statistics of the column families of the metadata.
+    pub fn statistics(&self) -> Result<Vec<ColumnFamilyStatistics>> {
+        [Task::NAMESPACE, Piece::NAMESPACE]
+            .iter()
+            .map(|cf_name| self.db.statistics(cf_name))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -873,6 +960,32 @@ mod tests {
             "uploaded_count should not be changed after upload_task_failed"
         );
 
+        // Test pin_task and unpin_task.
+        let task = metadata.pin_task(task_id).unwrap();
+        assert!(task.is_pinned(), "task should be pinned after pin_task");
+        assert!(metadata.get_task(task_id).unwrap().unwrap().is_pinned());
+
+        // The pinned task is still pinned after it is downloaded again.
+        metadata
+            .download_task_started(task_id, 1024, Some(1024), None)
+            .unwrap();
+        assert!(metadata.get_task(task_id).unwrap().unwrap().is_pinned());
+
+        let task = metadata.unpin_task(task_id).unwrap();
+        assert!(
+            !task.is_pinned(),
+            "task should be unpinned after unpin_task"
+        );
+        assert!(!metadata.get_task(task_id).unwrap().unwrap().is_pinned());
+        assert!(metadata.pin_task("not_exist").is_err());
+
+        // The task stored without the pinned field is not pinned.
+        let task: Task = serde_json::from_str(
+            r#"{"id":"task0","piece_length":1024,"content_length":null,"response_header":{},"uploading_count":0,"uploaded_count":0,"updated_at":"2024-01-01T00:00:00","created_at":"2024-01-01T00:00:00","prefetched_at":null,"failed_at":null,"finished_at":null}"#,
+        )
+        .unwrap();
+        assert!(!task.is_pinned());
+
         // Test get_tasks.
         let task_id = "task2";
 
@@ -15,11 +15,12 @@
  */
 
 use crate::storage_engine::{DatabaseObject, Operations, StorageEngine};
+use dragonfly_client_config::dfdaemon::{StorageMetadata, StorageMetadataCompression};
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error, Result,
 };
-use rocksdb::{ReadOptions, WriteOptions};
+use rocksdb::{properties, ColumnFamilyDescriptor, DBCompressionType, ReadOptions, WriteOptions};
 use std::{ops::Deref, path::Path};
 use tracing::{info, warn};
 
@@ -54,11 +55,45 @@ impl RocksdbStorageEngine {
     /// DEFAULT_BLOCK_SIZE is the default block size for rocksdb.
     const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
 
-    /// DEFAULT_CACHE_SIZE is the default cache size for rocksdb.
-    const DEFAULT_CACHE_SIZE: usize = 32 * 1024 * 1024;
+    /// DEFAULT_BLOOM_FILTER_BITS_PER_KEY is the default bits per key of the bloom filter, about
+    /// 1% of the lookups of the missing keys read the blocks.
+    const DEFAULT_BLOOM_FILTER_BITS_PER_KEY: f64 = 10.0;
+
+    /// WRITE_STALL_STATS is the property of the write stall counters of the column family.
+    const WRITE_STALL_STATS: &'static str = "rocksdb.cf-write-stall-stats";
 
     /// open opens a rocksdb storage engine with the given directory and column families.
-    pub fn open(dir: &Path, cf_names: &[&str], keep: bool) -> Result<Self> {
+    pub fn open(
+        dir: &Path,
+        cf_names: &[&str],
+        keep: bool,
+        config: &StorageMetadata,
+    ) -> Result<Self> {
+        let options = Self::options(config);
+        let dir = dir.join(Self::DEFAULT_DIR_NAME);
+
+        // If the storage is not kept, remove the db.
+        if !keep {
+            rocksdb::DB::destroy(&options, &dir).unwrap_or_else(|err| {
+                warn!("destroy {:?} failed: {}", dir, err);
+            });
+        }
+
+        // Open rocksdb, the column families are opened with the same options, otherwise
+        // the column family options only apply to the default column family.
+        let cf_descriptors = cf_names
+            .iter()
+            .map(|cf_name| ColumnFamilyDescriptor::new(*cf_name, options.clone()))
+            .collect::<Vec<_>>();
+        let db = rocksdb::DB::open_cf_descriptors(&options, &dir, cf_descriptors)
+            .or_err(ErrorType::StorageError)?;
+        info!("metadata initialized directory: {:?}", dir);
+
+        Ok(Self { inner: db })
+    }
+
+    /// options returns the rocksdb options tuned by the metadata configuration.
+    fn options(config: &StorageMetadata) -> rocksdb::Options {
         // Initialize rocksdb options.
         let mut options = rocksdb::Options::default();
         options.create_if_missing(true);
@@ -67,27 +102,106 @@ impl RocksdbStorageEngine {
         options.increase_parallelism(num_cpus::get() as i32);
         options.set_max_open_files(Self::DEFAULT_MAX_OPEN_FILES);
 
+        // The memtables and the background jobs override the ones set by the level style
+        // compaction and the parallelism.
+        options.set_write_buffer_size(config.write_buffer_size);
+        options.set_max_write_buffer_number(config.max_write_buffer_number);
+        options.set_max_background_jobs(config.max_background_jobs);
+
+        // The first two levels are not compressed, the same as the level style compaction.
+        let compression = match config.compression_type {
+            StorageMetadataCompression::None => DBCompressionType::None,
+            StorageMetadataCompression::Snappy => DBCompressionType::Snappy,
+            StorageMetadataCompression::Lz4 => DBCompressionType::Lz4,
+            StorageMetadataCompression::Zstd => DBCompressionType::Zstd,
+        };
+        options.set_compression_per_level(&[
+            DBCompressionType::None,
+            DBCompressionType::None,
+            compression,
+            compression,
+            compression,
+            compression,
+            compression,
+        ]);
+
         // Initialize rocksdb block based table options.
         let mut block_options = rocksdb::BlockBasedOptions::default();
-        block_options.set_block_cache(&rocksdb::Cache::new_lru_cache(Self::DEFAULT_CACHE_SIZE));
+        block_options.set_block_cache(&rocksdb::Cache::new_lru_cache(config.block_cache_size));
         block_options.set_block_size(Self::DEFAULT_BLOCK_SIZE);
+        if config.bloom_filter {
+            block_options.set_bloom_filter(Self::DEFAULT_BLOOM_FILTER_BITS_PER_KEY, false);
+        }
         options.set_block_based_table_factory(&block_options);
 
-        let dir = dir.join(Self::DEFAULT_DIR_NAME);
+        options
+    }
 
-        // If the storage is not kept, remove the db.
-        if !keep {
-            rocksdb::DB::destroy(&options, &dir).unwrap_or_else(|err| {
-                warn!("destroy {:?} failed: {}", dir, err);
-            });
-        }
+    /// statistics returns the statistics of the column family.
+    pub fn statistics(&self, cf_name: &str) -> Result<ColumnFamilyStatistics> {
+        let cf = self
+            .cf_handle(cf_name)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(cf_name.to_string()))?;
 
-        // Open rocksdb.
-        let db = rocksdb::DB::open_cf(&options, &dir, cf_names).or_err(ErrorType::StorageError)?;
-        info!("metadata initialized directory: {:?}", dir);
+        let estimated_keys = self
+            .property_int_value_cf(cf, properties::ESTIMATE_NUM_KEYS)
+            .or_err(ErrorType::StorageError)?
+            .unwrap_or_default();
+        let pending_compaction_bytes = self
+            .property_int_value_cf(cf, properties::ESTIMATE_PENDING_COMPACTION_BYTES)
+            .or_err(ErrorType::StorageError)?
+            .unwrap_or_default();
+        let (write_stall_delays, write_stall_stops) = self
+            .property_value_cf(cf, Self::WRITE_STALL_STATS)
+            .or_err(ErrorType::StorageError)?
+            .map(|stats| parse_write_stall_stats(&stats))
+            .unwrap_or_default();
 
-        Ok(Self { inner: db })
+        Ok(ColumnFamilyStatistics {
+            name: cf_name.to_string(),
+            estimated_keys,
+            pending_compaction_bytes,
+            write_stall_delays,
+            write_stall_stops,
+        })
+    }
+}
+
+/// ColumnFamilyStatistics is the statistics of the column family.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnFamilyStatistics {
+    /// name is the name of the column family.
+    pub name: String,
+
+    /// estimated_keys is the estimated number of the keys.
+    pub estimated_keys: u64,
+
+    /// pending_compaction_bytes is the estimated bytes to be rewritten by the compactions.
+    pub pending_compaction_bytes: u64,
+
+    /// write_stall_delays is the number of the delayed writes since the db is opened.
+    pub write_stall_delays: u64,
+
+    /// write_stall_stops is the number of the stopped writes since the db is opened.
+    pub write_stall_stops: u64,
+}
+
+/// parse_write_stall_stats parses the total delays and stops of the write stall stats, e.g.
+/// "Write Stall (count): memtable-limit-delays: 0, ..., total-delays: 1, total-stops: 2".
+fn parse_write_stall_stats(stats: &str) -> (u64, u64) {
+    let stats = stats.lines().next().unwrap_or_default();
+    let stats = stats.strip_prefix("Write Stall (count): ").unwrap_or(stats);
+
+    let (mut delays, mut stops) = (0, 0);
+    for stat in stats.split(", ") {
+        match stat.split_once(": ") {
+            Some(("total-delays", value)) => delays = value.trim().parse().unwrap_or_default(),
+            Some(("total-stops", value)) => stops = value.trim().parse().unwrap_or_default(),
+            _ => {}
+        }
     }
+
+    (delays, stops)
 }
 
 // RocksdbStorageEngine implements the storage engine operations.
@@ -166,3 +280,143 @@ where
     db.cf_handle(cf_name)
         .ok_or_else(|| Error::ColumnFamilyNotFound(cf_name.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    // options_file reads the latest rocksdb options file persisted in the db directory.
+    fn options_file(dir: &Path) -> String {
+        let path = std::fs::read_dir(dir.join(RocksdbStorageEngine::DEFAULT_DIR_NAME))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("OPTIONS-")
+            })
+            .max()
+            .unwrap();
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    // options_section returns the section of the options file, e.g. `CFOptions "foo"`.
+    fn options_section(options: &str, name: &str) -> String {
+        let header = format!("[{}]", name);
+        options
+            .lines()
+            .skip_while(|line| line.trim() != header)
+            .skip(1)
+            .take_while(|line| !line.trim_start().starts_with('['))
+            .map(|line| format!("{}\n", line.trim()))
+            .collect()
+    }
+
+    #[test]
+    fn should_apply_default_metadata_options() {
+        let dir = TempDir::new("rocksdb").unwrap();
+        let db =
+            RocksdbStorageEngine::open(dir.path(), &["foo"], false, &StorageMetadata::default())
+                .unwrap();
+
+        let options = options_file(dir.path());
+        assert!(options.contains(&format!("max_background_jobs={}\n", num_cpus::get())));
+
+        // The column family options apply to the column family instead of the default one.
+        let cf_options = options_section(&options, "CFOptions \"foo\"");
+        assert!(cf_options.contains("write_buffer_size=16777216\n"));
+        assert!(cf_options.contains("max_write_buffer_number=6\n"));
+        assert!(cf_options.contains(
+            "compression_per_level=kNoCompression:kNoCompression:kLZ4Compression:kLZ4Compression:kLZ4Compression:kLZ4Compression:kLZ4Compression\n"
+        ));
+        let table_options = options_section(&options, "TableOptions/BlockBasedTable \"foo\"");
+        assert!(table_options.contains("filter_policy=nullptr\n"));
+        assert_eq!(
+            db.property_int_value_cf(
+                db.cf_handle("foo").unwrap(),
+                properties::BLOCK_CACHE_CAPACITY
+            )
+            .unwrap(),
+            Some(32 * 1024 * 1024)
+        );
+    }
+
+    #[test]
+    fn should_apply_metadata_options() {
+        let dir = TempDir::new("rocksdb").unwrap();
+        let config = StorageMetadata {
+            block_cache_size: 256 * 1024 * 1024,
+            write_buffer_size: 64 * 1024 * 1024,
+            max_write_buffer_number: 4,
+            max_background_jobs: 3,
+            compression_type: StorageMetadataCompression::Zstd,
+            bloom_filter: true,
+        };
+        let db = RocksdbStorageEngine::open(dir.path(), &["foo"], false, &config).unwrap();
+
+        let options = options_file(dir.path());
+        assert!(options.contains("max_background_jobs=3\n"));
+
+        // The column family options apply to the column family instead of the default one.
+        let cf_options = options_section(&options, "CFOptions \"foo\"");
+        assert!(cf_options.contains("write_buffer_size=67108864\n"));
+        assert!(cf_options.contains("max_write_buffer_number=4\n"));
+        assert!(cf_options.contains(
+            "compression_per_level=kNoCompression:kNoCompression:kZSTD:kZSTD:kZSTD:kZSTD:kZSTD\n"
+        ));
+        let table_options = options_section(&options, "TableOptions/BlockBasedTable \"foo\"");
+        assert!(table_options.contains("block_size=65536\n"));
+        assert!(!table_options.contains("filter_policy=nullptr\n"));
+        assert_eq!(
+            db.property_int_value_cf(
+                db.cf_handle("foo").unwrap(),
+                properties::BLOCK_CACHE_CAPACITY
+            )
+            .unwrap(),
+            Some(256 * 1024 * 1024)
+        );
+    }
+
+    #[test]
+    fn should_return_column_family_statistics() {
+        let dir = TempDir::new("rocksdb").unwrap();
+        let db =
+            RocksdbStorageEngine::open(dir.path(), &["foo"], false, &StorageMetadata::default())
+                .unwrap();
+        for key in ["a", "b", "c"] {
+            db.put_cf(db.cf_handle("foo").unwrap(), key, "value")
+                .unwrap();
+        }
+
+        let statistics = db.statistics("foo").unwrap();
+        assert_eq!(statistics.name, "foo");
+        assert_eq!(statistics.estimated_keys, 3);
+        assert_eq!(statistics.pending_compaction_bytes, 0);
+        assert_eq!(statistics.write_stall_delays, 0);
+        assert_eq!(statistics.write_stall_stops, 0);
+
+        assert!(matches!(
+            db.statistics("bar"),
+            Err(Error::ColumnFamilyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn should_parse_write_stall_stats() {
+        assert_eq!(
+            parse_write_stall_stats(
+                "Write Stall (count): cf-l0-file-count-limit-delays-with-ongoing-compaction: 0, l0-file-count-limit-delays: 4, memtable-limit-stops: 1, total-delays: 4, total-stops: 1\n"
+            ),
+            (4, 1)
+        );
+        assert_eq!(
+            parse_write_stall_stats(
+                "Write Stall (count): total-delays: 2, total-stops: 3\ninterval: 5 total count\n"
+            ),
+            (2, 3)
+        );
+        assert_eq!(parse_write_stall_stats(""), (0, 0));
+    }
+}
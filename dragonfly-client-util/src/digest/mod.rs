@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use sha2::Digest as _;
 use std::fmt;
 use std::str::FromStr;
 
@@ -31,6 +32,10 @@ pub enum Algorithm {
 
     // Sha512 is sha512 algorithm for generate digest.
     Sha512,
+
+    // Crc32c is crc32c algorithm for generate digest, it is used for the integrity
+    // and not for the security.
+    Crc32c,
 }
 
 // Algorithm implements the Display.
@@ -41,6 +46,7 @@ impl fmt::Display for Algorithm {
             Algorithm::Blake3 => write!(f, "blake3"),
             Algorithm::Sha256 => write!(f, "sha256"),
             Algorithm::Sha512 => write!(f, "sha512"),
+            Algorithm::Crc32c => write!(f, "crc32c"),
         }
     }
 }
@@ -55,12 +61,14 @@ impl FromStr for Algorithm {
             "blake3" => Ok(Algorithm::Blake3),
             "sha256" => Ok(Algorithm::Sha256),
             "sha512" => Ok(Algorithm::Sha512),
+            "crc32c" => Ok(Algorithm::Crc32c),
             _ => Err(format!("invalid digest algorithm: {}", s)),
         }
     }
 }
 
 // Digest is a struct that is used to generate digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Digest {
     // algorithm is the algorithm that is used to generate digest.
     algorithm: Algorithm,
@@ -106,13 +114,146 @@ impl FromStr for Digest {
             return Err(format!("invalid digest: {}", s));
         }
 
-        let algorithm = match parts[0] {
-            "blake3" => Algorithm::Blake3,
-            "sha256" => Algorithm::Sha256,
-            "sha512" => Algorithm::Sha512,
-            _ => return Err(format!("invalid digest algorithm: {}", parts[0])),
+        let algorithm = parts[0].parse::<Algorithm>()?;
+        Ok(Digest::new(algorithm, parts[1].to_string()))
+    }
+}
+
+// Hasher is used to calculate the digest of the content by the algorithm.
+pub enum Hasher {
+    // Blake3 is the hasher of blake3 algorithm.
+    Blake3(Box<blake3::Hasher>),
+
+    // Sha256 is the hasher of sha256 algorithm.
+    Sha256(sha2::Sha256),
+
+    // Sha512 is the hasher of sha512 algorithm.
+    Sha512(sha2::Sha512),
+
+    // Crc32c is the hasher of crc32c algorithm, it holds the crc of the updated content.
+    Crc32c(u32),
+}
+
+// Hasher implements the Hasher.
+impl Hasher {
+    // new returns a new Hasher of the algorithm.
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(sha2::Sha512::new()),
+            Algorithm::Crc32c => Hasher::Crc32c(0),
+        }
+    }
+
+    // algorithm returns the algorithm of the hasher.
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            Hasher::Blake3(_) => Algorithm::Blake3,
+            Hasher::Sha256(_) => Algorithm::Sha256,
+            Hasher::Sha512(_) => Algorithm::Sha512,
+            Hasher::Crc32c(_) => Algorithm::Crc32c,
+        }
+    }
+
+    // update updates the hasher with the content.
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Sha512(hasher) => hasher.update(bytes),
+            Hasher::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, bytes),
+        }
+    }
+
+    // finalize returns the digest of the updated content.
+    pub fn finalize(self) -> Digest {
+        let algorithm = self.algorithm();
+        let encoded = match self {
+            Hasher::Blake3(hasher) => hex::encode(hasher.finalize().as_bytes()),
+            Hasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            Hasher::Sha512(hasher) => hex::encode(hasher.finalize()),
+            Hasher::Crc32c(crc) => format!("{:08x}", crc),
         };
 
-        Ok(Digest::new(algorithm, parts[1].to_string()))
+        Digest::new(algorithm, encoded)
+    }
+}
+
+// calculate returns the digest of the content by the algorithm.
+pub fn calculate(algorithm: Algorithm, bytes: &[u8]) -> Digest {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_digest() {
+        for (s, algorithm, encoded) in [
+            ("blake3:foo", Algorithm::Blake3, "foo"),
+            ("sha256:foo", Algorithm::Sha256, "foo"),
+            ("sha512:foo", Algorithm::Sha512, "foo"),
+            ("crc32c:e3069283", Algorithm::Crc32c, "e3069283"),
+        ] {
+            let digest = s.parse::<Digest>().unwrap();
+            assert_eq!(digest.algorithm(), algorithm);
+            assert_eq!(digest.encoded(), encoded);
+            assert_eq!(digest.to_string(), s);
+        }
+
+        assert!("foo".parse::<Digest>().is_err());
+        assert!("md5:foo".parse::<Digest>().is_err());
+    }
+
+    #[test]
+    fn should_calculate_digest() {
+        let content = b"123456789";
+        assert_eq!(
+            calculate(Algorithm::Blake3, content).to_string(),
+            format!("blake3:{}", blake3::hash(content).to_hex())
+        );
+        assert_eq!(
+            calculate(Algorithm::Sha256, content).to_string(),
+            "sha256:15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225"
+        );
+        assert_eq!(
+            calculate(Algorithm::Sha512, content).encoded(),
+            hex::encode(sha2::Sha512::digest(content))
+        );
+        assert_eq!(
+            calculate(Algorithm::Crc32c, content).to_string(),
+            "crc32c:e3069283"
+        );
+        assert_eq!(calculate(Algorithm::Crc32c, b"").encoded(), "00000000");
+    }
+
+    #[test]
+    fn should_round_trip_digest_by_chunks() {
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        for algorithm in [
+            Algorithm::Blake3,
+            Algorithm::Sha256,
+            Algorithm::Sha512,
+            Algorithm::Crc32c,
+        ] {
+            // The digest is same whether the content is updated at once or by chunks.
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in content.chunks(7) {
+                hasher.update(chunk);
+            }
+            let digest = hasher.finalize();
+            assert_eq!(digest, calculate(algorithm, &content));
+
+            // The digest string declares the algorithm.
+            let parsed = digest.to_string().parse::<Digest>().unwrap();
+            assert_eq!(parsed, digest);
+            assert_eq!(parsed.algorithm(), algorithm);
+        }
     }
 }
@@ -17,4 +17,5 @@
 pub mod digest;
 pub mod http;
 pub mod id_generator;
+pub mod net;
 pub mod tls;
@@ -0,0 +1,178 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_client_core::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+// TCP_ESTABLISHED is the hex state of the established connection in /proc/net/tcp.
+const TCP_ESTABLISHED: &str = "01";
+
+// TCPConnections is the count of the established tcp connections of the process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TCPConnections {
+    // count is the count of the established tcp connections.
+    pub count: u32,
+
+    // upload_count is the count of the established tcp connections on the upload port.
+    pub upload_count: u32,
+}
+
+// tcp_connections counts the established tcp connections of the process by the
+// proc directory of the process, e.g. /proc/self, and counts the connections
+// whose local port equals the upload port separately.
+pub fn tcp_connections(proc_dir: &Path, upload_port: u16) -> Result<TCPConnections> {
+    let inodes = socket_inodes(proc_dir)?;
+    let mut connections = TCPConnections::default();
+    for name in ["tcp", "tcp6"] {
+        // The tcp6 file does not exist if ipv6 is disabled.
+        let content = match fs::read_to_string(proc_dir.join("net").join(name)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        for (port, inode) in parse_established(&content) {
+            if !inodes.contains(&inode) {
+                continue;
+            }
+
+            connections.count += 1;
+            if port == upload_port {
+                connections.upload_count += 1;
+            }
+        }
+    }
+
+    Ok(connections)
+}
+
+// socket_inodes returns the inodes of the sockets opened by the process.
+fn socket_inodes(proc_dir: &Path) -> Result<HashSet<u64>> {
+    let mut inodes = HashSet::new();
+    for entry in fs::read_dir(proc_dir.join("fd"))? {
+        // The fd may be closed after reading the directory.
+        let Ok(link) = fs::read_link(entry?.path()) else {
+            continue;
+        };
+
+        let Some(inode) = link
+            .to_str()
+            .and_then(|link| link.strip_prefix("socket:["))
+            .and_then(|link| link.strip_suffix(']'))
+            .and_then(|inode| inode.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        inodes.insert(inode);
+    }
+
+    Ok(inodes)
+}
+
+// parse_established parses the local port and inode of the established
+// connections in the content of /proc/net/tcp or /proc/net/tcp6.
+fn parse_established(content: &str) -> impl Iterator<Item = (u16, u64)> + '_ {
+    content.lines().skip(1).filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || fields[3] != TCP_ESTABLISHED {
+            return None;
+        }
+
+        let (_, port) = fields[1].rsplit_once(':')?;
+        let port = u16::from_str_radix(port, 16).ok()?;
+        let inode = fields[9].parse::<u64>().ok()?;
+        Some((port, inode))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempdir::TempDir;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0FA1 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 100 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:0FA1 0100007F:D431 01 00000000:00000000 00:00000000 00000000     0        0 101 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:D431 0100007F:0FA1 01 00000000:00000000 00:00000000 00000000     0        0 102 1 0000000000000000 20 4 30 10 -1
+   3: 0100007F:1F90 0100007F:D432 01 00000000:00000000 00:00000000 00000000     0        0 200 1 0000000000000000 20 4 30 10 -1
+   4: 0100007F:0FA1 0100007F:D433 06 00000000:00000000 03:00000000 00000000     0        0 0 3 0000000000000000
+";
+
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0FA1 00000000000000000000000001000000:D434 01 00000000:00000000 00:00000000 00000000     0        0 103 1 0000000000000000 20 4 30 10 -1
+";
+
+    #[test]
+    fn should_parse_established() {
+        assert_eq!(
+            parse_established(TCP).collect::<Vec<_>>(),
+            vec![(4001, 101), (54321, 102), (8080, 200)]
+        );
+        assert_eq!(
+            parse_established(TCP6).collect::<Vec<_>>(),
+            vec![(4001, 103)]
+        );
+        assert_eq!(parse_established("").count(), 0);
+        assert_eq!(
+            parse_established("header\n   0: 0100007F:XYZ 0100007F:0FA1 01 0 0 0 0 0 101\n")
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn should_count_tcp_connections() {
+        let dir = TempDir::new("net").unwrap();
+        fs::create_dir_all(dir.path().join("fd")).unwrap();
+        fs::create_dir_all(dir.path().join("net")).unwrap();
+        fs::write(dir.path().join("net/tcp"), TCP).unwrap();
+
+        for (fd, target) in [
+            ("0", "/dev/null"),
+            ("3", "socket:[100]"),
+            ("4", "socket:[101]"),
+            ("5", "socket:[102]"),
+            ("6", "socket:[103]"),
+            ("7", "pipe:[104]"),
+        ] {
+            symlink(target, dir.path().join("fd").join(fd)).unwrap();
+        }
+
+        // The socket 200 is not opened by the process and the tcp6 file does not exist.
+        assert_eq!(
+            tcp_connections(dir.path(), 4001).unwrap(),
+            TCPConnections {
+                count: 2,
+                upload_count: 1,
+            }
+        );
+
+        fs::write(dir.path().join("net/tcp6"), TCP6).unwrap();
+        assert_eq!(
+            tcp_connections(dir.path(), 4001).unwrap(),
+            TCPConnections {
+                count: 3,
+                upload_count: 2,
+            }
+        );
+
+        assert!(tcp_connections(&dir.path().join("not_exist"), 4001).is_err());
+    }
+}
@@ -14,22 +14,33 @@
  * limitations under the License.
  */
 
-use crate::grpc::{manager::ManagerClient, scheduler::SchedulerClient};
+use crate::dynconfig::{Data, Dynconfig};
+use crate::grpc::{manager::ManagerClient, scheduler::SchedulerAnnounce};
+use crate::metrics::{
+    collect_announce_host_failure_metrics, collect_announce_host_finished_metrics,
+    collect_announce_host_started_metrics,
+};
 use crate::shutdown;
-use dragonfly_api::common::v2::{Build, Cpu, Host, Memory, Network};
+use dragonfly_api::common::v2::{Build, Cpu, CpuTimes, Disk, Host, Memory, Network};
 use dragonfly_api::manager::v2::{DeleteSeedPeerRequest, SourceType, UpdateSeedPeerRequest};
 use dragonfly_api::scheduler::v2::{AnnounceHostRequest, DeleteHostRequest};
 use dragonfly_client_config::{
-    dfdaemon::{Config, HostType},
+    dfdaemon::{AnnounceRetry, Config, HostType},
     CARGO_PKG_RUSTC_VERSION, CARGO_PKG_VERSION, GIT_HASH,
 };
 use dragonfly_client_core::error::{ErrorType, OrErr};
-use dragonfly_client_core::Result;
+use dragonfly_client_core::{Error, Result};
+use dragonfly_client_util::net::{tcp_connections, TCPConnections};
+use rand::Rng;
 use std::env;
-use std::sync::Arc;
-use sysinfo::{CpuExt, ProcessExt, System, SystemExt};
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{CpuExt, Pid, ProcessExt, System, SystemExt};
+use tokio::sync::{mpsc, Notify};
+use tracing::{debug, error, info, warn};
 
 // ManagerAnnouncer is used to announce the dfdaemon information to the manager.
 pub struct ManagerAnnouncer {
@@ -70,33 +81,41 @@ impl ManagerAnnouncer {
 
         // If the seed peer is enabled, we should announce the seed peer to the manager.
         if self.config.seed_peer.enable {
-            // Register the seed peer to the manager.
-            self.manager_client
-                .update_seed_peer(UpdateSeedPeerRequest {
-                    source_type: SourceType::SeedPeerSource.into(),
-                    hostname: self.config.host.hostname.clone(),
-                    r#type: self.config.seed_peer.kind.to_string(),
-                    idc: self.config.host.idc.clone(),
-                    location: self.config.host.location.clone(),
-                    ip: self.config.host.ip.unwrap().to_string(),
-                    port: self.config.upload.server.port as i32,
-                    download_port: self.config.upload.server.port as i32,
-                    seed_peer_cluster_id: self.config.seed_peer.cluster_id,
-                })
-                .await?;
+            let request = make_update_seed_peer_request(&self.config)?;
 
-            // Announce to scheduler shutting down with signals.
-            shutdown.recv().await;
+            // Register the seed peer to the manager, the registration is retried if the
+            // manager is unreachable temporarily, e.g. during a rolling deploy.
+            if register(&self.config.manager.announce_retry, &mut shutdown, || {
+                self.manager_client.update_seed_peer(request.clone())
+            })
+            .await?
+            .is_none()
+            {
+                info!("announce to manager shutting down");
+                return Ok(());
+            }
+
+            // Keep the seed peer registration alive until shutting down with signals,
+            // then the seed peer is registered again if the manager loses it.
+            keepalive(
+                self.config.manager.announce_interval,
+                &self.config.manager.announce_retry,
+                &mut shutdown,
+                || self.manager_client.update_seed_peer(request.clone()),
+            )
+            .await;
 
             // Delete the seed peer from the manager.
-            self.manager_client
-                .delete_seed_peer(DeleteSeedPeerRequest {
+            deregister(
+                self.config.shutdown.announce_timeout,
+                self.manager_client.delete_seed_peer(DeleteSeedPeerRequest {
                     source_type: SourceType::SeedPeerSource.into(),
                     hostname: self.config.host.hostname.clone(),
-                    ip: self.config.host.ip.unwrap().to_string(),
+                    ip: request.ip.clone(),
                     seed_peer_cluster_id: self.config.seed_peer.cluster_id,
-                })
-                .await?;
+                }),
+            )
+            .await?;
 
             info!("announce to manager shutting down");
         } else {
@@ -108,6 +127,205 @@ impl ManagerAnnouncer {
     }
 }
 
+// make_update_seed_peer_request makes the request to register the seed peer to the manager.
+fn make_update_seed_peer_request(config: &Config) -> Result<UpdateSeedPeerRequest> {
+    Ok(UpdateSeedPeerRequest {
+        source_type: SourceType::SeedPeerSource.into(),
+        hostname: config.host.hostname.clone(),
+        r#type: config.seed_peer.kind.to_string(),
+        idc: config.host.idc.clone(),
+        location: config.host.location.clone(),
+        ip: host_ip(config)?,
+        port: config.upload.server.port as i32,
+        download_port: download_port(config) as i32,
+        seed_peer_cluster_id: config.seed_peer.cluster_id,
+    })
+}
+
+// download_port returns the announced port for downloading pieces, it falls back
+// to the port of the upload server if the download port is not set.
+fn download_port(config: &Config) -> u16 {
+    config
+        .upload
+        .server
+        .download_port
+        .unwrap_or(config.upload.server.port)
+}
+
+// host_ip returns the advertise ip of the host, the ip may be missing if the
+// detection failed when loading the config.
+fn host_ip(config: &Config) -> Result<String> {
+    config.host.ip.map(|ip| ip.to_string()).ok_or_else(|| {
+        error!("host ip is not found in config");
+        Error::InvalidParameter
+    })
+}
+
+// make_host_template makes the host with the static fields, which are computed once
+// at startup instead of on every announcement.
+fn make_host_template(config: &Config, host_id: &str, sys: &System) -> Result<Host> {
+    // If the seed peer is enabled, we should announce the seed peer to the scheduler.
+    let host_type = if config.seed_peer.enable {
+        config.seed_peer.kind
+    } else {
+        HostType::Normal
+    };
+
+    // Get the platform information, the platform falls back to the os if the
+    // distribution is unknown.
+    let (platform, platform_family) = platform()
+        .unwrap_or_else(|| (env::consts::OS.to_string(), env::consts::FAMILY.to_string()));
+
+    // Get the build information.
+    let build = Build {
+        git_version: CARGO_PKG_VERSION.to_string(),
+        git_commit: Some(GIT_HASH.unwrap_or_default().to_string()),
+        go_version: None,
+        rust_version: Some(CARGO_PKG_RUSTC_VERSION.to_string()),
+        platform: Some(env!("BUILD_PLATFORM").to_string()),
+    };
+
+    Ok(Host {
+        id: host_id.to_string(),
+        r#type: host_type as u32,
+        hostname: config.host.hostname.clone(),
+        ip: host_ip(config)?,
+        port: config.upload.server.port as i32,
+        download_port: download_port(config) as i32,
+        os: env::consts::OS.to_string(),
+        platform,
+        platform_family,
+        platform_version: sys.os_version().unwrap_or_default(),
+        kernel_version: sys.kernel_version().unwrap_or_default(),
+        build: Some(build),
+        ..Default::default()
+    })
+}
+
+// make_host makes the host by filling the dynamic fields into the host template.
+fn make_host(
+    template: &Host,
+    cpu: Cpu,
+    memory: Memory,
+    network: Network,
+    disk: Option<Disk>,
+) -> Host {
+    Host {
+        cpu: Some(cpu),
+        memory: Some(memory),
+        network: Some(network),
+        disk,
+        ..template.clone()
+    }
+}
+
+// HostInfoCollector collects the information of the host, it is the single source of the
+// host information announced to the scheduler, and it can be shared to report the host
+// state outside the announcer, e.g. by the health check.
+pub struct HostInfoCollector {
+    // config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    // host is the template of the collected host, it holds the static fields which
+    // never change after startup.
+    host: Host,
+
+    // system is the cached system information, it is refreshed partially on every
+    // collection, and the cpu usage is calculated between two refreshes.
+    system: Mutex<System>,
+
+    // tcp_connections is the cached count of the tcp connections, it is refreshed
+    // in the background to avoid blocking the collection.
+    tcp_connections: Arc<Mutex<TCPConnections>>,
+
+    // tcp_connections_refreshing indicates whether the tcp connections are refreshing.
+    tcp_connections_refreshing: Arc<AtomicBool>,
+}
+
+// HostInfoCollector implements the collector of the host information.
+impl HostInfoCollector {
+    // new creates a new host information collector, the static fields of the host are
+    // computed once, and the system is refreshed for the cpu usage of the first collection.
+    pub fn new(config: Arc<Config>, host_id: &str) -> Result<Self> {
+        let mut system = System::new();
+        let host = make_host_template(&config, host_id, &system)?;
+        refresh_system(&mut system, sysinfo::get_current_pid().ok());
+
+        Ok(Self {
+            config,
+            host,
+            system: Mutex::new(system),
+            tcp_connections: Arc::new(Mutex::new(TCPConnections::default())),
+            tcp_connections_refreshing: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // collect collects the host information, the static fields are cloned from the host
+    // template and only the dynamic fields are collected. The count of the tcp connections
+    // is the cached count of the last refresh.
+    pub fn collect(&self) -> Result<Host> {
+        // Refresh the cached system information.
+        let pid = sysinfo::get_current_pid().ok();
+        let mut sys = self.system.lock().unwrap();
+        refresh_system(&mut sys, pid);
+
+        // Get the process information, the process may not be enumerable in
+        // some sandboxes, then only the global information is collected.
+        let (process_cpu_usage, process_memory) = process_usage(&sys, pid);
+
+        // Get the cpu and memory information, which are constrained by the cgroup limits
+        // if the dfdaemon runs in a cgroup limited environment, e.g. the kubernetes pod.
+        let limits = cgroup_limits();
+        let cpu = constrain_cpu(make_cpu(&sys, process_cpu_usage), &limits);
+        let memory = constrain_memory(make_memory(&sys, process_memory), process_memory, &limits);
+        drop(sys);
+
+        // Get the network information.
+        let tcp_connections = *self.tcp_connections.lock().unwrap();
+        let network = Network {
+            tcp_connection_count: tcp_connections.count,
+            upload_tcp_connection_count: tcp_connections.upload_count,
+            idc: self.config.host.idc.clone(),
+            location: self.config.host.location.clone(),
+        };
+
+        // Get the disk information, the host is still collected without the disk
+        // information if the storage directory is unavailable.
+        let disk = collect_disk(self.config.storage.dir.as_path());
+
+        Ok(make_host(&self.host, cpu, memory, network, disk))
+    }
+
+    // refresh_tcp_connections refreshes the cached count of the tcp connections in
+    // the background, the refresh is skipped if the previous refresh is not finished.
+    pub fn refresh_tcp_connections(&self) {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+
+        if self.tcp_connections_refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let upload_port = self.config.upload.server.port;
+        let cache = self.tcp_connections.clone();
+        let refreshing = self.tcp_connections_refreshing.clone();
+        tokio::task::spawn_blocking(move || {
+            // If the proc files are not readable, e.g. permission denied,
+            // collect zero connections instead of the stale count.
+            let connections =
+                tcp_connections(Path::new("/proc/self"), upload_port).unwrap_or_else(|err| {
+                    debug!("count tcp connections failed: {}", err);
+                    TCPConnections::default()
+                });
+
+            *cache.lock().unwrap() = connections;
+
+            refreshing.store(false, Ordering::Release);
+        });
+    }
+}
+
 // Announcer is used to announce the dfdaemon information to the manager and scheduler.
 pub struct SchedulerAnnouncer {
     // config is the configuration of the dfdaemon.
@@ -116,8 +334,18 @@ pub struct SchedulerAnnouncer {
     // host_id is the id of the host.
     host_id: String,
 
-    // scheduler_client is the grpc client of the scheduler.
-    scheduler_client: Arc<SchedulerClient>,
+    // dynconfig is the dynamic configuration of the dfdaemon.
+    dynconfig: Arc<Dynconfig>,
+
+    // scheduler_client is the client of the scheduler, it is the grpc client of the
+    // scheduler in the dfdaemon.
+    scheduler_client: Arc<dyn SchedulerAnnounce>,
+
+    // host_info_collector collects the information of the announced host.
+    host_info_collector: Arc<HostInfoCollector>,
+
+    // announce_trigger is used to announce the host to the scheduler immediately.
+    announce_trigger: AnnounceTrigger,
 
     // shutdown is used to shutdown the announcer.
     shutdown: shutdown::Shutdown,
@@ -126,43 +354,123 @@ pub struct SchedulerAnnouncer {
     _shutdown_complete: mpsc::UnboundedSender<()>,
 }
 
+// AnnounceTrigger is the handle to make the scheduler announcer announce the host immediately,
+// the triggers are coalesced into one announcement if the announcer has not handled them.
+#[derive(Clone, Default)]
+pub struct AnnounceTrigger {
+    // notify is used to wake up the announce loop.
+    notify: Arc<Notify>,
+}
+
+// AnnounceTrigger implements the trigger of the scheduler announcer.
+impl AnnounceTrigger {
+    // trigger makes the scheduler announcer announce the host immediately, and the interval
+    // of the announcements is reset.
+    pub fn trigger(&self) {
+        self.notify.notify_one();
+    }
+}
+
 // SchedulerAnnouncer implements the scheduler announcer of the dfdaemon.
 impl SchedulerAnnouncer {
     // new creates a new scheduler announcer.
     pub async fn new(
         config: Arc<Config>,
         host_id: String,
-        scheduler_client: Arc<SchedulerClient>,
+        dynconfig: Arc<Dynconfig>,
+        scheduler_client: Arc<dyn SchedulerAnnounce>,
         shutdown: shutdown::Shutdown,
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
     ) -> Result<Self> {
+        // The system is refreshed by the collector before the first announcement, then the
+        // cpu usage of the first announcement is calculated from this refresh.
+        let host_info_collector = Arc::new(HostInfoCollector::new(config.clone(), &host_id)?);
         let announcer = Self {
             config,
             host_id,
+            dynconfig,
             scheduler_client,
+            host_info_collector,
+            announce_trigger: AnnounceTrigger::default(),
             shutdown,
             _shutdown_complete: shutdown_complete_tx,
         };
 
-        // Initialize the scheduler announcer.
-        announcer
-            .scheduler_client
-            .init_announce_host(announcer.make_announce_host_request()?)
-            .await?;
+        // Count the tcp connections before the first announcement.
+        announcer.host_info_collector.refresh_tcp_connections();
+        tokio::time::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL).await;
+
+        // Initialize the scheduler announcer, if the scheduler cluster is not resolved yet
+        // or the scheduler is unavailable, the dfdaemon starts anyway and the run loop
+        // announces the host immediately with the backoff of the retries.
+        let scheduler_cluster_id = scheduler_cluster_id(&*announcer.dynconfig.data.read().await);
+        if let Err(err) = init_announce_host(
+            &*announcer.scheduler_client,
+            scheduler_cluster_id,
+            |scheduler_cluster_id| announcer.make_announce_host_request(scheduler_cluster_id),
+        )
+        .await
+        {
+            error!(
+                "init announce host to scheduler failed, retry in run loop: {}",
+                err
+            );
+            announcer.announce_trigger.trigger();
+        }
+
         Ok(announcer)
     }
 
+    // announce_trigger returns the handle to announce the host to the scheduler immediately.
+    pub fn announce_trigger(&self) -> AnnounceTrigger {
+        self.announce_trigger.clone()
+    }
+
+    // host_info_collector returns the collector of the announced host, then the host state
+    // reported outside the announcer is the same as the announced one.
+    pub fn host_info_collector(&self) -> Arc<HostInfoCollector> {
+        self.host_info_collector.clone()
+    }
+
     // run announces the dfdaemon information to the scheduler.
     pub async fn run(&self) {
         // Clone the shutdown channel.
         let mut shutdown = self.shutdown.clone();
 
-        // Start the scheduler announcer.
-        let mut interval = tokio::time::interval(self.config.scheduler.announce_interval);
+        // The backoff of the retries is capped by the announce interval, then the
+        // retries never delay the announcement longer than the steady-state tick.
+        let mut retry = self.config.scheduler.announce_retry.clone();
+        retry.max_backoff = retry
+            .max_backoff
+            .min(self.config.scheduler.announce_interval);
+
+        // Start the scheduler announcer, the first tick is randomized in the jitter
+        // window to avoid the restarted peers announcing at the same time.
+        let interval = self.config.scheduler.announce_interval;
+        let jitter = self.config.scheduler.announce_interval_jitter;
+        let mut delay = first_jittered_interval(interval, jitter, &mut rand::thread_rng());
         loop {
             tokio::select! {
-                _ = interval.tick() => {
-                    let request = match self.make_announce_host_request() {
+                triggered = wait_for_announce(delay, &self.announce_trigger) => {
+                    // The interval is reset after the triggered announcement.
+                    if triggered {
+                        info!("announce host to scheduler by trigger");
+                    }
+                    delay = jittered_interval(interval, jitter, &mut rand::thread_rng());
+
+                    // Refresh the tcp connections for the next announcement.
+                    self.host_info_collector.refresh_tcp_connections();
+
+                    // If the scheduler cluster is not resolved by dynconfig, retry
+                    // on the next interval instead of announcing an invalid cluster.
+                    let data = self.dynconfig.data.read().await;
+                    let Some(scheduler_cluster_id) = scheduler_cluster_id(&data) else {
+                        info!("scheduler cluster is not resolved, skip announcing host");
+                        continue;
+                    };
+                    drop(data);
+
+                    let request = match self.make_announce_host_request(scheduler_cluster_id) {
                         Ok(request) => request,
                         Err(err) => {
                             error!("make announce host request failed: {}", err);
@@ -170,15 +478,33 @@ impl SchedulerAnnouncer {
                         }
                     };
 
-                    if let Err(err) = self.scheduler_client.announce_host(request).await {
-                        error!("announce host to scheduler failed: {}", err);
+                    // Retry the announcement with backoff, the shutdown signal interrupts
+                    // the retries and is handled by the next loop. The in-flight announcement
+                    // is drained before the host is deleted from the scheduler.
+                    let mut drain_shutdown = self.shutdown.clone();
+                    match drain(
+                        self.config.shutdown.announce_timeout,
+                        &mut drain_shutdown,
+                        announce_host_with_retry(
+                            &*self.scheduler_client,
+                            &retry,
+                            &mut shutdown,
+                            request,
+                        ),
+                    )
+                    .await
+                    {
+                        Some(Err(err)) => error!("announce host to scheduler failed: {}", err),
+                        Some(Ok(())) | None => {}
                     };
                 }
                 _ = shutdown.recv() => {
                     // Announce to scheduler shutting down with signals.
-                    if let Err(err) = self.scheduler_client.delete_host(DeleteHostRequest{
-                        host_id: self.host_id.clone(),
-                    }).await {
+                    if let Err(err) = delete_host_with_timeout(
+                        &*self.scheduler_client,
+                        self.config.shutdown.announce_timeout,
+                        self.host_id.clone(),
+                    ).await {
                         error!("delete host from scheduler failed: {}", err);
                     }
 
@@ -189,96 +515,1529 @@ impl SchedulerAnnouncer {
         }
     }
 
-    // make_announce_host_request makes the announce host request.
-    fn make_announce_host_request(&self) -> Result<AnnounceHostRequest> {
-        // If the seed peer is enabled, we should announce the seed peer to the scheduler.
-        let host_type = if self.config.seed_peer.enable {
-            self.config.seed_peer.kind
-        } else {
-            HostType::Normal
+    // make_announce_host_request makes the announce host request with the host collected
+    // by the host information collector.
+    fn make_announce_host_request(&self, scheduler_cluster_id: u64) -> Result<AnnounceHostRequest> {
+        let host = Host {
+            scheduler_cluster_id,
+            ..self.host_info_collector.collect()?
         };
 
-        // Get the system information.
-        let mut sys = System::new_all();
-        sys.refresh_all();
+        Ok(AnnounceHostRequest {
+            host: Some(host),
+            interval: Some(
+                prost_wkt_types::Duration::try_from(self.config.scheduler.announce_interval)
+                    .or_err(ErrorType::ParseError)?,
+            ),
+        })
+    }
+}
 
-        // Get the process information.
-        let process = sys.process(sysinfo::get_current_pid().unwrap()).unwrap();
+// cpu_times returns the cumulative cpu times in seconds since boot, sysinfo does
+// not expose the cpu times by mode, so read them from /proc/stat.
+#[cfg(target_os = "linux")]
+fn cpu_times() -> Option<CpuTimes> {
+    let content = std::fs::read_to_string("/proc/stat").ok()?;
 
-        // Get the cpu information.
-        let cpu = Cpu {
-            logical_count: sys.physical_core_count().unwrap_or_default() as u32,
-            physical_count: sys.physical_core_count().unwrap_or_default() as u32,
-            percent: sys.global_cpu_info().cpu_usage() as f64,
-            process_percent: process.cpu_usage() as f64,
+    // Clock ticks per second, which is the unit of the cpu times in /proc/stat.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks <= 0 {
+        return None;
+    }
+
+    parse_cpu_times(&content, ticks as f64)
+}
+
+// cpu_times returns none on the platforms without /proc/stat.
+#[cfg(not(target_os = "linux"))]
+fn cpu_times() -> Option<CpuTimes> {
+    None
+}
+
+// platform returns the distribution and the family of the distribution from the os-release.
+#[cfg(target_os = "linux")]
+fn platform() -> Option<(String, String)> {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| parse_os_release(&content))
+}
+
+// platform returns the distribution and the family of the distribution, which is
+// only available on linux.
+#[cfg(not(target_os = "linux"))]
+fn platform() -> Option<(String, String)> {
+    None
+}
+
+// parse_os_release parses the ID and ID_LIKE of the os-release, and returns the distribution
+// and the family of the distribution, e.g. ("ubuntu", "debian") and ("centos", "rhel").
+#[cfg(any(target_os = "linux", test))]
+fn parse_os_release(content: &str) -> Option<(String, String)> {
+    let value = |key: &str| {
+        content.lines().find_map(|line| {
+            let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+            Some(
+                value
+                    .trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .to_lowercase(),
+            )
+        })
+    };
+
+    let id = value("ID").filter(|id| !id.is_empty())?;
+    let id_like = value("ID_LIKE").unwrap_or_default();
+
+    // The family is the well-known distribution in the ID and ID_LIKE, otherwise the
+    // most generic distribution in the ID_LIKE, e.g. "opensuse suse".
+    let candidates: Vec<&str> = std::iter::once(id.as_str())
+        .chain(id_like.split_whitespace())
+        .collect();
+    let family = candidates
+        .iter()
+        .find_map(|candidate| match *candidate {
+            "debian" | "ubuntu" => Some("debian"),
+            "rhel" | "centos" | "fedora" => Some("rhel"),
+            "suse" | "opensuse" | "sles" => Some("suse"),
+            "arch" => Some("arch"),
+            "alpine" => Some("alpine"),
+            _ => None,
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| candidates.last().unwrap_or(&id.as_str()).to_string());
+
+    Some((id, family))
+}
+
+// parse_cpu_times parses the aggregate cpu line of /proc/stat into the cpu times in seconds.
+#[cfg(any(target_os = "linux", test))]
+fn parse_cpu_times(content: &str, ticks: f64) -> Option<CpuTimes> {
+    let line = content
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some("cpu"))?;
+
+    // Older kernels report fewer columns, so the missing columns are zero.
+    let mut values = [0.0; 10];
+    for (value, field) in values.iter_mut().zip(line.split_whitespace().skip(1)) {
+        *value = field.parse::<u64>().ok()? as f64 / ticks;
+    }
+
+    let [user, nice, system, idle, iowait, irq, softirq, steal, guest, guest_nice] = values;
+    Some(CpuTimes {
+        user,
+        system,
+        idle,
+        nice,
+        iowait,
+        irq,
+        softirq,
+        steal,
+        guest,
+        guest_nice,
+    })
+}
+
+// announce_host waits for the announcement of the host and collects the metrics of the
+// announcement, the retries of the announcement are counted as one announcement.
+async fn announce_host<Fut>(announcement: Fut) -> Result<()>
+where
+    Fut: Future<Output = Result<()>>,
+{
+    collect_announce_host_started_metrics();
+    let start = Instant::now();
+    let result = announcement.await;
+    collect_announce_host_finished_metrics(start.elapsed());
+    if let Err(err) = &result {
+        collect_announce_host_failure_metrics(err);
+    }
+
+    result
+}
+
+// deregister waits for the deregistration until the timeout, if the timeout is
+// exceeded, the deregistration is abandoned to avoid delaying the shutdown.
+async fn deregister<T, Fut>(timeout: Duration, operation: Fut) -> Result<()>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    match tokio::time::timeout(timeout, operation).await {
+        Ok(result) => result.map(|_| ()),
+        Err(_) => {
+            warn!("deregister timeout after {:?}, skip it", timeout);
+            Ok(())
+        }
+    }
+}
+
+// wait_for_announce waits for the next announcement, it returns true if the announcement is
+// triggered before the delay elapses.
+async fn wait_for_announce(delay: Duration, announce_trigger: &AnnounceTrigger) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => false,
+        _ = announce_trigger.notify.notified() => true,
+    }
+}
+
+// init_announce_host announces the host to the scheduler when the announcer is created,
+// it fails if the scheduler cluster is not resolved by dynconfig.
+async fn init_announce_host<F>(
+    scheduler_client: &dyn SchedulerAnnounce,
+    scheduler_cluster_id: Option<u64>,
+    make_request: F,
+) -> Result<()>
+where
+    F: FnOnce(u64) -> Result<AnnounceHostRequest>,
+{
+    let scheduler_cluster_id = scheduler_cluster_id.ok_or(Error::AvailableSchedulersNotFound)?;
+    announce_host(scheduler_client.init_announce_host(make_request(scheduler_cluster_id)?)).await
+}
+
+// announce_host_with_retry announces the host to the scheduler with retries, the retries
+// are interrupted by the shutdown signal.
+async fn announce_host_with_retry(
+    scheduler_client: &dyn SchedulerAnnounce,
+    retry: &AnnounceRetry,
+    shutdown: &mut shutdown::Shutdown,
+    request: AnnounceHostRequest,
+) -> Result<()> {
+    announce_host(retry_with_backoff(retry, shutdown, || {
+        scheduler_client.announce_host(request.clone())
+    }))
+    .await
+}
+
+// delete_host_with_timeout deletes the host from the scheduler when shutting down, the
+// deletion is abandoned after the timeout.
+async fn delete_host_with_timeout(
+    scheduler_client: &dyn SchedulerAnnounce,
+    timeout: Duration,
+    host_id: String,
+) -> Result<()> {
+    deregister(
+        timeout,
+        scheduler_client.delete_host(DeleteHostRequest { host_id }),
+    )
+    .await
+}
+
+// drain waits for the in-flight operation, if the shutdown signal is received before the
+// operation finishes, the operation is drained until the timeout and abandoned after that.
+async fn drain<T, Fut>(
+    timeout: Duration,
+    shutdown: &mut shutdown::Shutdown,
+    operation: Fut,
+) -> Option<T>
+where
+    Fut: Future<Output = T>,
+{
+    tokio::pin!(operation);
+    tokio::select! {
+        result = &mut operation => Some(result),
+        _ = shutdown.recv() => match tokio::time::timeout(timeout, operation).await {
+            Ok(result) => Some(result),
+            Err(_) => {
+                warn!("drain timeout after {:?}, skip it", timeout);
+                None
+            }
+        },
+    }
+}
+
+// register calls the operation with retries, and returns none if the shutdown signal
+// is received before the operation succeeds.
+async fn register<T, F, Fut>(
+    retry: &AnnounceRetry,
+    shutdown: &mut shutdown::Shutdown,
+    operation: F,
+) -> Result<Option<T>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    match retry_with_backoff(retry, shutdown, operation).await {
+        Ok(value) => Ok(Some(value)),
+        Err(_) if shutdown.is_shutdown() => Ok(None),
+        Err(err) => {
+            error!("register failed: {}", err);
+            Err(err)
+        }
+    }
+}
+
+// keepalive calls the operation on every interval with retries until the shutdown
+// signal is received, the first call is skipped because it has been called.
+async fn keepalive<T, F, Fut>(
+    interval: Duration,
+    retry: &AnnounceRetry,
+    shutdown: &mut shutdown::Shutdown,
+    mut operation: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(err) = retry_with_backoff(retry, shutdown, &mut operation).await {
+                    error!("keepalive failed: {}", err);
+                }
+            }
+            _ = shutdown.recv() => {
+                info!("keepalive shutting down");
+                return
+            }
+        }
+    }
+}
+
+// retry_with_backoff calls the operation until it succeeds, the retries are exhausted
+// or the shutdown signal is received, and returns the last error if it fails.
+async fn retry_with_backoff<T, F, Fut>(
+    retry: &AnnounceRetry,
+    shutdown: &mut shutdown::Shutdown,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        let err = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if attempt >= retry.max_retries {
+            return Err(err);
+        }
+
+        let backoff = backoff(retry, attempt);
+        attempt += 1;
+        error!(
+            "announce failed: {}, retry {} after {:?}",
+            err, attempt, backoff
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.recv() => {
+                info!("retry is interrupted by shutdown");
+                return Err(err);
+            }
+        }
+    }
+}
+
+// backoff returns the exponential backoff of the attempt with jitter, the backoff
+// is doubled from the initial backoff, capped by the max backoff and randomized
+// in [backoff / 2, backoff] to avoid the peers retrying at the same time.
+fn backoff(retry: &AnnounceRetry, attempt: u32) -> Duration {
+    let backoff = retry
+        .initial_backoff
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(retry.max_backoff);
+
+    let half = backoff / 2;
+    half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+}
+
+// jittered_interval returns the interval randomized in
+// [interval * (1 - jitter), interval * (1 + jitter)].
+fn jittered_interval<R: Rng>(interval: Duration, jitter: f64, rng: &mut R) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0);
+    if jitter == 0.0 {
+        return interval;
+    }
+
+    interval.mul_f64(rng.gen_range(1.0 - jitter..=1.0 + jitter))
+}
+
+// first_jittered_interval returns the delay of the first tick randomized in
+// [0, interval * jitter].
+fn first_jittered_interval<R: Rng>(interval: Duration, jitter: f64, rng: &mut R) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0);
+    if jitter == 0.0 {
+        return Duration::ZERO;
+    }
+
+    interval.mul_f64(rng.gen_range(0.0..=jitter))
+}
+
+// refresh_system refreshes the cpu, memory and the current process of the system,
+// the process table and components are not refreshed, because they are not announced.
+fn refresh_system(sys: &mut System, pid: Option<Pid>) {
+    sys.refresh_cpu();
+    sys.refresh_memory();
+    if let Some(pid) = pid {
+        sys.refresh_process(pid);
+    }
+}
+
+// process_usage returns the cpu usage and memory of the process, it returns
+// zero if the process is not found.
+fn process_usage(sys: &System, pid: Option<Pid>) -> (f32, u64) {
+    match pid.and_then(|pid| sys.process(pid)) {
+        Some(process) => (process.cpu_usage(), process.memory()),
+        None => {
+            warn!(
+                "process {:?} is not found, announce zero process usage",
+                pid
+            );
+            (0.0, 0)
+        }
+    }
+}
+
+// make_cpu makes the cpu information of the system and the process.
+fn make_cpu(sys: &System, process_cpu_usage: f32) -> Cpu {
+    Cpu {
+        logical_count: sys.physical_core_count().unwrap_or_default() as u32,
+        physical_count: sys.physical_core_count().unwrap_or_default() as u32,
+        percent: sys.global_cpu_info().cpu_usage() as f64,
+        process_percent: process_cpu_usage as f64,
+        times: cpu_times(),
+    }
+}
+
+// scheduler_cluster_id returns the scheduler cluster id resolved by dynconfig,
+// the id of the scheduler cluster starts from 1, so 0 is unresolved.
+fn scheduler_cluster_id(data: &Data) -> Option<u64> {
+    data.available_scheduler_cluster_id.filter(|id| *id != 0)
+}
+
+// collect_disk collects the disk information of the path, it returns none if the
+// disk information is unavailable, e.g. the storage directory is unmounted.
+fn collect_disk(path: &Path) -> Option<Disk> {
+    match make_disk(path) {
+        Ok(disk) => Some(disk),
+        Err(err) => {
+            warn!("get disk information of {:?} failed: {}", path, err);
+            None
+        }
+    }
+}
+
+// make_disk makes the disk information of the filesystem where the path is located.
+fn make_disk(path: &Path) -> Result<Disk> {
+    let stats = fs2::statvfs(path)?;
+    let (inodes_total, inodes_free) = inodes(path)?;
+    Ok(make_disk_by_stats(
+        stats.total_space(),
+        stats.available_space(),
+        inodes_total,
+        inodes_free,
+    ))
+}
+
+// make_disk_by_stats makes the disk information by the space and inode statistics.
+fn make_disk_by_stats(total: u64, free: u64, inodes_total: u64, inodes_free: u64) -> Disk {
+    let used = total.saturating_sub(free);
+
+    // Filesystems like btrfs report zero total inodes, so the percent
+    // calculation must tolerate the zero total.
+    let inodes_used = inodes_total.saturating_sub(inodes_free);
+
+    Disk {
+        total,
+        free,
+        used,
+        used_percent: percent(used, total),
+        inodes_total,
+        inodes_used,
+        inodes_free,
+        inodes_used_percent: percent(inodes_used, inodes_total),
+    }
+}
+
+// inodes returns the total and free inodes of the filesystem where the path is located.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn inodes(path: &Path) -> Result<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::InvalidParameter)?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return Err(Error::IO(std::io::Error::last_os_error()));
+    }
+
+    // The width of fsfilcnt_t differs across unix platforms.
+    Ok((stats.f_files as u64, stats.f_ffree as u64))
+}
+
+// inodes returns zero inodes on the platforms without inode semantics.
+#[cfg(not(unix))]
+fn inodes(_path: &Path) -> Result<(u64, u64)> {
+    Ok((0, 0))
+}
+
+// make_memory makes the memory information of the system and the process.
+fn make_memory(sys: &System, process_memory: u64) -> Memory {
+    Memory {
+        total: sys.total_memory(),
+        available: sys.available_memory(),
+        used: sys.used_memory(),
+        used_percent: percent(sys.used_memory(), sys.total_memory()),
+        process_used_percent: percent(process_memory, sys.total_memory()),
+        free: sys.free_memory(),
+    }
+}
+
+// DEFAULT_CGROUP_DIR is the default directory where the cgroup filesystem is mounted.
+#[cfg(target_os = "linux")]
+const DEFAULT_CGROUP_DIR: &str = "/sys/fs/cgroup";
+
+// CgroupLimits is the cpu and memory limits of the cgroup where the dfdaemon runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct CgroupLimits {
+    // cpu is the number of cpus allowed by the cpu quota.
+    cpu: Option<f64>,
+
+    // memory is the memory limit in bytes.
+    memory: Option<u64>,
+
+    // memory_usage is the memory usage of the cgroup in bytes.
+    memory_usage: Option<u64>,
+}
+
+// cgroup_limits returns the limits of the cgroup where the dfdaemon runs.
+#[cfg(target_os = "linux")]
+fn cgroup_limits() -> CgroupLimits {
+    read_cgroup_limits(Path::new(DEFAULT_CGROUP_DIR))
+}
+
+// cgroup_limits returns no limits on the platforms without cgroup.
+#[cfg(not(target_os = "linux"))]
+fn cgroup_limits() -> CgroupLimits {
+    CgroupLimits::default()
+}
+
+// read_cgroup_limits reads the cpu and memory limits of the cgroup mounted at the root,
+// both the unified hierarchy of cgroup v2 and the controller hierarchies of cgroup v1
+// are supported. The unlimited resource is none.
+#[cfg(any(target_os = "linux", test))]
+fn read_cgroup_limits(root: &Path) -> CgroupLimits {
+    let read = |path: &str| std::fs::read_to_string(root.join(path)).ok();
+    let parse = |content: String| content.trim().parse::<u64>().ok();
+
+    // The unified hierarchy has the cgroup.controllers file in the root, and the
+    // unlimited resources are "max".
+    if root.join("cgroup.controllers").exists() {
+        return CgroupLimits {
+            cpu: read("cpu.max").and_then(|content| {
+                let mut fields = content.split_whitespace();
+                let quota = fields.next()?.parse::<f64>().ok()?;
+                let period = fields.next()?.parse::<f64>().ok()?;
+                cpu_limit(quota, period)
+            }),
+            memory: read("memory.max").and_then(parse),
+            memory_usage: read("memory.current").and_then(parse),
+        };
+    }
+
+    // The cpu quota of cgroup v1 is -1 if unlimited, and the memory limit is a large
+    // value beyond the host memory, which is ignored when constraining the memory.
+    CgroupLimits {
+        cpu: read("cpu/cpu.cfs_quota_us").and_then(|quota| {
+            let quota = quota.trim().parse::<f64>().ok()?;
+            let period = read("cpu/cpu.cfs_period_us")?.trim().parse::<f64>().ok()?;
+            cpu_limit(quota, period)
+        }),
+        memory: read("memory/memory.limit_in_bytes").and_then(parse),
+        memory_usage: read("memory/memory.usage_in_bytes").and_then(parse),
+    }
+}
 
-            // TODO: Get the cpu times.
-            times: None,
+// cpu_limit returns the number of cpus allowed by the cpu quota in the period.
+#[cfg(any(target_os = "linux", test))]
+fn cpu_limit(quota: f64, period: f64) -> Option<f64> {
+    if quota <= 0.0 || period <= 0.0 {
+        return None;
+    }
+
+    Some(quota / period)
+}
+
+// constrain_cpu constrains the cpu counts of the host by the cpu limit of the cgroup,
+// the fractional cpu limit is rounded up, e.g. the 1.5 cpus limit is 2 cpus.
+fn constrain_cpu(mut cpu: Cpu, limits: &CgroupLimits) -> Cpu {
+    if let Some(limit) = limits.cpu {
+        let count = limit.ceil() as u32;
+        if cpu.logical_count == 0 || count < cpu.logical_count {
+            cpu.logical_count = count;
+        }
+
+        if cpu.physical_count == 0 || count < cpu.physical_count {
+            cpu.physical_count = count;
+        }
+    }
+
+    cpu
+}
+
+// constrain_memory constrains the memory of the host by the memory limit of the cgroup,
+// the memory usage of the cgroup is used instead of the host memory usage if present.
+fn constrain_memory(memory: Memory, process_memory: u64, limits: &CgroupLimits) -> Memory {
+    let Some(total) = limits.memory.filter(|limit| *limit < memory.total) else {
+        return memory;
+    };
+
+    let used = limits.memory_usage.unwrap_or(memory.used).min(total);
+    Memory {
+        total,
+        available: total - used,
+        used,
+        used_percent: percent(used, total),
+        process_used_percent: percent(process_memory, total),
+        free: total - used,
+    }
+}
+
+// percent calculates the percentage of used in total, the range of the result is [0, 100].
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    (used as f64 / total as f64 * 100.0).clamp(0.0, 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::sync::atomic::AtomicUsize;
+    use tempdir::TempDir;
+
+    // MockScheduler is the mock of the scheduler, the announcements fail until the count
+    // of the failures is reached, and the deletion of the host hangs if it is set.
+    #[derive(Default)]
+    struct MockScheduler {
+        // announce_failures is the count of the failed announcements.
+        announce_failures: usize,
+
+        // announce_count is the count of the announcements.
+        announce_count: AtomicUsize,
+
+        // delete_host_hangs indicates whether the deletion of the host hangs.
+        delete_host_hangs: bool,
+
+        // deleted_host_id is the id of the deleted host.
+        deleted_host_id: Mutex<Option<String>>,
+    }
+
+    // MockScheduler implements the SchedulerAnnounce trait.
+    #[tonic::async_trait]
+    impl SchedulerAnnounce for MockScheduler {
+        async fn init_announce_host(&self, request: AnnounceHostRequest) -> Result<()> {
+            self.announce_host(request).await
+        }
+
+        async fn announce_host(&self, _request: AnnounceHostRequest) -> Result<()> {
+            if self.announce_count.fetch_add(1, Ordering::SeqCst) < self.announce_failures {
+                return Err(Error::Unknown("scheduler is unavailable".to_string()));
+            }
+
+            Ok(())
+        }
+
+        async fn delete_host(&self, request: DeleteHostRequest) -> Result<()> {
+            if self.delete_host_hangs {
+                std::future::pending::<()>().await;
+            }
+
+            *self.deleted_host_id.lock().unwrap() = Some(request.host_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_calculate_memory_used_percent() {
+        let total = 16 * 1024 * 1024 * 1024;
+        let used = 4 * 1024 * 1024 * 1024;
+        let memory = Memory {
+            total,
+            available: total - used,
+            used,
+            used_percent: percent(used, total),
+            process_used_percent: percent(512 * 1024 * 1024, total),
+            free: total - used,
         };
 
-        // Get the memory information.
+        assert_eq!(memory.used_percent, 25.0);
+        assert!(memory.process_used_percent > 0.0 && memory.process_used_percent < 100.0);
+        assert_eq!(percent(used, 0), 0.0);
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(total + 1, total), 100.0);
+    }
+
+    #[test]
+    fn should_make_memory_on_live_system() {
+        let mut sys = System::new();
+        sys.refresh_memory();
+
+        let memory = make_memory(&sys, sys.used_memory() / 2);
+        assert!(memory.total > 0);
+        assert!(memory.used_percent > 0.0 && memory.used_percent <= 100.0);
+        assert!(memory.process_used_percent > 0.0 && memory.process_used_percent <= 100.0);
+    }
+
+    #[test]
+    fn should_read_cgroup_v2_limits() {
+        let dir = tempdir::TempDir::new("cgroup").unwrap();
+        let write =
+            |path: &str, content: &str| std::fs::write(dir.path().join(path), content).unwrap();
+        write(
+            "cgroup.controllers",
+            "cpuset cpu io memory hugetlb pids rdma misc\n",
+        );
+        write("cpu.max", "150000 100000\n");
+        write("memory.max", "2147483648\n");
+        write("memory.current", "536870912\n");
+        assert_eq!(
+            read_cgroup_limits(dir.path()),
+            CgroupLimits {
+                cpu: Some(1.5),
+                memory: Some(2147483648),
+                memory_usage: Some(536870912),
+            }
+        );
+
+        // The unlimited resources are none.
+        write("cpu.max", "max 100000\n");
+        write("memory.max", "max\n");
+        assert_eq!(
+            read_cgroup_limits(dir.path()),
+            CgroupLimits {
+                cpu: None,
+                memory: None,
+                memory_usage: Some(536870912),
+            }
+        );
+    }
+
+    #[test]
+    fn should_read_cgroup_v1_limits() {
+        let dir = tempdir::TempDir::new("cgroup").unwrap();
+        std::fs::create_dir_all(dir.path().join("cpu")).unwrap();
+        std::fs::create_dir_all(dir.path().join("memory")).unwrap();
+        let write =
+            |path: &str, content: &str| std::fs::write(dir.path().join(path), content).unwrap();
+        write("cpu/cpu.cfs_quota_us", "200000\n");
+        write("cpu/cpu.cfs_period_us", "100000\n");
+        write("memory/memory.limit_in_bytes", "1073741824\n");
+        write("memory/memory.usage_in_bytes", "268435456\n");
+        assert_eq!(
+            read_cgroup_limits(dir.path()),
+            CgroupLimits {
+                cpu: Some(2.0),
+                memory: Some(1073741824),
+                memory_usage: Some(268435456),
+            }
+        );
+
+        // The unlimited cpu quota is none.
+        write("cpu/cpu.cfs_quota_us", "-1\n");
+        assert_eq!(read_cgroup_limits(dir.path()).cpu, None);
+
+        // No limits without the cgroup filesystem.
+        let dir = tempdir::TempDir::new("cgroup").unwrap();
+        assert_eq!(read_cgroup_limits(dir.path()), CgroupLimits::default());
+    }
+
+    #[test]
+    fn should_constrain_cpu_and_memory_by_cgroup_limits() {
+        let cpu = Cpu {
+            logical_count: 16,
+            physical_count: 8,
+            ..Default::default()
+        };
+        let total = 16 * 1024 * 1024 * 1024;
         let memory = Memory {
-            total: sys.total_memory(),
-            available: sys.available_memory(),
-            used: sys.used_memory(),
-            used_percent: (sys.used_memory() / sys.total_memory()) as f64,
-
-            // TODO: Get the process used memory.
-            process_used_percent: 0 as f64,
-            free: sys.free_memory(),
+            total,
+            available: total / 2,
+            used: total / 2,
+            used_percent: 50.0,
+            process_used_percent: percent(1024 * 1024 * 1024, total),
+            free: total / 2,
         };
 
-        // Get the network information.
-        let network = Network {
-            // TODO: Get the count of the tcp connection.
-            tcp_connection_count: 0,
+        // The host-wide numbers are reported without the limits.
+        let limits = CgroupLimits::default();
+        assert_eq!(constrain_cpu(cpu.clone(), &limits), cpu);
+        assert_eq!(
+            constrain_memory(memory.clone(), 1024 * 1024 * 1024, &limits),
+            memory
+        );
 
-            // TODO: Get the count of the upload tcp connection.
-            upload_tcp_connection_count: 0,
-            idc: self.config.host.idc.clone(),
-            location: self.config.host.location.clone(),
+        // The constrained numbers are reported with the limits.
+        let limits = CgroupLimits {
+            cpu: Some(1.5),
+            memory: Some(4 * 1024 * 1024 * 1024),
+            memory_usage: Some(1024 * 1024 * 1024),
         };
+        let constrained_cpu = constrain_cpu(cpu.clone(), &limits);
+        assert_eq!(constrained_cpu.logical_count, 2);
+        assert_eq!(constrained_cpu.physical_count, 2);
 
-        // Get the build information.
-        let build = Build {
-            git_version: CARGO_PKG_VERSION.to_string(),
-            git_commit: Some(GIT_HASH.unwrap_or_default().to_string()),
-            go_version: None,
-            rust_version: Some(CARGO_PKG_RUSTC_VERSION.to_string()),
-            platform: None,
+        let constrained_memory = constrain_memory(memory.clone(), 1024 * 1024 * 1024, &limits);
+        assert_eq!(constrained_memory.total, 4 * 1024 * 1024 * 1024);
+        assert_eq!(constrained_memory.used, 1024 * 1024 * 1024);
+        assert_eq!(constrained_memory.available, 3 * 1024 * 1024 * 1024);
+        assert_eq!(constrained_memory.free, 3 * 1024 * 1024 * 1024);
+        assert_eq!(constrained_memory.used_percent, 25.0);
+        assert_eq!(constrained_memory.process_used_percent, 25.0);
+
+        // The memory limit beyond the host memory is ignored, e.g. the unlimited cgroup v1.
+        let limits = CgroupLimits {
+            cpu: Some(32.0),
+            memory: Some(9223372036854771712),
+            memory_usage: Some(1024 * 1024 * 1024),
         };
+        assert_eq!(constrain_cpu(cpu.clone(), &limits), cpu);
+        assert_eq!(
+            constrain_memory(memory.clone(), 1024 * 1024 * 1024, &limits),
+            memory
+        );
+    }
 
-        // Struct the host information.
-        let host = Host {
-            id: self.host_id.to_string(),
-            r#type: host_type as u32,
-            hostname: self.config.host.hostname.clone(),
-            ip: self.config.host.ip.unwrap().to_string(),
-            port: self.config.upload.server.port as i32,
-            download_port: self.config.upload.server.port as i32,
-            os: env::consts::OS.to_string(),
-            platform: env::consts::OS.to_string(),
-            platform_family: env::consts::FAMILY.to_string(),
-            platform_version: sys.os_version().unwrap_or_default(),
-            kernel_version: sys.kernel_version().unwrap_or_default(),
-            cpu: Some(cpu),
-            memory: Some(memory),
-            network: Some(network),
-
-            // TODO: Get the disk information.
-            disk: None,
-            build: Some(build),
-
-            // TODO: Get scheduler cluster id from dynconfig.
-            scheduler_cluster_id: 0,
+    #[test]
+    fn should_fail_without_host_ip() {
+        let mut config = Config::default();
+        assert!(matches!(host_ip(&config), Err(Error::InvalidParameter)));
+        assert!(matches!(
+            make_update_seed_peer_request(&config),
+            Err(Error::InvalidParameter)
+        ));
+
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+        assert_eq!(host_ip(&config).unwrap(), "127.0.0.1");
+        assert_eq!(
+            make_update_seed_peer_request(&config).unwrap().ip,
+            "127.0.0.1"
+        );
+    }
+
+    #[test]
+    fn should_keep_static_host_fields_across_announcements() {
+        let mut config = Config::default();
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+        config.upload.server.port = 4000;
+
+        let mut sys = System::new();
+        let template = make_host_template(&config, "foo", &sys).unwrap();
+        let mut make = || {
+            refresh_system(&mut sys, None);
+            make_host(
+                &template,
+                make_cpu(&sys, 0.0),
+                make_memory(&sys, 0),
+                Network::default(),
+                None,
+            )
         };
 
-        Ok(AnnounceHostRequest {
-            host: Some(host),
-            interval: Some(
-                prost_wkt_types::Duration::try_from(self.config.scheduler.announce_interval)
-                    .or_err(ErrorType::ParseError)?,
-            ),
+        let first = make();
+        let second = make();
+
+        // The static fields are cloned from the template.
+        for host in [&first, &second] {
+            assert_eq!(host.id, "foo");
+            assert_eq!(host.ip, "127.0.0.1");
+            assert_eq!(host.port, 4000);
+            assert_eq!(host.hostname, template.hostname);
+            assert_eq!(host.os, template.os);
+            assert_eq!(host.platform, template.platform);
+            assert_eq!(host.platform_family, template.platform_family);
+            assert_eq!(host.platform_version, template.platform_version);
+            assert_eq!(host.kernel_version, template.kernel_version);
+            assert_eq!(host.build, template.build);
+            assert_eq!(host.r#type, template.r#type);
+            assert_eq!(host.scheduler_cluster_id, 0);
+        }
+
+        // The template is missing without the host ip.
+        config.host.ip = None;
+        assert!(matches!(
+            make_host_template(&config, "foo", &sys),
+            Err(Error::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn should_collect_host_info() {
+        let dir = TempDir::new("announcer").unwrap();
+        let mut config = Config::default();
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+        config.host.idc = Some("idc".to_string());
+        config.storage.dir = dir.path().to_path_buf();
+
+        let collector = HostInfoCollector::new(Arc::new(config), "foo").unwrap();
+        let host = collector.collect().unwrap();
+        assert_eq!(host.id, "foo");
+        assert_eq!(host.ip, "127.0.0.1");
+        assert_eq!(host.scheduler_cluster_id, 0);
+        assert!(host.cpu.is_some());
+        assert!(host.memory.unwrap().total > 0);
+        assert_eq!(host.network.unwrap().idc, Some("idc".to_string()));
+        assert!(host.disk.unwrap().total > 0);
+
+        // The static fields are the same across the collections.
+        let again = collector.collect().unwrap();
+        assert_eq!(again.id, host.id);
+        assert_eq!(again.hostname, host.hostname);
+        assert_eq!(again.build, host.build);
+    }
+
+    #[test]
+    fn should_collect_host_info_without_storage_dir() {
+        let dir = TempDir::new("announcer").unwrap();
+        let mut config = Config::default();
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+        config.storage.dir = dir.path().join("missing");
+
+        let collector = HostInfoCollector::new(Arc::new(config), "foo").unwrap();
+        assert!(collector.collect().unwrap().disk.is_none());
+    }
+
+    #[test]
+    fn should_fail_to_create_host_info_collector_without_host_ip() {
+        assert!(matches!(
+            HostInfoCollector::new(Arc::new(Config::default()), "foo"),
+            Err(Error::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn should_announce_build_platform() {
+        let mut config = Config::default();
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+
+        let template = make_host_template(&config, "foo", &System::new()).unwrap();
+        let platform = template.build.unwrap().platform.unwrap();
+        assert!(!platform.is_empty());
+        assert_eq!(platform, env!("BUILD_PLATFORM"));
+    }
+
+    #[test]
+    fn should_announce_download_port() {
+        let mut config = Config::default();
+        config.host.ip = Some("127.0.0.1".parse().unwrap());
+        config.upload.server.port = 4000;
+
+        // The download port falls back to the upload port.
+        let request = make_update_seed_peer_request(&config).unwrap();
+        assert_eq!(request.port, 4000);
+        assert_eq!(request.download_port, 4000);
+        assert_eq!(download_port(&config), 4000);
+
+        // The download port diverges from the upload port.
+        config.upload.server.download_port = Some(4010);
+        let request = make_update_seed_peer_request(&config).unwrap();
+        assert_eq!(request.port, 4000);
+        assert_eq!(request.download_port, 4010);
+        assert_eq!(download_port(&config), 4010);
+    }
+
+    #[test]
+    fn should_calculate_backoff_with_jitter() {
+        let retry = AnnounceRetry {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+
+        for (attempt, expected) in [(0, 1), (1, 2), (2, 4), (3, 5), (40, 5)] {
+            let expected = Duration::from_secs(expected);
+            let backoff = backoff(&retry, attempt);
+            assert!(backoff >= expected / 2 && backoff <= expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_retry_with_backoff_until_success() {
+        let retry = AnnounceRetry {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(20),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+
+        // The operation fails 3 times and then succeeds.
+        let calls = std::cell::Cell::new(0);
+        let result = retry_with_backoff(&retry, &mut shutdown, || {
+            calls.set(calls.get() + 1);
+            let failed = calls.get() <= 3;
+            async move {
+                if failed {
+                    return Err(Error::Unknown("announce failed".to_string()));
+                }
+
+                Ok(())
+            }
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(calls.get(), 4);
+
+        // The operation always fails and the retries are exhausted.
+        calls.set(0);
+        let result: Result<()> = retry_with_backoff(&retry, &mut shutdown, || {
+            calls.set(calls.get() + 1);
+            async { Err(Error::Unknown("announce failed".to_string())) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 6);
+    }
+
+    #[tokio::test]
+    async fn should_retry_with_exponential_backoff_timing() {
+        let retry = AnnounceRetry {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(80),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+
+        // The operation fails 3 times, the backoffs are in [10ms, 20ms], [20ms, 40ms]
+        // and [40ms, 80ms] with jitter.
+        let calls = std::cell::Cell::new(0);
+        let start = std::time::Instant::now();
+        let result = retry_with_backoff(&retry, &mut shutdown, || {
+            calls.set(calls.get() + 1);
+            let failed = calls.get() <= 3;
+            async move {
+                if failed {
+                    return Err(Error::Unknown("announce failed".to_string()));
+                }
+
+                Ok(())
+            }
+        })
+        .await;
+        let elapsed = start.elapsed();
+        assert!(result.is_ok());
+        assert_eq!(calls.get(), 4);
+        assert!(elapsed >= Duration::from_millis(70));
+        assert!(elapsed < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn should_register_after_retries() {
+        let retry = AnnounceRetry {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+
+        // The registration fails twice and then succeeds.
+        let calls = std::cell::Cell::new(0);
+        let result = register(&retry, &mut shutdown, || {
+            calls.set(calls.get() + 1);
+            let attempt = calls.get();
+            async move {
+                if attempt <= 2 {
+                    return Err(Error::Unknown("manager is unreachable".to_string()));
+                }
+
+                Ok(attempt)
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), Some(3));
+
+        // The registration fails after the retries are exhausted.
+        let result: Result<Option<()>> = register(&retry, &mut shutdown, || async {
+            Err(Error::Unknown("manager is unreachable".to_string()))
+        })
+        .await;
+        assert!(result.is_err());
+
+        // The registration is abandoned without error if shutting down.
+        let retry = AnnounceRetry {
+            max_retries: 10,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(10),
+        };
+        let trigger = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            trigger.trigger();
+        });
+        let result: Result<Option<()>> = register(&retry, &mut shutdown, || async {
+            Err(Error::Unknown("manager is unreachable".to_string()))
+        })
+        .await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_deregister_within_timeout() {
+        // The hanging deregistration is abandoned after the timeout.
+        let start = std::time::Instant::now();
+        let result = deregister(
+            Duration::from_millis(50),
+            std::future::pending::<Result<()>>(),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The result of the deregistration is returned before the timeout.
+        let result = deregister(Duration::from_secs(5), async {
+            Err::<(), _>(Error::Unknown("delete host failed".to_string()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(deregister(Duration::from_secs(5), async { Ok(1) })
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_drain_within_timeout() {
+        // The operation is awaited without the shutdown signal.
+        let mut shutdown = shutdown::Shutdown::new();
+        assert_eq!(
+            drain(Duration::from_millis(50), &mut shutdown, async { 1 }).await,
+            Some(1)
+        );
+
+        // The in-flight operation is drained after the shutdown signal.
+        shutdown.trigger();
+        let result = drain(Duration::from_secs(5), &mut shutdown, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            1
+        })
+        .await;
+        assert_eq!(result, Some(1));
+
+        // The hanging operation is abandoned after the timeout, then the shutdown
+        // continues with the deregistration.
+        let start = std::time::Instant::now();
+        let result = drain(
+            Duration::from_millis(50),
+            &mut shutdown,
+            std::future::pending::<()>(),
+        )
+        .await;
+        assert!(result.is_none());
+        assert!(deregister(
+            Duration::from_millis(50),
+            std::future::pending::<Result<()>>(),
+        )
+        .await
+        .is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn should_announce_by_trigger() {
+        let announce_trigger = AnnounceTrigger::default();
+        let interval = Duration::from_secs(3600);
+
+        // The triggered announcement does not wait for the interval.
+        let trigger = announce_trigger.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            trigger.trigger();
+        });
+        let start = std::time::Instant::now();
+        assert!(wait_for_announce(interval, &announce_trigger).await);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The concurrent triggers are coalesced into one announcement.
+        for _ in 0..3 {
+            announce_trigger.trigger();
+        }
+        assert!(wait_for_announce(interval, &announce_trigger).await);
+        assert!(!wait_for_announce(Duration::from_millis(10), &announce_trigger).await);
+    }
+
+    #[tokio::test]
+    async fn should_announce_host_to_mock_scheduler() {
+        let retry = AnnounceRetry {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+
+        // The announcement succeeds after the retries.
+        let scheduler = MockScheduler {
+            announce_failures: 2,
+            ..Default::default()
+        };
+        assert!(announce_host_with_retry(
+            &scheduler,
+            &retry,
+            &mut shutdown,
+            AnnounceHostRequest::default()
+        )
+        .await
+        .is_ok());
+        assert_eq!(scheduler.announce_count.load(Ordering::SeqCst), 3);
+
+        // The announcement fails if the retries are exhausted.
+        let scheduler = MockScheduler {
+            announce_failures: 10,
+            ..Default::default()
+        };
+        assert!(announce_host_with_retry(
+            &scheduler,
+            &retry,
+            &mut shutdown,
+            AnnounceHostRequest::default()
+        )
+        .await
+        .is_err());
+        assert_eq!(scheduler.announce_count.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn should_fail_init_announce_host_without_aborting() {
+        // The scheduler cluster is not resolved, the request is not made.
+        let scheduler = MockScheduler::default();
+        let result = init_announce_host(&scheduler, None, |_| {
+            panic!("request should not be made without scheduler cluster")
+        })
+        .await;
+        assert!(matches!(result, Err(Error::AvailableSchedulersNotFound)));
+        assert_eq!(scheduler.announce_count.load(Ordering::SeqCst), 0);
+
+        // The scheduler is unavailable, the error is returned without retries, then
+        // the run loop retries the announcement.
+        let scheduler = MockScheduler {
+            announce_failures: 1,
+            ..Default::default()
+        };
+        assert!(
+            init_announce_host(&scheduler, Some(1), |_| Ok(AnnounceHostRequest::default()))
+                .await
+                .is_err()
+        );
+        assert_eq!(scheduler.announce_count.load(Ordering::SeqCst), 1);
+
+        // The scheduler is available, the host is announced.
+        let scheduler = MockScheduler::default();
+        assert!(
+            init_announce_host(&scheduler, Some(1), |_| Ok(AnnounceHostRequest::default()))
+                .await
+                .is_ok()
+        );
+        assert_eq!(scheduler.announce_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn should_delete_host_from_mock_scheduler() {
+        let scheduler = MockScheduler::default();
+        delete_host_with_timeout(&scheduler, Duration::from_secs(5), "foo".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            scheduler.deleted_host_id.lock().unwrap().as_deref(),
+            Some("foo")
+        );
+
+        // The hanging deletion does not block the shutdown.
+        let scheduler = MockScheduler {
+            delete_host_hangs: true,
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        delete_host_with_timeout(&scheduler, Duration::from_millis(50), "foo".to_string())
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(scheduler.deleted_host_id.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_keepalive_until_shutdown() {
+        let retry = AnnounceRetry {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+        let trigger = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            trigger.trigger();
+        });
+
+        // The odd calls fail and are retried, so every interval has two calls.
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        keepalive(Duration::from_millis(20), &retry, &mut shutdown, || {
+            let failed = calls.fetch_add(1, Ordering::SeqCst) % 2 == 0;
+            async move {
+                if failed {
+                    return Err(Error::Unknown("update seed peer failed".to_string()));
+                }
+
+                Ok(())
+            }
+        })
+        .await;
+
+        let calls = calls.load(Ordering::SeqCst);
+        assert!(shutdown.is_shutdown());
+        assert!(calls >= 4, "calls: {}", calls);
+        assert!(calls <= 20, "calls: {}", calls);
+    }
+
+    #[tokio::test]
+    async fn should_interrupt_retry_by_shutdown() {
+        let retry = AnnounceRetry {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(3600),
+            max_backoff: Duration::from_secs(3600),
+        };
+        let mut shutdown = shutdown::Shutdown::new();
+        shutdown.trigger();
+
+        let calls = std::cell::Cell::new(0);
+        let result: Result<()> = tokio::time::timeout(
+            Duration::from_secs(5),
+            retry_with_backoff(&retry, &mut shutdown, || {
+                calls.set(calls.get() + 1);
+                async { Err(Error::Unknown("announce failed".to_string())) }
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+        assert!(shutdown.is_shutdown());
+    }
+
+    #[test]
+    fn should_keep_jittered_interval_in_bounds() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let interval = Duration::from_secs(300);
+        for _ in 0..1000 {
+            let delay = jittered_interval(interval, 0.1, &mut rng);
+            assert!(delay >= Duration::from_secs(270) && delay <= Duration::from_secs(330));
+
+            let delay = first_jittered_interval(interval, 0.1, &mut rng);
+            assert!(delay <= Duration::from_secs(30));
+        }
+
+        assert_eq!(jittered_interval(interval, 0.0, &mut rng), interval);
+        assert_eq!(
+            first_jittered_interval(interval, 0.0, &mut rng),
+            Duration::ZERO
+        );
+
+        // The jitter out of range is clamped, the interval is never negative.
+        for _ in 0..1000 {
+            let delay = jittered_interval(interval, 2.0, &mut rng);
+            assert!(delay <= Duration::from_secs(600));
+        }
+    }
+
+    #[test]
+    fn should_refresh_cached_system() {
+        let pid = sysinfo::get_current_pid().unwrap();
+        let mut sys = System::new();
+        refresh_system(&mut sys, Some(pid));
+        let cpus = sys.cpus().len();
+        assert!(cpus > 0);
+        assert!(sys.total_memory() > 0);
+        assert_eq!(sys.processes().len(), 1);
+
+        // The second refresh reuses the cached system without loading the process table.
+        std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+        refresh_system(&mut sys, Some(pid));
+        assert_eq!(sys.cpus().len(), cpus);
+        assert_eq!(sys.processes().len(), 1);
+        assert!(sys.process(pid).is_some());
+
+        let usage = sys.global_cpu_info().cpu_usage();
+        assert!((0.0..=100.0).contains(&usage));
+    }
+
+    #[test]
+    fn should_make_system_information_without_process() {
+        use sysinfo::PidExt;
+
+        // The process is not enumerable, the global information is still reported.
+        let pid = Some(Pid::from_u32(999_999_999));
+        let mut sys = System::new();
+        refresh_system(&mut sys, pid);
+        assert_eq!(process_usage(&sys, pid), (0.0, 0));
+        assert_eq!(process_usage(&sys, None), (0.0, 0));
+
+        let (process_cpu_usage, process_memory) = process_usage(&sys, pid);
+        let cpu = make_cpu(&sys, process_cpu_usage);
+        assert_eq!(cpu.process_percent, 0.0);
+
+        let memory = make_memory(&sys, process_memory);
+        assert!(memory.total > 0);
+        assert!(memory.used_percent > 0.0);
+        assert_eq!(memory.process_used_percent, 0.0);
+    }
+
+    #[test]
+    fn should_follow_dynconfig_scheduler_cluster_id() {
+        let mut data = Data::default();
+        assert_eq!(scheduler_cluster_id(&data), None);
+
+        data.available_scheduler_cluster_id = Some(0);
+        assert_eq!(scheduler_cluster_id(&data), None);
+
+        data.available_scheduler_cluster_id = Some(1);
+        assert_eq!(scheduler_cluster_id(&data), Some(1));
+
+        // Refreshing dynconfig switches to another scheduler cluster.
+        data.available_scheduler_cluster_id = Some(2);
+        assert_eq!(scheduler_cluster_id(&data), Some(2));
+    }
+
+    #[test]
+    fn should_make_disk_by_stats() {
+        let disk = make_disk_by_stats(1000, 250, 400, 100);
+        assert_eq!(disk.used, 750);
+        assert_eq!(disk.used_percent, 75.0);
+        assert_eq!(disk.inodes_used, 300);
+        assert_eq!(disk.inodes_used_percent, 75.0);
+
+        // The inode percentage follows the same convention as the space percentage.
+        let disk = make_disk_by_stats(400, 100, 1000, 250);
+        assert_eq!(disk.used_percent, percent(disk.used, disk.total));
+        assert_eq!(
+            disk.inodes_used_percent,
+            percent(disk.inodes_used, disk.inodes_total)
+        );
+
+        let disk = make_disk_by_stats(1000, 250, 0, 0);
+        assert_eq!(disk.inodes_used, 0);
+        assert_eq!(disk.inodes_used_percent, 0.0);
+    }
+
+    #[test]
+    fn should_make_disk_with_inodes() {
+        let dir = TempDir::new("announcer").unwrap();
+        let disk = make_disk(dir.path()).unwrap();
+        assert!(disk.total > 0);
+        assert_eq!(disk.used + disk.free, disk.total);
+        assert_eq!(disk.inodes_used + disk.inodes_free, disk.inodes_total);
+        assert!(disk.inodes_used_percent >= 0.0 && disk.inodes_used_percent <= 100.0);
+        assert!(make_disk(dir.path().join("not_exist").as_path()).is_err());
+
+        // The statvfs error is isolated from the host information.
+        assert!(collect_disk(dir.path()).is_some());
+        assert!(collect_disk(dir.path().join("not_exist").as_path()).is_none());
+    }
+
+    #[test]
+    fn should_parse_cpu_times() {
+        let content = "cpu  10132153 290696 3084719 46828483 16683 0 25195 0 175628 0
+cpu0 1393280 32966 572056 13343292 6130 0 17875 0 23933 0
+intr 1462898 0 0 0
+ctxt 2420547
+btime 1700000000
+";
+        let times = parse_cpu_times(content, 100.0).unwrap();
+        assert_eq!(times.user, 101321.53);
+        assert_eq!(times.nice, 2906.96);
+        assert_eq!(times.system, 30847.19);
+        assert_eq!(times.idle, 468284.83);
+        assert_eq!(times.iowait, 166.83);
+        assert_eq!(times.irq, 0.0);
+        assert_eq!(times.softirq, 251.95);
+        assert_eq!(times.steal, 0.0);
+        assert_eq!(times.guest, 1756.28);
+        assert_eq!(times.guest_nice, 0.0);
+
+        let times = parse_cpu_times("cpu  100 200 300 400\n", 100.0).unwrap();
+        assert_eq!(times.idle, 4.0);
+        assert_eq!(times.iowait, 0.0);
+
+        assert!(parse_cpu_times("cpu0 100 200 300 400\n", 100.0).is_none());
+        assert!(parse_cpu_times("cpu  100 abc 300 400\n", 100.0).is_none());
+    }
+
+    #[test]
+    fn should_parse_os_release() {
+        let content = r#"PRETTY_NAME="Ubuntu 22.04.4 LTS"
+NAME="Ubuntu"
+VERSION_ID="22.04"
+VERSION="22.04.4 LTS (Jammy Jellyfish)"
+VERSION_CODENAME=jammy
+ID=ubuntu
+ID_LIKE=debian
+HOME_URL="https://www.ubuntu.com/"
+UBUNTU_CODENAME=jammy
+"#;
+        assert_eq!(
+            parse_os_release(content).unwrap(),
+            ("ubuntu".to_string(), "debian".to_string())
+        );
+
+        let content = r#"NAME="CentOS Linux"
+VERSION="7 (Core)"
+ID="centos"
+ID_LIKE="rhel fedora"
+VERSION_ID="7"
+"#;
+        assert_eq!(
+            parse_os_release(content).unwrap(),
+            ("centos".to_string(), "rhel".to_string())
+        );
+
+        let content = "ID=debian\nVERSION_ID=\"12\"\n";
+        assert_eq!(
+            parse_os_release(content).unwrap(),
+            ("debian".to_string(), "debian".to_string())
+        );
+
+        // The unknown distribution is the family of itself.
+        assert_eq!(
+            parse_os_release("ID=foo\nID_LIKE=\"bar baz\"\n").unwrap(),
+            ("foo".to_string(), "baz".to_string())
+        );
+        assert_eq!(
+            parse_os_release("ID=foo\n").unwrap(),
+            ("foo".to_string(), "foo".to_string())
+        );
+
+        // The ID of the distribution is required.
+        assert!(parse_os_release("NAME=foo\nID_LIKE=debian\n").is_none());
+        assert!(parse_os_release("ID=\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn should_collect_announce_host_failure_metrics() {
+        let failures = || {
+            crate::metrics::ANNOUNCE_HOST_FAILURE_COUNT
+                .with_label_values(&["Unavailable"])
+                .get()
+        };
+        let count = || {
+            crate::metrics::ANNOUNCE_HOST_COUNT
+                .with_label_values(&[])
+                .get()
+        };
+        let (failures_before, count_before) = (failures(), count());
+
+        assert!(announce_host(async { Ok(()) }).await.is_ok());
+        assert_eq!(failures(), failures_before);
+
+        let result = announce_host(async {
+            Err(Error::TonicStatus(tonic::Status::unavailable(
+                "scheduler is unavailable",
+            )))
         })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(failures(), failures_before + 1);
+        assert_eq!(count(), count_before + 2);
     }
 }
@@ -15,8 +15,10 @@
  */
 
 use clap::Parser;
-use dragonfly_client_config::default_piece_length;
+use dragonfly_client_config::{default_piece_length, dfdaemon};
+use dragonfly_client_storage::Storage;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 // ExportCommand is the subcommand of export.
@@ -60,12 +62,50 @@ pub struct ExportCommand {
         help = "Specify the timeout for exporting a file"
     )]
     timeout: Duration,
+
+    #[arg(
+        long = "raw",
+        default_value_t = false,
+        conflicts_with_all = ["application", "tag", "piece_length", "timeout"],
+        help = "Export the task from the storage of dfdaemon to the task archive in the output directory, dfdaemon must be stopped"
+    )]
+    raw: bool,
+
+    #[arg(
+        long = "config",
+        default_value_os_t = dfdaemon::default_dfdaemon_config_path(),
+        requires = "raw",
+        help = "Specify dfdaemon's config file to find the storage in raw mode"
+    )]
+    config: PathBuf,
 }
 
 // Implement the execute for ExportCommand.
 impl ExportCommand {
     pub async fn execute(&self) -> Result<(), anyhow::Error> {
+        if self.raw {
+            return self.execute_raw().await;
+        }
+
         println!("ExportCommand is executed!");
         Ok(())
     }
+
+    // execute_raw exports the task from the storage of dfdaemon to the task archive,
+    // and prints the path of the task archive.
+    async fn execute_raw(&self) -> Result<(), anyhow::Error> {
+        let mut config = dfdaemon::Config::load(&self.config).await?;
+
+        // The storage is opened with keep to only read the task, otherwise it is removed
+        // when the storage is opened. The storage.keep of dfdaemon is not changed.
+        config.storage.keep = true;
+        let dir = config.storage.dir.clone();
+        let storage = Storage::new(Arc::new(config), dir.as_path()).await?;
+
+        let path = storage
+            .export_task(self.id.as_str(), self.output.as_path())
+            .await?;
+        println!("{}", path.display());
+        Ok(())
+    }
 }
@@ -16,9 +16,11 @@
 
 use clap::Parser;
 use dragonfly_client_config::{
-    default_piece_length, dfcache::default_dfcache_persistent_replica_count,
+    default_piece_length, dfcache::default_dfcache_persistent_replica_count, dfdaemon,
 };
+use dragonfly_client_storage::Storage;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 // ImportCommand is the subcommand of import.
@@ -70,12 +72,54 @@ pub struct ImportCommand {
         help = "Specify the timeout for importing a file"
     )]
     timeout: Duration,
+
+    #[arg(
+        long = "raw",
+        default_value_t = false,
+        conflicts_with_all = ["persistent_replica_count", "application", "tag", "piece_length", "ttl", "timeout"],
+        help = "Import the task archive exported by the raw export into the storage of dfdaemon, dfdaemon must be stopped"
+    )]
+    raw: bool,
+
+    #[arg(
+        long = "config",
+        default_value_os_t = dfdaemon::default_dfdaemon_config_path(),
+        requires = "raw",
+        help = "Specify dfdaemon's config file to find the storage in raw mode"
+    )]
+    config: PathBuf,
 }
 
 // Implement the execute for ImportCommand.
 impl ImportCommand {
     pub async fn execute(&self) -> Result<(), anyhow::Error> {
+        if self.raw {
+            return self.execute_raw().await;
+        }
+
         println!("ImportCommand is executed!");
         Ok(())
     }
+
+    // execute_raw imports the task archive into the storage of dfdaemon, and prints
+    // the id of the imported task.
+    async fn execute_raw(&self) -> Result<(), anyhow::Error> {
+        let config = dfdaemon::Config::load(&self.config).await?;
+
+        // The imported task is removed when dfdaemon starts if the storage is not kept, and
+        // the storage is also removed when it is opened here.
+        if !config.storage.keep {
+            return Err(anyhow::anyhow!(
+                "storage.keep of {} must be enabled to import the task archive",
+                self.config.display()
+            ));
+        }
+
+        let dir = config.storage.dir.clone();
+        let storage = Storage::new(Arc::new(config), dir.as_path()).await?;
+
+        let task = storage.import_task(self.path.as_path()).await?;
+        println!("{}", task.id);
+        Ok(())
+    }
 }
@@ -15,7 +15,7 @@
  */
 
 use clap::{Parser, Subcommand};
-use dragonfly_client::tracing::init_tracing;
+use dragonfly_client::tracing::{init_tracing, LogFormat, LogRotation, TracingOptions};
 use dragonfly_client_config::{dfcache, dfdaemon};
 use std::path::PathBuf;
 use tracing::Level;
@@ -65,6 +65,28 @@ struct Args {
     )]
     log_max_files: usize,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LogFormat::Compact,
+        help = "Specify the format of the log [compact, json], the json format is also used for the console log if verbose"
+    )]
+    log_format: LogFormat,
+
+    #[arg(
+        long,
+        default_value = "hourly",
+        help = "Specify the rotation of the log file [hourly, daily, max size with unit, e.g. 100MB]"
+    )]
+    log_rotation: LogRotation,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to compress the rotated log files with gzip"
+    )]
+    log_compression: bool,
+
     #[arg(
         long = "verbose",
         default_value_t = false,
@@ -129,18 +151,24 @@ impl Command {
     }
 }
 
-fn main() {
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
     // Parse command line arguments.
     let args = Args::parse();
 
     // Initialize tracing.
-    let _guards = init_tracing(
-        dfcache::NAME,
-        &args.log_dir,
-        args.log_level,
-        args.log_max_files,
-        None,
-        false,
-        args.verbose,
-    );
+    let (_guards, _) = init_tracing(TracingOptions {
+        name: dfcache::NAME.to_string(),
+        log_dir: args.log_dir.clone(),
+        log_level: args.log_level,
+        log_max_files: args.log_max_files,
+        log_format: args.log_format,
+        log_rotation: args.log_rotation,
+        log_compression: args.log_compression,
+        verbose: args.verbose,
+        ..Default::default()
+    });
+
+    // Execute the command.
+    args.command.execute().await
 }
@@ -23,12 +23,14 @@ use dragonfly_client::grpc::{
     manager::ManagerClient, scheduler::SchedulerClient,
 };
 use dragonfly_client::health::Health;
-use dragonfly_client::metrics::Metrics;
+use dragonfly_client::metrics::{collect_storage_check_metrics, Metrics};
 use dragonfly_client::proxy::Proxy;
 use dragonfly_client::shutdown;
 use dragonfly_client::stats::Stats;
 use dragonfly_client::task::Task;
-use dragonfly_client::tracing::init_tracing;
+use dragonfly_client::tracing::{
+    init_tracing, toggle_debug_log_level, LogFormat, LogRotation, TracingOptions,
+};
 use dragonfly_client_backend::BackendFactory;
 use dragonfly_client_config::dfdaemon;
 use dragonfly_client_storage::Storage;
@@ -36,6 +38,7 @@ use dragonfly_client_util::id_generator::IDGenerator;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc;
 use tracing::{error, info, Level};
 
@@ -95,6 +98,28 @@ struct Args {
     )]
     log_max_files: usize,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LogFormat::Compact,
+        help = "Specify the format of the log [compact, json], the json format is also used for the console log if verbose"
+    )]
+    log_format: LogFormat,
+
+    #[arg(
+        long,
+        default_value = "hourly",
+        help = "Specify the rotation of the log file [hourly, daily, max size with unit, e.g. 100MB]"
+    )]
+    log_rotation: LogRotation,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to compress the rotated log files with gzip"
+    )]
+    log_compression: bool,
+
     #[arg(
         long = "verbose",
         default_value_t = false,
@@ -113,15 +138,32 @@ async fn main() -> Result<(), anyhow::Error> {
     let config = Arc::new(config);
 
     // Initialize tracing.
-    let _guards = init_tracing(
-        dfdaemon::NAME,
-        &args.log_dir,
-        args.log_level,
-        args.log_max_files,
-        config.tracing.addr.to_owned(),
-        true,
-        args.verbose,
-    );
+    let (_guards, log_level_handle) = init_tracing(TracingOptions {
+        name: dfdaemon::NAME.to_string(),
+        log_dir: args.log_dir.clone(),
+        log_level: args.log_level,
+        log_filters: config.log.filters.clone(),
+        log_max_files: args.log_max_files,
+        log_format: args.log_format,
+        log_rotation: args.log_rotation,
+        log_compression: args.log_compression,
+        tracing: config.tracing.clone(),
+        redirect_stderr: true,
+        verbose: args.verbose,
+    });
+
+    // Toggle the DEBUG log level when SIGUSR1 is received.
+    let mut user_defined_signal = signal(SignalKind::user_defined1())?;
+    let default_log_level = args.log_level;
+    let sigusr1_log_level_handle = log_level_handle.clone();
+    tokio::spawn(async move {
+        while user_defined_signal.recv().await.is_some() {
+            match toggle_debug_log_level(&sigusr1_log_level_handle, default_log_level) {
+                Ok(level) => info!("log level changed to {} by SIGUSR1", level),
+                Err(err) => error!("failed to toggle log level: {}", err),
+            }
+        }
+    });
 
     // Initialize storage.
     let storage = Storage::new(config.clone(), config.storage.dir.as_path())
@@ -130,6 +172,17 @@ async fn main() -> Result<(), anyhow::Error> {
             error!("initialize storage failed: {}", err);
             err
         })?;
+
+    // Check the integrity of the storage, the inconsistent tasks and pieces left
+    // by the crash are deleted.
+    let report = storage
+        .check(config.storage.check_on_startup)
+        .await
+        .map_err(|err| {
+            error!("check storage failed: {}", err);
+            err
+        })?;
+    collect_storage_check_metrics(report.deleted_tasks, report.deleted_pieces);
     let storage = Arc::new(storage);
 
     // Initialize id generator.
@@ -168,7 +221,7 @@ async fn main() -> Result<(), anyhow::Error> {
     let dynconfig = Arc::new(dynconfig);
 
     // Initialize scheduler client.
-    let scheduler_client = SchedulerClient::new(dynconfig.clone())
+    let scheduler_client = SchedulerClient::new(config.clone(), dynconfig.clone())
         .await
         .map_err(|err| {
             error!("initialize scheduler client failed: {}", err);
@@ -206,6 +259,7 @@ async fn main() -> Result<(), anyhow::Error> {
             config.metrics.server.ip.unwrap(),
             config.metrics.server.port,
         ),
+        storage.clone(),
         shutdown.clone(),
         shutdown_complete_tx.clone(),
     );
@@ -213,6 +267,7 @@ async fn main() -> Result<(), anyhow::Error> {
     // Initialize stats server.
     let stats = Stats::new(
         SocketAddr::new(config.stats.server.ip.unwrap(), config.stats.server.port),
+        log_level_handle,
         shutdown.clone(),
         shutdown_complete_tx.clone(),
     );
@@ -237,6 +292,7 @@ async fn main() -> Result<(), anyhow::Error> {
     let scheduler_announcer = SchedulerAnnouncer::new(
         config.clone(),
         id_generator.host_id(),
+        dynconfig.clone(),
         scheduler_client.clone(),
         shutdown.clone(),
         shutdown_complete_tx.clone(),
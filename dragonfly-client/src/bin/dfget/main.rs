@@ -20,7 +20,7 @@ use dragonfly_api::dfdaemon::v2::{download_task_response, DownloadTaskRequest};
 use dragonfly_api::errordetails::v2::Backend;
 use dragonfly_client::grpc::dfdaemon_download::DfdaemonDownloadClient;
 use dragonfly_client::grpc::health::HealthClient;
-use dragonfly_client::tracing::init_tracing;
+use dragonfly_client::tracing::{init_tracing, LogFormat, LogRotation, TracingOptions};
 use dragonfly_client_config::{self, default_piece_length, dfdaemon, dfget};
 use dragonfly_client_core::{
     error::{ErrorType, ExternalError, OrErr},
@@ -138,6 +138,13 @@ struct Args {
     )]
     disable_back_to_source: bool,
 
+    #[arg(
+        long = "pin",
+        default_value_t = false,
+        help = "Pin the downloaded task in dfdaemon to protect it from gc, e.g. the base images preheated on the seed peers"
+    )]
+    pin: bool,
+
     #[arg(
         short = 'l',
         long,
@@ -160,6 +167,28 @@ struct Args {
     )]
     log_max_files: usize,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LogFormat::Compact,
+        help = "Specify the format of the log [compact, json], the json format is also used for the console log if verbose"
+    )]
+    log_format: LogFormat,
+
+    #[arg(
+        long,
+        default_value = "hourly",
+        help = "Specify the rotation of the log file [hourly, daily, max size with unit, e.g. 100MB]"
+    )]
+    log_rotation: LogRotation,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Specify whether to compress the rotated log files with gzip"
+    )]
+    log_compression: bool,
+
     #[arg(
         long = "verbose",
         default_value_t = false,
@@ -210,15 +239,17 @@ async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     // Initialize tracing.
-    let _guards = init_tracing(
-        dfget::NAME,
-        &args.log_dir,
-        args.log_level,
-        args.log_max_files,
-        None,
-        false,
-        args.verbose,
-    );
+    let (_guards, _) = init_tracing(TracingOptions {
+        name: dfget::NAME.to_string(),
+        log_dir: args.log_dir.clone(),
+        log_level: args.log_level,
+        log_max_files: args.log_max_files,
+        log_format: args.log_format,
+        log_rotation: args.log_rotation,
+        log_compression: args.log_compression,
+        verbose: args.verbose,
+        ..Default::default()
+    });
 
     // Run dfget command.
     if let Err(err) = run(args).await {
@@ -368,37 +399,43 @@ async fn run(args: Args) -> Result<()> {
         err
     })?;
 
-    // Create dfdaemon client.
-    let response = dfdaemon_download_client
-        .download_task(DownloadTaskRequest {
-            download: Some(Download {
-                url: args.url.to_string(),
-                digest: Some(args.digest),
-                // NOTE: Dfget does not support range download.
-                range: None,
-                r#type: TaskType::Dfdaemon as i32,
-                tag: Some(args.tag),
-                application: Some(args.application),
-                priority: args.priority,
-                filtered_query_params: args.filtered_query_params.unwrap_or_default(),
-                request_header: header_vec_to_hashmap(args.header.unwrap_or_default())?,
-                piece_length: args.piece_length,
-                output_path: Some(args.output.into_os_string().into_string().unwrap()),
-                timeout: Some(
-                    prost_wkt_types::Duration::try_from(args.timeout)
-                        .or_err(ErrorType::ParseError)?,
-                ),
-                need_back_to_source: false,
-                disable_back_to_source: args.disable_back_to_source,
-                certificate_chain: Vec::new(),
-                prefetch: false,
-            }),
-        })
-        .await
-        .map_err(|err| {
-            error!("download task failed: {}", err);
-            err
-        })?;
+    let request = DownloadTaskRequest {
+        download: Some(Download {
+            url: args.url.to_string(),
+            digest: Some(args.digest),
+            // NOTE: Dfget does not support range download.
+            range: None,
+            r#type: TaskType::Dfdaemon as i32,
+            tag: Some(args.tag),
+            application: Some(args.application),
+            priority: args.priority,
+            filtered_query_params: args.filtered_query_params.unwrap_or_default(),
+            request_header: header_vec_to_hashmap(args.header.unwrap_or_default())?,
+            piece_length: args.piece_length,
+            output_path: Some(args.output.into_os_string().into_string().unwrap()),
+            timeout: Some(
+                prost_wkt_types::Duration::try_from(args.timeout).or_err(ErrorType::ParseError)?,
+            ),
+            need_back_to_source: false,
+            disable_back_to_source: args.disable_back_to_source,
+            certificate_chain: Vec::new(),
+            prefetch: false,
+        }),
+    };
+
+    // Create dfdaemon client, the task is not unpinned if the pin flag is unset, because
+    // the task may be pinned by the other downloads.
+    let response = if args.pin {
+        dfdaemon_download_client
+            .download_task_with_pin(request, true)
+            .await
+    } else {
+        dfdaemon_download_client.download_task(request).await
+    }
+    .map_err(|err| {
+        error!("download task failed: {}", err);
+        err
+    })?;
 
     // Initialize progress bar.
     let pb = ProgressBar::new(0);
@@ -19,11 +19,17 @@ use crate::shutdown;
 use dragonfly_api::scheduler::v2::DeleteTaskRequest;
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::Result;
-use dragonfly_client_storage::{metadata, Storage};
+use dragonfly_client_storage::{metadata::Task, Storage};
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
 use tracing::{error, info};
 
+// DELETE_TASK_FROM_SCHEDULER_CONCURRENCY is the max count of the concurrent requests to delete
+// the evicted tasks from the scheduler.
+const DELETE_TASK_FROM_SCHEDULER_CONCURRENCY: usize = 16;
+
 // GC is the garbage collector of dfdaemon.
 pub struct GC {
     // config is the configuration of the dfdaemon.
@@ -97,11 +103,12 @@ impl GC {
     // evict_by_task_ttl evicts the cache by task ttl.
     async fn evict_by_task_ttl(&self) -> Result<()> {
         info!("start to evict by task ttl");
+        let mut evicted_task_ids = Vec::new();
         for task in self.storage.get_tasks()? {
             // If the task is expired and not uploading, evict the task.
             if task.is_expired(self.config.gc.policy.task_ttl) {
-                // If the task is uploading, skip it.
-                if task.is_uploading() {
+                // If the task is uploading or pinned, skip it.
+                if task.is_uploading() || task.is_pinned() {
                     continue;
                 }
 
@@ -112,100 +119,339 @@ impl GC {
                         info!("failed to evict task {}: {}", task.id, err);
                     });
                 info!("evict task {}", task.id);
-
-                self.delete_task_from_scheduler(task.clone()).await;
-                info!("delete task {} from scheduler", task.id);
+                evicted_task_ids.push(task.id);
             }
         }
 
+        self.delete_tasks_from_scheduler(evicted_task_ids).await;
         Ok(())
     }
 
-    // evict_by_disk_usage evicts the cache by disk usage.
+    // evict_by_disk_usage evicts the cache by disk usage, if the disk usage of the storage
+    // directory exceeds the high threshold, the tasks are evicted until the disk usage drops
+    // below the low threshold.
     async fn evict_by_disk_usage(&self) -> Result<()> {
         let stats = fs2::statvfs(self.config.storage.dir.as_path())?;
-        let available_space = stats.available_space();
         let total_space = stats.total_space();
+        let available_space = stats.available_space();
+        let Some(need_evict_space) = need_evict_space(
+            total_space,
+            available_space,
+            self.config.gc.policy.dist_high_threshold_percent,
+            self.config.gc.policy.dist_low_threshold_percent,
+        ) else {
+            return Ok(());
+        };
+
+        info!(
+            "start to evict by disk usage, disk usage {:.2}% is higher than high threshold {}%, need to evict {} bytes",
+            disk_usage_percent(total_space, available_space),
+            self.config.gc.policy.dist_high_threshold_percent,
+            need_evict_space
+        );
+
+        // Evict the cache by the need evict space.
+        let evicted_task_ids = evict_tasks(&self.storage, need_evict_space).await?;
+        self.delete_tasks_from_scheduler(evicted_task_ids).await;
+        Ok(())
+    }
+
+    // delete_tasks_from_scheduler deletes the evicted tasks from the scheduler concurrently,
+    // the failure of a task is logged and does not stop deleting the other tasks.
+    async fn delete_tasks_from_scheduler(&self, task_ids: Vec<String>) {
+        let host_id = self.host_id.clone();
+        let scheduler_client = self.scheduler_client.clone();
+        delete_tasks(
+            task_ids,
+            DELETE_TASK_FROM_SCHEDULER_CONCURRENCY,
+            move |task_id| {
+                let host_id = host_id.clone();
+                let scheduler_client = scheduler_client.clone();
+                async move {
+                    scheduler_client
+                        .delete_task(
+                            task_id.as_str(),
+                            DeleteTaskRequest {
+                                host_id,
+                                task_id: task_id.clone(),
+                            },
+                        )
+                        .await
+                }
+            },
+        )
+        .await;
+    }
+}
+
+// disk_usage_percent returns the used percent of the disk, it is calculated in the same way
+// as the disk usage announced to the scheduler.
+fn disk_usage_percent(total_space: u64, available_space: u64) -> f64 {
+    if total_space == 0 {
+        return 0.0;
+    }
 
-        // Calculate the usage percent.
-        let usage_percent = (100 - available_space * 100 / total_space) as u8;
-        if usage_percent >= self.config.gc.policy.dist_high_threshold_percent {
-            info!(
-                "start to evict by disk usage, disk usage {}% is higher than high threshold {}%",
-                usage_percent, self.config.gc.policy.dist_high_threshold_percent
-            );
-
-            // Calculate the need evict space.
-            let need_evict_space = total_space as f64
-                * ((usage_percent - self.config.gc.policy.dist_low_threshold_percent) as f64
-                    / 100.0);
-
-            // Evict the cache by the need evict space.
-            if let Err(err) = self.evict_space(need_evict_space as u64).await {
-                info!("failed to evict by disk usage: {}", err);
+    total_space.saturating_sub(available_space) as f64 / total_space as f64 * 100.0
+}
+
+// need_evict_space returns the space to evict to make the disk usage drop below the low threshold,
+// it returns none if the disk usage does not exceed the high threshold.
+fn need_evict_space(
+    total_space: u64,
+    available_space: u64,
+    high_threshold_percent: u8,
+    low_threshold_percent: u8,
+) -> Option<u64> {
+    if total_space == 0
+        || disk_usage_percent(total_space, available_space) < high_threshold_percent as f64
+    {
+        return None;
+    }
+
+    let low_threshold_space = (total_space as u128 * low_threshold_percent as u128 / 100) as u64;
+    Some(
+        total_space
+            .saturating_sub(available_space)
+            .saturating_sub(low_threshold_space),
+    )
+}
+
+// evictable_tasks returns the tasks which can be evicted in least-recently-accessed order, the
+// updated_at of the task is refreshed when it is downloaded by the other peers. The tasks which
+// are being downloaded or uploaded, and the pinned tasks are skipped.
+fn evictable_tasks(tasks: Vec<Task>) -> Vec<Task> {
+    let mut tasks = tasks
+        .into_iter()
+        .filter(|task| {
+            let downloading = !task.is_finished() && !task.is_failed();
+            !downloading && !task.is_uploading() && !task.is_pinned()
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+    tasks
+}
+
+// evict_tasks evicts the tasks in least-recently-accessed order until the evicted space reaches
+// the need evict space, the metadata and the content of the task are deleted together. It
+// returns the ids of the evicted tasks in the order of eviction.
+async fn evict_tasks(storage: &Storage, need_evict_space: u64) -> Result<Vec<String>> {
+    let mut evicted_space = 0;
+    let mut evicted_task_ids = Vec::new();
+    for task in evictable_tasks(storage.get_tasks()?) {
+        // Evict enough space.
+        if evicted_space >= need_evict_space {
+            break;
+        }
+
+        // If the task has no content length, skip it.
+        let task_space = match task.content_length() {
+            Some(content_length) => content_length,
+            None => {
+                error!("task {} has no content length", task.id);
+                continue;
             }
+        };
+
+        // Evict the task.
+        if let Err(err) = storage.delete_task(&task.id).await {
+            info!("failed to evict task {}: {}", task.id, err);
+            continue;
         }
 
-        Ok(())
+        // Update the evicted space.
+        evicted_space += task_space;
+        info!("evict task {} size {}", task.id, task_space);
+        evicted_task_ids.push(task.id);
     }
 
-    // evict_space evicts the cache by the given space.
-    async fn evict_space(&self, need_evict_space: u64) -> Result<()> {
-        let mut tasks = self.storage.get_tasks()?;
-        tasks.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+    info!(
+        "evict {} tasks, reclaimed {} bytes",
+        evicted_task_ids.len(),
+        evicted_space
+    );
+    Ok(evicted_task_ids)
+}
 
-        let mut evicted_space = 0;
-        for task in tasks {
-            // Evict enough space.
-            if evicted_space >= need_evict_space {
+// delete_tasks deletes the tasks by the delete function concurrently, at most concurrency
+// tasks are deleted at the same time. The failed tasks are logged.
+async fn delete_tasks<F, Fut>(task_ids: Vec<String>, concurrency: usize, delete: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let mut join_set = JoinSet::new();
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    for task_id in task_ids {
+        let permit = match semaphore.clone().acquire_owned().await {
+            Ok(permit) => permit,
+            Err(err) => {
+                error!("failed to acquire permit: {}", err);
                 break;
             }
+        };
 
-            // If the task is uploading, skip it.
-            if task.is_uploading() {
-                continue;
+        let delete_task = delete(task_id.clone());
+        join_set.spawn(async move {
+            let _permit = permit;
+            match delete_task.await {
+                Ok(_) => info!("delete task {} from scheduler", task_id),
+                Err(err) => error!("failed to delete task {} from scheduler: {}", task_id, err),
             }
+        });
+    }
 
-            // If the task has no content length, skip it.
-            let task_space = match task.content_length() {
-                Some(content_length) => content_length,
-                None => {
-                    error!("task {} has no content length", task.id);
-                    continue;
-                }
-            };
+    while let Some(result) = join_set.join_next().await {
+        if let Err(err) = result {
+            error!("failed to join the task deletion: {}", err);
+        }
+    }
+}
 
-            // Evict the task.
-            if let Err(err) = self.storage.delete_task(&task.id).await {
-                info!("failed to evict task {}: {}", task.id, err);
-                continue;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_client_core::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[test]
+    fn should_calculate_need_evict_space() {
+        // The disk usage does not exceed the high threshold.
+        assert_eq!(need_evict_space(1000, 500, 80, 60), None);
+        assert_eq!(need_evict_space(1000, 201, 80, 60), None);
+        assert_eq!(need_evict_space(0, 0, 80, 60), None);
+
+        // The space is evicted until the disk usage drops below the low threshold.
+        assert_eq!(need_evict_space(1000, 200, 80, 60), Some(200));
+        assert_eq!(need_evict_space(1000, 0, 80, 60), Some(400));
+        assert_eq!(
+            need_evict_space(u64::MAX, 0, 80, 60),
+            Some(u64::MAX - (u64::MAX as u128 * 60 / 100) as u64)
+        );
+        assert_eq!(disk_usage_percent(1000, 250), 75.0);
+    }
+
+    #[test]
+    fn should_sort_evictable_tasks_by_access_time() {
+        let now = chrono::Utc::now().naive_utc();
+        let task = |id: &str, updated_at: chrono::NaiveDateTime| Task {
+            id: id.to_string(),
+            content_length: Some(1),
+            updated_at,
+            finished_at: Some(now),
+            ..Default::default()
+        };
+
+        let tasks = vec![
+            task("recent", now),
+            task("oldest", now - Duration::from_secs(30)),
+            Task {
+                uploading_count: 1,
+                ..task("uploading", now - Duration::from_secs(40))
+            },
+            Task {
+                finished_at: None,
+                ..task("downloading", now - Duration::from_secs(50))
+            },
+            Task {
+                finished_at: None,
+                failed_at: Some(now),
+                ..task("failed", now - Duration::from_secs(10))
+            },
+            Task {
+                pinned: true,
+                ..task("pinned", now - Duration::from_secs(60))
+            },
+            task("older", now - Duration::from_secs(20)),
+        ];
+        assert_eq!(
+            evictable_tasks(tasks)
+                .iter()
+                .map(|task| task.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["oldest", "older", "failed", "recent"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_evict_tasks_by_space() {
+        let dir = tempdir::TempDir::new("gc").unwrap();
+        let storage = Storage::new(Arc::new(Config::default()), dir.path())
+            .await
+            .unwrap();
+
+        // The tasks are accessed in order, and the last task is still downloading.
+        for (id, finished) in [("a", true), ("b", true), ("c", true), ("d", false)] {
+            storage
+                .download_task_started(id, 1, Some(100), None)
+                .unwrap();
+            if finished {
+                storage.download_task_finished(id).unwrap();
             }
 
-            // Update the evicted space.
-            evicted_space += task_space;
-            info!("evict task {} size {}", task.id, task_space);
+            tokio::fs::write(dir.path().join("content").join(id), vec![0; 100])
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
 
-            self.delete_task_from_scheduler(task.clone()).await;
-            info!("delete task {} from scheduler", task.id);
+        // The least recently accessed tasks are evicted until the space is enough.
+        let evicted_task_ids = evict_tasks(&storage, 150).await.unwrap();
+        assert_eq!(evicted_task_ids, vec!["a", "b"]);
+        for id in ["a", "b"] {
+            assert!(storage.get_task(id).unwrap().is_none());
+            assert!(!dir.path().join("content").join(id).exists());
         }
 
-        info!("evict total size {}", evicted_space);
-        Ok(())
+        // The downloading task and the pinned task are not evicted even if more space is needed.
+        storage.pin_task("c").unwrap();
+        let evicted_task_ids = evict_tasks(&storage, 1000).await.unwrap();
+        assert!(evicted_task_ids.is_empty());
+        for id in ["c", "d"] {
+            assert!(storage.get_task(id).unwrap().is_some());
+            assert!(dir.path().join("content").join(id).exists());
+        }
+
+        // The unpinned task is evicted.
+        storage.unpin_task("c").unwrap();
+        let evicted_task_ids = evict_tasks(&storage, 1000).await.unwrap();
+        assert_eq!(evicted_task_ids, vec!["c"]);
     }
 
-    // delete_task_from_scheduler deletes the task from the scheduler.
-    async fn delete_task_from_scheduler(&self, task: metadata::Task) {
-        self.scheduler_client
-            .delete_task(
-                task.id.as_str(),
-                DeleteTaskRequest {
-                    host_id: self.host_id.clone(),
-                    task_id: task.id.clone(),
-                },
-            )
-            .await
-            .unwrap_or_else(|err| {
-                error!("failed to delete peer {}: {}", task.id, err);
-            });
+    #[tokio::test]
+    async fn should_delete_tasks_concurrently() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let task_ids: Vec<String> = (0..32).map(|i| format!("task-{}", i)).collect();
+
+        delete_tasks(task_ids.clone(), 4, |task_id| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let deleted = deleted.clone();
+            async move {
+                let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(count, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+
+                // The failure of a task does not stop deleting the other tasks.
+                if task_id == "task-0" {
+                    return Err(Error::Unknown("scheduler is unavailable".to_string()));
+                }
+
+                deleted.lock().unwrap().push(task_id);
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 4);
+        let mut deleted = deleted.lock().unwrap().clone();
+        deleted.sort();
+        let mut expected = task_ids[1..].to_vec();
+        expected.sort();
+        assert_eq!(deleted, expected);
     }
 }
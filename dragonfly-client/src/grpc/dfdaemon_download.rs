@@ -169,6 +169,9 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
         // Record the start time.
         let start_time = Instant::now();
 
+        // Get whether to pin the task from the metadata.
+        let pinned = super::metadata_bool(request.metadata(), super::PIN_METADATA_KEY)?;
+
         // Clone the request.
         let request = request.into_inner();
 
@@ -207,7 +210,10 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
         Span::current().record("peer_id", peer_id.as_str());
 
         // Download task started.
-        info!("download task started: {:?}", download);
+        info!(
+            "download task started: {:?}",
+            super::redact_download(&download, &self.task.config.tracing.redacted_headers)
+        );
         let task = match self
             .task
             .download_started(task_id.as_str(), download.clone())
@@ -255,6 +261,18 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
             }
         };
 
+        // Pin or unpin the task if it is required by the metadata, then the task is
+        // protected from gc after it is downloaded.
+        if let Some(pinned) = pinned {
+            self.task
+                .set_pinned(task_id.as_str(), pinned)
+                .map_err(|err| {
+                    error!("set task pinned failed: {}", err);
+                    Status::internal(err.to_string())
+                })?;
+            info!("task pinned: {}", pinned);
+        }
+
         // Clone the task.
         let task_manager = self.task.clone();
 
@@ -470,12 +488,17 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
         Ok(Response::new(ReceiverStream::new(out_stream_rx)))
     }
 
-    // stat_task gets the status of the task.
+    // stat_task gets the status of the task. The task is pinned or unpinned before it is
+    // stated if the pin metadata is set, and the pin state of the task in the local storage
+    // is returned by the pinned metadata of the response.
     #[instrument(skip_all, fields(host_id, task_id))]
     async fn stat_task(
         &self,
         request: Request<DfdaemonStatTaskRequest>,
     ) -> Result<Response<Task>, Status> {
+        // Get whether to pin the task from the metadata.
+        let pinned = super::metadata_bool(request.metadata(), super::PIN_METADATA_KEY)?;
+
         // Clone the request.
         let request = request.into_inner();
 
@@ -489,6 +512,21 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
         Span::current().record("host_id", host_id.as_str());
         Span::current().record("task_id", task_id.as_str());
 
+        // Pin or unpin the task in the local storage.
+        if let Some(pinned) = pinned {
+            match self.task.set_pinned(task_id.as_str(), pinned) {
+                Ok(_) => info!("task pinned: {}", pinned),
+                Err(ClientError::TaskNotFound(_)) => {
+                    error!("task not found");
+                    return Err(Status::not_found(format!("task {} not found", task_id)));
+                }
+                Err(err) => {
+                    error!("set task pinned failed: {}", err);
+                    return Err(Status::internal(err.to_string()));
+                }
+            }
+        }
+
         // Get the task from the scheduler.
         let task = self
             .task
@@ -505,17 +543,67 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
                 Status::internal(e.to_string())
             })?;
 
-        Ok(Response::new(task))
+        // Get the pin state of the task from the local storage.
+        let pinned = self
+            .task
+            .get_task(task_id.as_str())
+            .map_err(|e| {
+                error!("get task: {}", e);
+                Status::internal(e.to_string())
+            })?
+            .map(|task| task.is_pinned())
+            .unwrap_or_default();
+
+        let mut response = Response::new(task);
+        response.metadata_mut().insert(
+            super::PINNED_METADATA_KEY,
+            super::metadata_bool_value(pinned),
+        );
+
+        Ok(response)
     }
 
-    // delete_task calls the dfdaemon to delete the task.
-    #[instrument(skip_all)]
+    // delete_task deletes the task from the local storage, the pinned task is deleted only
+    // if the force metadata is true.
+    #[instrument(skip_all, fields(task_id))]
     async fn delete_task(
         &self,
         request: Request<DeleteTaskRequest>,
     ) -> Result<Response<()>, Status> {
-        println!("delete_task: {:?}", request);
-        Err(Status::unimplemented("not implemented"))
+        // Get whether to delete the pinned task from the metadata.
+        let force = super::metadata_bool(request.metadata(), super::FORCE_METADATA_KEY)?
+            .unwrap_or_default();
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Span record the task id.
+        Span::current().record("task_id", request.task_id.as_str());
+
+        match self.task.delete_task(request.task_id.as_str(), force).await {
+            Ok(()) => {
+                info!("task deleted");
+                Ok(Response::new(()))
+            }
+            Err(ClientError::TaskNotFound(_)) => {
+                error!("task not found");
+                Err(Status::not_found(format!(
+                    "task {} not found",
+                    request.task_id
+                )))
+            }
+            Err(ClientError::TaskPinned(_)) => {
+                error!("task is pinned");
+                Err(Status::failed_precondition(format!(
+                    "task {} is pinned, delete it with force",
+                    request.task_id
+                )))
+            }
+            Err(err) => {
+                error!("delete task: {}", err);
+                Err(Status::internal(err.to_string()))
+            }
+        }
     }
 
     // delete_host calls the scheduler to delete the host.
@@ -611,21 +699,23 @@ impl DfdaemonDownloadClient {
         &self,
         request: DownloadTaskRequest,
     ) -> ClientResult<tonic::Response<tonic::codec::Streaming<DownloadTaskResponse>>> {
-        // Get the download from the request.
-        let download = request.clone().download.ok_or_else(|| {
-            tonic::Status::invalid_argument("missing download in download task request")
-        })?;
-
-        // Initialize the request.
-        let mut request = tonic::Request::new(request);
+        let request = Self::make_download_task_request(request)?;
+        let response = self.client.clone().download_task(request).await?;
+        Ok(response)
+    }
 
-        // Set the timeout to the request.
-        if let Some(timeout) = download.timeout {
-            request.set_timeout(
-                Duration::try_from(timeout)
-                    .map_err(|_| tonic::Status::invalid_argument("invalid timeout"))?,
-            );
-        }
+    // download_task_with_pin tells the dfdaemon to download the task, the task is pinned
+    // or unpinned after it is started, the pinned task is protected from gc.
+    #[instrument(skip_all)]
+    pub async fn download_task_with_pin(
+        &self,
+        request: DownloadTaskRequest,
+        pinned: bool,
+    ) -> ClientResult<tonic::Response<tonic::codec::Streaming<DownloadTaskResponse>>> {
+        let mut request = Self::make_download_task_request(request)?;
+        request
+            .metadata_mut()
+            .insert(super::PIN_METADATA_KEY, super::metadata_bool_value(pinned));
 
         let response = self.client.clone().download_task(request).await?;
         Ok(response)
@@ -639,10 +729,32 @@ impl DfdaemonDownloadClient {
         Ok(response.into_inner())
     }
 
-    // delete_task tells the dfdaemon to delete the task.
+    // set_task_pinned pins or unpins the downloaded task, it returns the status of the task
+    // and whether the task is pinned in the local storage of the dfdaemon.
     #[instrument(skip_all)]
-    pub async fn delete_task(&self, request: DeleteTaskRequest) -> ClientResult<()> {
-        let request = Self::make_request(request);
+    pub async fn set_task_pinned(&self, task_id: &str, pinned: bool) -> ClientResult<(Task, bool)> {
+        let mut request = Self::make_request(DfdaemonStatTaskRequest {
+            task_id: task_id.to_string(),
+        });
+        request
+            .metadata_mut()
+            .insert(super::PIN_METADATA_KEY, super::metadata_bool_value(pinned));
+
+        let response = self.client.clone().stat_task(request).await?;
+        let pinned = super::metadata_bool(response.metadata(), super::PINNED_METADATA_KEY)?
+            .unwrap_or_default();
+        Ok((response.into_inner(), pinned))
+    }
+
+    // delete_task tells the dfdaemon to delete the task, the pinned task is deleted only
+    // if force is true.
+    #[instrument(skip_all)]
+    pub async fn delete_task(&self, request: DeleteTaskRequest, force: bool) -> ClientResult<()> {
+        let mut request = Self::make_request(request);
+        request
+            .metadata_mut()
+            .insert(super::FORCE_METADATA_KEY, super::metadata_bool_value(force));
+
         self.client.clone().delete_task(request).await?;
         Ok(())
     }
@@ -719,4 +831,28 @@ impl DfdaemonDownloadClient {
         request.set_timeout(super::REQUEST_TIMEOUT);
         request
     }
+
+    // make_download_task_request makes the download task request with the timeout of
+    // the download.
+    fn make_download_task_request(
+        request: DownloadTaskRequest,
+    ) -> ClientResult<tonic::Request<DownloadTaskRequest>> {
+        // Get the download from the request.
+        let download = request.clone().download.ok_or_else(|| {
+            tonic::Status::invalid_argument("missing download in download task request")
+        })?;
+
+        // Initialize the request.
+        let mut request = tonic::Request::new(request);
+
+        // Set the timeout to the request.
+        if let Some(timeout) = download.timeout {
+            request.set_timeout(
+                Duration::try_from(timeout)
+                    .map_err(|_| tonic::Status::invalid_argument("invalid timeout"))?,
+            );
+        }
+
+        Ok(request)
+    }
 }
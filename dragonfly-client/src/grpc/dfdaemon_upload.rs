@@ -192,7 +192,10 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         Span::current().record("peer_id", peer_id.as_str());
 
         // Download task started.
-        info!("download task started: {:?}", download);
+        info!(
+            "download task started: {:?}",
+            super::redact_download(&download, &self.task.config.tracing.redacted_headers)
+        );
         let task = match self
             .task
             .download_started(task_id.as_str(), download.clone())
@@ -25,6 +25,7 @@ use dragonfly_api::scheduler::v2::{
     DeleteTaskRequest, StatCachePeerRequest, StatCacheTaskRequest, StatPeerRequest,
     StatTaskRequest, UploadCacheTaskRequest,
 };
+use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::error::{ErrorType, ExternalError, OrErr};
 use dragonfly_client_core::{Error, Result};
 use hashring::HashRing;
@@ -54,6 +55,9 @@ impl std::fmt::Display for VNode {
 // SchedulerClient is a wrapper of SchedulerGRPCClient.
 #[derive(Clone)]
 pub struct SchedulerClient {
+    // config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
     // dynconfig is the dynamic configuration of the dfdaemon.
     dynconfig: Arc<Dynconfig>,
 
@@ -70,8 +74,9 @@ pub struct SchedulerClient {
 // SchedulerClient implements the grpc client of the scheduler.
 impl SchedulerClient {
     // new creates a new SchedulerClient.
-    pub async fn new(dynconfig: Arc<Dynconfig>) -> Result<Self> {
+    pub async fn new(config: Arc<Config>, dynconfig: Arc<Dynconfig>) -> Result<Self> {
         let client = Self {
+            config,
             dynconfig,
             available_schedulers: Arc::new(RwLock::new(Vec::new())),
             available_scheduler_addrs: Arc::new(RwLock::new(Vec::new())),
@@ -406,9 +411,10 @@ impl SchedulerClient {
         self.update_available_scheduler_addrs().await?;
 
         // Get the scheduler address from the hashring.
+        let key = hashring_key(task_id, self.config.scheduler.hashring_key_length)?;
         let addrs = self.hashring.read().await;
         let addr = *addrs
-            .get(&task_id[0..5].to_string())
+            .get(&key)
             .ok_or_else(|| Error::HashRing(task_id.to_string()))?;
         drop(addrs);
         info!("picked {:?}", addr);
@@ -540,3 +546,74 @@ impl SchedulerClient {
         request
     }
 }
+
+// SchedulerAnnounce is the interface of the scheduler used by the announcer, it is
+// implemented by SchedulerClient and mocked in the tests of the announcer.
+#[tonic::async_trait]
+pub trait SchedulerAnnounce: Send + Sync {
+    // init_announce_host announces the host to all the available schedulers.
+    async fn init_announce_host(&self, request: AnnounceHostRequest) -> Result<()>;
+
+    // announce_host announces the host to the scheduler.
+    async fn announce_host(&self, request: AnnounceHostRequest) -> Result<()>;
+
+    // delete_host tells the scheduler that the host is deleting.
+    async fn delete_host(&self, request: DeleteHostRequest) -> Result<()>;
+}
+
+// SchedulerClient implements the SchedulerAnnounce trait.
+#[tonic::async_trait]
+impl SchedulerAnnounce for SchedulerClient {
+    // init_announce_host announces the host to all the available schedulers.
+    async fn init_announce_host(&self, request: AnnounceHostRequest) -> Result<()> {
+        SchedulerClient::init_announce_host(self, request).await
+    }
+
+    // announce_host announces the host to the scheduler.
+    async fn announce_host(&self, request: AnnounceHostRequest) -> Result<()> {
+        SchedulerClient::announce_host(self, request).await
+    }
+
+    // delete_host tells the scheduler that the host is deleting.
+    async fn delete_host(&self, request: DeleteHostRequest) -> Result<()> {
+        SchedulerClient::delete_host(self, request).await
+    }
+}
+
+// hashring_key returns the prefix of the task id with the length as the key of the hashring,
+// it returns an error instead of panicking if the task id is shorter than the length.
+fn hashring_key(task_id: &str, length: usize) -> Result<String> {
+    match task_id.get(..length) {
+        Some(key) => Ok(key.to_string()),
+        None => {
+            error!(
+                "task id {} is shorter than the hashring key length {}",
+                task_id, length
+            );
+            Err(Error::HashRing(task_id.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_get_hashring_key() {
+        assert_eq!(hashring_key("abcdefgh", 5).unwrap(), "abcde");
+        assert_eq!(hashring_key("abcde", 5).unwrap(), "abcde");
+        assert_eq!(hashring_key("abcdefgh", 8).unwrap(), "abcdefgh");
+        assert_eq!(hashring_key("abcdefgh", 2).unwrap(), "ab");
+    }
+
+    #[test]
+    fn should_not_get_hashring_key_for_short_task_id() {
+        assert!(matches!(hashring_key("abc", 5), Err(Error::HashRing(_))));
+        assert!(matches!(hashring_key("", 5), Err(Error::HashRing(_))));
+        assert!(matches!(hashring_key("abcde", 8), Err(Error::HashRing(_))));
+
+        // The key is not split in the middle of a multi-byte character.
+        assert!(matches!(hashring_key("abcdé", 5), Err(Error::HashRing(_))));
+    }
+}
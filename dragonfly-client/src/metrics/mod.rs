@@ -17,12 +17,18 @@
 use crate::shutdown;
 use chrono::DateTime;
 use dragonfly_api::common::v2::{Range, TrafficType};
+use dragonfly_client_core::Error;
+use dragonfly_client_storage::{storage_engine::rocksdb::ColumnFamilyStatistics, Storage};
 use lazy_static::lazy_static;
+use opentelemetry::metrics::Meter;
+use opentelemetry::{Context, KeyValue};
+use prometheus::proto::{Metric, MetricFamily, MetricType};
 use prometheus::{
     exponential_buckets, gather, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec,
     Opts, Registry, TextEncoder,
 };
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{error, info};
@@ -115,6 +121,55 @@ lazy_static! {
             Opts::new("proxy_request_failure_total", "Counter of the number of failed of the proxy request.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
             &[]
         ).expect("metric can be created");
+
+    // ANNOUNCE_HOST_COUNT is used to count the number of announcing host to the scheduler.
+    pub static ref ANNOUNCE_HOST_COUNT: IntCounterVec =
+        IntCounterVec::new(
+            Opts::new("announce_host_total", "Counter of the number of the announcing host.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &[]
+        ).expect("metric can be created");
+
+    // ANNOUNCE_HOST_FAILURE_COUNT is used to count the failed number of announcing host to the scheduler.
+    pub static ref ANNOUNCE_HOST_FAILURE_COUNT: IntCounterVec =
+        IntCounterVec::new(
+            Opts::new("announce_host_failure_total", "Counter of the number of failed of the announcing host.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["type"]
+        ).expect("metric can be created");
+
+    // ANNOUNCE_HOST_DURATION is used to record the duration of announcing host to the scheduler.
+    pub static ref ANNOUNCE_HOST_DURATION: HistogramVec =
+        HistogramVec::new(
+            HistogramOpts::new("announce_host_duration_milliseconds", "Histogram of the announcing host duration.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME).buckets(exponential_buckets(1.0, 2.0, 16).unwrap()),
+            &[]
+        ).expect("metric can be created");
+
+    // STORAGE_CHECK_DELETED_COUNT is used to count the inconsistent tasks and pieces deleted by the storage check.
+    pub static ref STORAGE_CHECK_DELETED_COUNT: IntCounterVec =
+        IntCounterVec::new(
+            Opts::new("storage_check_deleted_total", "Counter of the number of the inconsistent tasks and pieces deleted by the storage check.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["type"]
+        ).expect("metric can be created");
+
+    // STORAGE_METADATA_ESTIMATED_KEYS_GAUGE is used to gauge the estimated number of the keys of the metadata store.
+    pub static ref STORAGE_METADATA_ESTIMATED_KEYS_GAUGE: IntGaugeVec =
+        IntGaugeVec::new(
+            Opts::new("storage_metadata_estimated_keys", "Gauge of the estimated number of the keys of the metadata store.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["column_family"]
+        ).expect("metric can be created");
+
+    // STORAGE_METADATA_PENDING_COMPACTION_BYTES_GAUGE is used to gauge the estimated bytes to be compacted of the metadata store.
+    pub static ref STORAGE_METADATA_PENDING_COMPACTION_BYTES_GAUGE: IntGaugeVec =
+        IntGaugeVec::new(
+            Opts::new("storage_metadata_pending_compaction_bytes", "Gauge of the estimated bytes to be compacted of the metadata store.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["column_family"]
+        ).expect("metric can be created");
+
+    // STORAGE_METADATA_WRITE_STALL_GAUGE is used to gauge the number of the stalled writes of the metadata store since dfdaemon started.
+    pub static ref STORAGE_METADATA_WRITE_STALL_GAUGE: IntGaugeVec =
+        IntGaugeVec::new(
+            Opts::new("storage_metadata_write_stalls", "Gauge of the number of the stalled writes of the metadata store since dfdaemon started.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["column_family", "type"]
+        ).expect("metric can be created");
 }
 
 // TaskSize represents the size of the task.
@@ -340,12 +395,164 @@ pub fn collect_proxy_request_failure_metrics() {
     PROXY_REQUSET_FAILURE_COUNT.with_label_values(&[]).inc();
 }
 
+// collect_announce_host_started_metrics collects the announce host started metrics.
+pub fn collect_announce_host_started_metrics() {
+    ANNOUNCE_HOST_COUNT.with_label_values(&[]).inc();
+}
+
+// collect_announce_host_finished_metrics collects the announce host finished metrics.
+pub fn collect_announce_host_finished_metrics(cost: Duration) {
+    ANNOUNCE_HOST_DURATION
+        .with_label_values(&[])
+        .observe(cost.as_millis() as f64);
+}
+
+// collect_announce_host_failure_metrics collects the announce host failure metrics, the
+// failures are labeled by the type of the error.
+pub fn collect_announce_host_failure_metrics(err: &Error) {
+    let typ = match err {
+        Error::TonicStatus(status) => format!("{:?}", status.code()),
+        Error::ExternalError(err) => format!("{:?}", err.etype),
+        Error::AvailableSchedulersNotFound => "AvailableSchedulersNotFound".to_string(),
+        Error::HashRing(_) => "HashRing".to_string(),
+        Error::InvalidURI(_) => "InvalidURI".to_string(),
+        _ => "Unknown".to_string(),
+    };
+
+    ANNOUNCE_HOST_FAILURE_COUNT
+        .with_label_values(&[typ.as_str()])
+        .inc();
+}
+
+// collect_storage_check_metrics collects the storage check metrics, the deleted tasks
+// and pieces are labeled by the type.
+pub fn collect_storage_check_metrics(deleted_tasks: usize, deleted_pieces: usize) {
+    STORAGE_CHECK_DELETED_COUNT
+        .with_label_values(&["task"])
+        .inc_by(deleted_tasks as u64);
+    STORAGE_CHECK_DELETED_COUNT
+        .with_label_values(&["piece"])
+        .inc_by(deleted_pieces as u64);
+}
+
+// collect_storage_metadata_metrics collects the statistics of the column families of the
+// metadata store, the stalled writes are labeled by the type of delay and stop.
+pub fn collect_storage_metadata_metrics(statistics: &[ColumnFamilyStatistics]) {
+    for statistics in statistics {
+        STORAGE_METADATA_ESTIMATED_KEYS_GAUGE
+            .with_label_values(&[statistics.name.as_str()])
+            .set(statistics.estimated_keys as i64);
+        STORAGE_METADATA_PENDING_COMPACTION_BYTES_GAUGE
+            .with_label_values(&[statistics.name.as_str()])
+            .set(statistics.pending_compaction_bytes as i64);
+        STORAGE_METADATA_WRITE_STALL_GAUGE
+            .with_label_values(&[statistics.name.as_str(), "delay"])
+            .set(statistics.write_stall_delays as i64);
+        STORAGE_METADATA_WRITE_STALL_GAUGE
+            .with_label_values(&[statistics.name.as_str(), "stop"])
+            .set(statistics.write_stall_stops as i64);
+    }
+}
+
+// register_otlp_metrics bridges the custom metrics and the prometheus metrics, e.g. the process
+// metrics of the host, to the meter of the otlp exporter. The metric families are gathered from
+// the registries when the meter collects the metrics.
+pub fn register_otlp_metrics(meter: &Meter) -> opentelemetry::metrics::Result<()> {
+    let callback_meter = meter.clone();
+    meter.register_callback(move |cx| {
+        for metric_family in REGISTRY.gather().iter().chain(gather().iter()) {
+            observe_metric_family(&callback_meter, cx, metric_family);
+        }
+    })
+}
+
+// observe_metric_family observes the metrics of the family by the instruments of the meter, the
+// counters and the gauges are observed as is, and the histograms are observed by the sum and the
+// count. The instruments are created once by the meter and reused by the later collections.
+fn observe_metric_family(meter: &Meter, cx: &Context, metric_family: &MetricFamily) {
+    let name = metric_family.get_name();
+    let help = metric_family.get_help();
+    match metric_family.get_field_type() {
+        MetricType::COUNTER => {
+            let counter = match meter
+                .f64_observable_counter(name.to_string())
+                .with_description(help)
+                .try_init()
+            {
+                Ok(counter) => counter,
+                Err(err) => {
+                    error!("create otlp counter {} failed: {}", name, err);
+                    return;
+                }
+            };
+
+            for metric in metric_family.get_metric() {
+                counter.observe(cx, metric.get_counter().get_value(), &attributes(metric));
+            }
+        }
+        MetricType::GAUGE => {
+            let gauge = match meter
+                .f64_observable_gauge(name.to_string())
+                .with_description(help)
+                .try_init()
+            {
+                Ok(gauge) => gauge,
+                Err(err) => {
+                    error!("create otlp gauge {} failed: {}", name, err);
+                    return;
+                }
+            };
+
+            for metric in metric_family.get_metric() {
+                gauge.observe(cx, metric.get_gauge().get_value(), &attributes(metric));
+            }
+        }
+        MetricType::HISTOGRAM => {
+            let (sum, count) = match (
+                meter
+                    .f64_observable_counter(format!("{}_sum", name))
+                    .with_description(help)
+                    .try_init(),
+                meter
+                    .u64_observable_counter(format!("{}_count", name))
+                    .with_description(help)
+                    .try_init(),
+            ) {
+                (Ok(sum), Ok(count)) => (sum, count),
+                (Err(err), _) | (_, Err(err)) => {
+                    error!("create otlp histogram {} failed: {}", name, err);
+                    return;
+                }
+            };
+
+            for metric in metric_family.get_metric() {
+                let attributes = attributes(metric);
+                let histogram = metric.get_histogram();
+                sum.observe(cx, histogram.get_sample_sum(), &attributes);
+                count.observe(cx, histogram.get_sample_count(), &attributes);
+            }
+        }
+        _ => {}
+    }
+}
+
+// attributes returns the attributes of the otlp metric by the labels of the metric.
+fn attributes(metric: &Metric) -> Vec<KeyValue> {
+    metric
+        .get_label()
+        .iter()
+        .map(|label| KeyValue::new(label.get_name().to_string(), label.get_value().to_string()))
+        .collect()
+}
+
 // Metrics is the metrics server.
-#[derive(Debug)]
 pub struct Metrics {
     // addr is the address of the metrics server.
     addr: SocketAddr,
 
+    // storage is used to collect the statistics of the metadata store.
+    storage: Arc<Storage>,
+
     // shutdown is used to shutdown the metrics server.
     shutdown: shutdown::Shutdown,
 
@@ -358,11 +565,13 @@ impl Metrics {
     // new creates a new Metrics.
     pub fn new(
         addr: SocketAddr,
+        storage: Arc<Storage>,
         shutdown: shutdown::Shutdown,
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
     ) -> Self {
         Self {
             addr,
+            storage,
             shutdown,
             _shutdown_complete: shutdown_complete_tx,
         }
@@ -391,9 +600,11 @@ impl Metrics {
             .set(1);
 
         // Create the metrics route.
+        let storage = self.storage.clone();
         let metrics_route = warp::path!("metrics")
             .and(warp::get())
             .and(warp::path::end())
+            .and(warp::any().map(move || storage.clone()))
             .and_then(Self::metrics_handler);
 
         // Start the metrics server and wait for it to finish.
@@ -459,10 +670,46 @@ impl Metrics {
         REGISTRY
             .register(Box::new(PROXY_REQUSET_FAILURE_COUNT.clone()))
             .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(ANNOUNCE_HOST_COUNT.clone()))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(ANNOUNCE_HOST_FAILURE_COUNT.clone()))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(ANNOUNCE_HOST_DURATION.clone()))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(STORAGE_CHECK_DELETED_COUNT.clone()))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(STORAGE_METADATA_ESTIMATED_KEYS_GAUGE.clone()))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(
+                STORAGE_METADATA_PENDING_COMPACTION_BYTES_GAUGE.clone(),
+            ))
+            .expect("metric can be registered");
+
+        REGISTRY
+            .register(Box::new(STORAGE_METADATA_WRITE_STALL_GAUGE.clone()))
+            .expect("metric can be registered");
     }
 
     // metrics_handler handles the metrics request.
-    async fn metrics_handler() -> Result<impl Reply, Rejection> {
+    async fn metrics_handler(storage: Arc<Storage>) -> Result<impl Reply, Rejection> {
+        // Collect the statistics of the metadata store when the metrics are scraped.
+        match storage.metadata_statistics() {
+            Ok(statistics) => collect_storage_metadata_metrics(&statistics),
+            Err(err) => error!("collect metadata statistics failed: {}", err),
+        }
+
         let encoder = TextEncoder::new();
 
         // Encode custom metrics.
@@ -499,3 +746,56 @@ impl Metrics {
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::sdk::export::metrics::{
+        aggregation::cumulative_temporality_selector, InstrumentationLibraryReader,
+    };
+    use opentelemetry::sdk::metrics::{controllers, processors, selectors};
+    use std::collections::HashSet;
+
+    #[test]
+    fn should_bridge_metrics_to_otlp_meter() {
+        let _ = REGISTRY.register(Box::new(VERSION_GAUGE.clone()));
+        let _ = REGISTRY.register(Box::new(ANNOUNCE_HOST_COUNT.clone()));
+        let _ = REGISTRY.register(Box::new(ANNOUNCE_HOST_DURATION.clone()));
+        VERSION_GAUGE
+            .with_label_values(&["v0.1.0", "foo", "linux", "2024-01-01"])
+            .set(1);
+        collect_announce_host_started_metrics();
+        collect_announce_host_finished_metrics(Duration::from_millis(5));
+
+        // The metrics are collected by the in-memory controller.
+        let controller = controllers::basic(processors::factory(
+            selectors::simple::inexpensive(),
+            cumulative_temporality_selector(),
+        ))
+        .build();
+        register_otlp_metrics(&controller.meter("dragonfly-client")).unwrap();
+        controller.collect(&Context::new()).unwrap();
+
+        let mut names = HashSet::new();
+        controller
+            .try_for_each(&mut |_, reader| {
+                reader.try_for_each(&cumulative_temporality_selector(), &mut |record| {
+                    names.insert(record.descriptor().name().to_string());
+                    Ok(())
+                })
+            })
+            .unwrap();
+
+        for name in [
+            "dragonfly_client_version",
+            "dragonfly_client_announce_host_total",
+            "dragonfly_client_announce_host_duration_milliseconds_sum",
+            "dragonfly_client_announce_host_duration_milliseconds_count",
+            "process_cpu_seconds_total",
+            "process_resident_memory_bytes",
+        ] {
+            assert!(names.contains(name), "{} is not bridged: {:?}", name, names);
+        }
+    }
+}
@@ -31,8 +31,8 @@ use dragonfly_client_core::error::{ErrorType, OrErr};
 use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
 use dragonfly_client_util::{
     http::{
-        hashmap_to_hyper_header_map, hyper_headermap_to_reqwest_headermap,
-        reqwest_headermap_to_hashmap,
+        hashmap_to_hyper_header_map, hyper_headermap_to_reqwest_headermap, redact_hyper_header_map,
+        redact_url, reqwest_headermap_to_hashmap,
     },
     tls::{
         generate_ca_cert_from_pem, generate_certs_from_pem, generate_self_signed_certs_by_ca_cert,
@@ -277,7 +277,12 @@ pub async fn http_handler(
     request: Request<hyper::body::Incoming>,
     registry_certs: Arc<Option<Vec<CertificateDer<'static>>>>,
 ) -> ClientResult<Response> {
-    info!("handle HTTP request: {:?}", request);
+    info!(
+        "handle HTTP request for method: {}, uri: {}, header: {:?}",
+        request.method(),
+        redact_url(&request.uri().to_string()),
+        redact_hyper_header_map(request.headers(), &config.tracing.redacted_headers)
+    );
 
     // If find the matching rule, proxy the request via the dfdaemon.
     let request_uri = request.uri();
@@ -287,7 +292,7 @@ pub async fn http_handler(
         info!(
             "proxy HTTP request via dfdaemon for method: {}, uri: {}",
             request.method(),
-            request_uri
+            redact_url(&request_uri.to_string())
         );
         return proxy_by_dfdaemon(config, task, rule.clone(), request).await;
     }
@@ -296,7 +301,7 @@ pub async fn http_handler(
         info!(
             "proxy HTTPS request directly to remote server for method: {}, uri: {}",
             request.method(),
-            request.uri()
+            redact_url(&request.uri().to_string())
         );
         return proxy_https(request, registry_certs).await;
     }
@@ -304,7 +309,7 @@ pub async fn http_handler(
     info!(
         "proxy HTTP request directly to remote server for method: {}, uri: {}",
         request.method(),
-        request.uri()
+        redact_url(&request.uri().to_string())
     );
     return proxy_http(request).await;
 }
@@ -318,7 +323,12 @@ pub async fn https_handler(
     registry_certs: Arc<Option<Vec<CertificateDer<'static>>>>,
     server_ca_cert: Arc<Option<Certificate>>,
 ) -> ClientResult<Response> {
-    info!("handle HTTPS request: {:?}", request);
+    info!(
+        "handle HTTPS request for method: {}, uri: {}, header: {:?}",
+        request.method(),
+        redact_url(&request.uri().to_string()),
+        redact_hyper_header_map(request.headers(), &config.tracing.redacted_headers)
+    );
 
     // Proxy the request directly  to the remote server.
     if let Some(host) = request.uri().host() {
@@ -672,7 +682,10 @@ async fn proxy_by_dfdaemon(
 #[instrument(skip_all)]
 async fn proxy_http(request: Request<hyper::body::Incoming>) -> ClientResult<Response> {
     let Some(host) = request.uri().host() else {
-        error!("CONNECT host is not socket addr: {:?}", request.uri());
+        error!(
+            "CONNECT host is not socket addr: {}",
+            redact_url(&request.uri().to_string())
+        );
         return Ok(make_error_response(
             http::StatusCode::BAD_REQUEST,
             "CONNECT must be to a socket address",
@@ -15,6 +15,7 @@
  */
 
 use crate::shutdown;
+use crate::tracing::{set_log_filter, LogLevelHandle};
 use pprof::protos::Message;
 use pprof::ProfilerGuard;
 use serde::{Deserialize, Serialize};
@@ -22,7 +23,7 @@ use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{error, info};
-use warp::{Filter, Rejection, Reply};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
 
 // DEFAULT_PROFILER_SECONDS is the default seconds to start profiling.
 const DEFAULT_PROFILER_SECONDS: u64 = 10;
@@ -57,6 +58,9 @@ pub struct Stats {
     // addr is the address of the stats server.
     addr: SocketAddr,
 
+    // log_level_handle is used to change the log filter at runtime.
+    log_level_handle: LogLevelHandle,
+
     // shutdown is used to shutdown the stats server.
     shutdown: shutdown::Shutdown,
 
@@ -69,11 +73,13 @@ impl Stats {
     // new creates a new Stats.
     pub fn new(
         addr: SocketAddr,
+        log_level_handle: LogLevelHandle,
         shutdown: shutdown::Shutdown,
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
     ) -> Self {
         Self {
             addr,
+            log_level_handle,
             shutdown,
             _shutdown_complete: shutdown_complete_tx,
         }
@@ -98,10 +104,18 @@ impl Stats {
         // Create the pprof routes.
         let pprof_routes = pprof_profile_route.or(pprof_heap_route);
 
+        // Create the log level route.
+        let log_level_handle = self.log_level_handle.clone();
+        let log_level_route = warp::path!("debug" / "log-level")
+            .and(warp::put())
+            .and(warp::body::bytes())
+            .and(warp::any().map(move || log_level_handle.clone()))
+            .and_then(Self::log_level_handler);
+
         // Start the stats server and wait for it to finish.
         info!("stats server listening on {}", self.addr);
         tokio::select! {
-            _ = warp::serve(pprof_routes).run(self.addr) => {
+            _ = warp::serve(pprof_routes.or(log_level_route)).run(self.addr) => {
                 // Stats server ended.
                 info!("stats server ended");
             }
@@ -146,6 +160,29 @@ impl Stats {
         Ok(body)
     }
 
+    // log_level_handler handles the log level request, the body is a level such as
+    // `debug`, or a full filter directive such as `info,dragonfly_client::grpc=debug`.
+    async fn log_level_handler(
+        body: bytes::Bytes,
+        log_level_handle: LogLevelHandle,
+    ) -> Result<impl Reply, Rejection> {
+        let directive = match std::str::from_utf8(&body) {
+            Ok(directive) => directive.trim(),
+            Err(err) => {
+                error!("invalid log filter: {}", err);
+                return Ok(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        match set_log_filter(&log_level_handle, directive) {
+            Ok(()) => Ok(StatusCode::OK),
+            Err(err) => {
+                error!("failed to set log filter {}: {}", directive, err);
+                Ok(StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
     // pprof_heap_handler handles the pprof heap request.
     async fn pprof_heap_handler() -> Result<impl Reply, Rejection> {
         info!("start heap profiling");
@@ -63,7 +63,7 @@ pub mod piece_collector;
 // Task represents a task manager.
 pub struct Task {
     // config is the configuration of the dfdaemon.
-    config: Arc<Config>,
+    pub config: Arc<Config>,
 
     // id_generator is the id generator.
     pub id_generator: Arc<IDGenerator>,
@@ -180,6 +180,26 @@ impl Task {
         self.storage.prefetch_task_failed(id).await
     }
 
+    // set_pinned pins or unpins the task, the pinned task is protected from gc.
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> ClientResult<metadata::Task> {
+        if pinned {
+            self.storage.pin_task(id)
+        } else {
+            self.storage.unpin_task(id)
+        }
+    }
+
+    // get_task returns the local metadata of the task.
+    pub fn get_task(&self, id: &str) -> ClientResult<Option<metadata::Task>> {
+        self.storage.get_task(id)
+    }
+
+    // delete_task deletes the task from the local storage, the pinned task is deleted
+    // only if force is true.
+    pub async fn delete_task(&self, id: &str, force: bool) -> ClientResult<()> {
+        self.storage.delete_task_with_force(id, force).await
+    }
+
     // hard_link_or_copy hard links or copies the task content to the destination.
     pub async fn hard_link_or_copy(
         &self,
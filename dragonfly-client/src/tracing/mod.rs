@@ -14,33 +14,328 @@
  * limitations under the License.
  */
 
+mod rolling;
+#[cfg(feature = "syslog")]
+mod syslog;
+
+use crate::metrics::register_otlp_metrics;
+use dragonfly_client_config::dfdaemon::{Tracing, TracingProtocol, TracingTLS};
+use dragonfly_client_core::{
+    error::{ErrorType, ExternalError, OrErr},
+    Error, Result,
+};
+use opentelemetry::sdk::export::metrics::aggregation::cumulative_temporality_selector;
+use opentelemetry::sdk::metrics::{controllers::BasicController, selectors};
 use opentelemetry::sdk::propagation::TraceContextPropagator;
-use rolling_file::*;
+use opentelemetry::sdk::trace::{Sampler, Tracer, TracerProvider};
+use opentelemetry::sdk::Resource;
+use opentelemetry::{metrics::MeterProvider, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_tonic::metadata::{MetadataKey, MetadataMap};
+use opentelemetry_tonic::transport::{Certificate, ClientTlsConfig, Identity};
+use rolling::LogFileAppender;
+use rolling_file::RollingConditionBasic;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::OpenOptions;
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
-use tracing::{info, Level};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn, Level};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_log::LogTracer;
 use tracing_subscriber::{
     filter::LevelFilter,
-    fmt::{time::ChronoLocal, Layer},
+    fmt::{time::ChronoLocal, Layer, MakeWriter},
     prelude::*,
-    EnvFilter, Registry,
+    registry::LookupSpan,
+    reload, EnvFilter, Registry,
 };
 
-pub fn init_tracing(
-    name: &str,
-    log_dir: &PathBuf,
-    log_level: Level,
-    log_max_files: usize,
-    jaeger_addr: Option<String>,
-    redirect_stderr: bool,
-    verbose: bool,
-) -> Vec<WorkerGuard> {
+// OTEL_EXPORTER_OTLP_ENDPOINT is the environment variable of the base endpoint of the otlp
+// exporter, the spans are exported to the /v1/traces path of the endpoint.
+const OTEL_EXPORTER_OTLP_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+// OTEL_EXPORTER_OTLP_TRACES_ENDPOINT is the environment variable of the endpoint of the otlp
+// exporter for the spans, it takes precedence over the OTEL_EXPORTER_OTLP_ENDPOINT.
+const OTEL_EXPORTER_OTLP_TRACES_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT";
+
+// OTEL_EXPORTER_OTLP_PROTOCOL is the environment variable of the protocol of the otlp exporter,
+// grpc or http/protobuf, it takes precedence over the configured protocol.
+const OTEL_EXPORTER_OTLP_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_PROTOCOL";
+
+// OTEL_EXPORTER_OTLP_TRACES_PROTOCOL is the environment variable of the protocol of the otlp
+// exporter for the spans, it takes precedence over the OTEL_EXPORTER_OTLP_PROTOCOL.
+const OTEL_EXPORTER_OTLP_TRACES_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL";
+
+// OTEL_EXPORTER_OTLP_METRICS_ENDPOINT is the environment variable of the endpoint of the otlp
+// exporter for the metrics, it takes precedence over the endpoint of the spans.
+const OTEL_EXPORTER_OTLP_METRICS_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT";
+
+// DEFAULT_OTLP_METRICS_ENDPOINT is the default endpoint of the otlp metrics exporter, the
+// metrics are pushed by grpc.
+const DEFAULT_OTLP_METRICS_ENDPOINT: &str = "http://localhost:4317";
+
+// OTEL_EXPORTER_OTLP_HEADERS is the environment variable of the headers of the otlp exporter,
+// e.g. "api-key=foo,tenant=bar".
+const OTEL_EXPORTER_OTLP_HEADERS: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+
+// OTEL_RESOURCE_ATTRIBUTES is the environment variable of the resource attributes of the spans,
+// e.g. "deployment.environment=production,cluster=foo".
+const OTEL_RESOURCE_ATTRIBUTES: &str = "OTEL_RESOURCE_ATTRIBUTES";
+
+// LogLevelHandle is the handle to change the log filter at runtime, it keeps the configured
+// log filters, so they are appended again when the log level is changed.
+#[derive(Clone, Debug)]
+pub struct LogLevelHandle {
+    // handle reloads the env filter of the subscriber.
+    handle: reload::Handle<EnvFilter, Registry>,
+
+    // log_filters are the configured filter directives appended to the log level.
+    log_filters: Arc<Vec<String>>,
+
+    // level is the current log level, it is none if the filter is replaced by a full
+    // directive or created from the RUST_LOG environment variable.
+    level: Arc<Mutex<Option<Level>>>,
+}
+
+// LogLevelHandle implements the handle.
+impl LogLevelHandle {
+    // new returns a new LogLevelHandle.
+    fn new(
+        handle: reload::Handle<EnvFilter, Registry>,
+        level: Option<Level>,
+        log_filters: Vec<String>,
+    ) -> Self {
+        Self {
+            handle,
+            log_filters: Arc::new(log_filters),
+            level: Arc::new(Mutex::new(level)),
+        }
+    }
+}
+
+// TracingGuard flushes the buffered spans and logs when it is dropped, so it must be held
+// until the program exits.
+pub struct TracingGuard {
+    // meter_provider pushes the metrics to the otlp endpoint, it is stopped with the last
+    // push of the metrics.
+    meter_provider: Option<BasicController>,
+
+    // tracer_provider is flushed and shut down before the log guards are dropped, then the
+    // errors of exporting the last spans are still logged.
+    tracer_provider: Option<Box<dyn TracerProviderHandle>>,
+
+    // _log_guards flush the buffered logs of the non-blocking writers.
+    _log_guards: Vec<WorkerGuard>,
+}
+
+// TracingGuard implements Drop.
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(meter_provider) = self.meter_provider.take() {
+            if let Err(err) = meter_provider.stop(&opentelemetry::Context::current()) {
+                error!("stop meter provider failed: {}", err);
+            }
+        }
+
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            tracer_provider.force_flush();
+            tracer_provider.shutdown();
+        }
+    }
+}
+
+// TracerProviderHandle is the handle of the tracer provider flushed and shut down by the
+// tracing guard.
+trait TracerProviderHandle: Send {
+    // force_flush exports the spans buffered in the span processors.
+    fn force_flush(&self);
+
+    // shutdown shuts down the tracer provider, the spans are not exported anymore.
+    fn shutdown(&self);
+}
+
+// TracerProvider implements TracerProviderHandle.
+impl TracerProviderHandle for TracerProvider {
+    fn force_flush(&self) {
+        for result in TracerProvider::force_flush(self) {
+            if let Err(err) = result {
+                error!("flush tracer provider failed: {}", err);
+            }
+        }
+    }
+
+    // shutdown removes the global tracer provider, then the span processors are shut down
+    // when the last reference of the tracer provider is dropped.
+    fn shutdown(&self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+// SpanExporter is the exporter of the sampled spans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SpanExporter {
+    // Otlp exports the spans to the otlp endpoint by grpc or http, which is configured by
+    // the standard opentelemetry environment variables or the tracing configuration.
+    Otlp {
+        protocol: TracingProtocol,
+        endpoint: String,
+        headers: HashMap<String, String>,
+    },
+
+    // Jaeger exports the spans to the jaeger agent.
+    Jaeger {
+        addr: String,
+    },
+}
+
+// LogFormat is the format of the log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    // Compact is the human-oriented compact format.
+    #[default]
+    #[value(alias = "text")]
+    Compact,
+
+    // Json is the machine-parseable json format, one json object per line.
+    Json,
+}
+
+// LogRotation is the rotation policy of the log file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogRotation {
+    // Hourly rotates the log file every hour.
+    #[default]
+    Hourly,
+
+    // Daily rotates the log file every day.
+    Daily,
+
+    // MaxSize rotates the log file when its size exceeds the bytes.
+    MaxSize(u64),
+}
+
+// LogRotation implements FromStr, the value is hourly, daily or the max size
+// with an optional unit, e.g. 1024, 512KB, 100MB or 1GB.
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            value => {
+                let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+                    Some(index) => value.split_at(index),
+                    None => (value, ""),
+                };
+
+                let multiplier = match unit.trim() {
+                    "" | "b" => 1,
+                    "kb" | "k" => 1024,
+                    "mb" | "m" => 1024 * 1024,
+                    "gb" | "g" => 1024 * 1024 * 1024,
+                    _ => return Err(format!("invalid log rotation {}", s)),
+                };
+
+                match number
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(multiplier))
+                {
+                    Some(size) if size > 0 => Ok(LogRotation::MaxSize(size)),
+                    _ => Err(format!("invalid log rotation {}", s)),
+                }
+            }
+        }
+    }
+}
+
+// TracingOptions is the options to initialize the tracing, the command line tools use the
+// default tracing configuration, which only exports the spans configured by the OTEL
+// environment variables.
+#[derive(Debug, Clone)]
+pub struct TracingOptions {
+    // name is the name of the service, it is the name of the log file and the service
+    // name of the spans.
+    pub name: String,
+
+    // log_dir is the directory of the log files.
+    pub log_dir: PathBuf,
+
+    // log_level is the level of the logs.
+    pub log_level: Level,
+
+    // log_filters are the filter directives appended to the log level.
+    pub log_filters: Vec<String>,
+
+    // log_max_files is the max number of the log files.
+    pub log_max_files: usize,
+
+    // log_format is the format of the logs.
+    pub log_format: LogFormat,
+
+    // log_rotation is the rotation policy of the log file.
+    pub log_rotation: LogRotation,
+
+    // log_compression indicates whether compress the rotated log files.
+    pub log_compression: bool,
+
+    // tracing is the configuration of exporting the spans, the metrics and the syslog.
+    pub tracing: Tracing,
+
+    // redirect_stderr indicates whether redirect the stderr to the log directory.
+    pub redirect_stderr: bool,
+
+    // verbose indicates whether print the logs to the stdout.
+    pub verbose: bool,
+}
+
+// TracingOptions implements Default.
+impl Default for TracingOptions {
+    fn default() -> Self {
+        Self {
+            name: dragonfly_client_config::SERVICE_NAME.to_string(),
+            log_dir: dragonfly_client_config::default_log_dir(),
+            log_level: Level::INFO,
+            log_filters: Vec::new(),
+            log_max_files: 6,
+            log_format: LogFormat::default(),
+            log_rotation: LogRotation::default(),
+            log_compression: false,
+            tracing: Tracing::default(),
+            redirect_stderr: false,
+            verbose: false,
+        }
+    }
+}
+
+// init_tracing initializes the logs, the spans and the metrics by the options, the returned
+// guard must be held until the program exits.
+pub fn init_tracing(options: TracingOptions) -> (TracingGuard, LogLevelHandle) {
+    let TracingOptions {
+        name,
+        log_dir,
+        log_level,
+        log_filters,
+        log_max_files,
+        log_format,
+        log_rotation,
+        log_compression,
+        tracing: tracing_config,
+        redirect_stderr,
+        verbose,
+    } = options;
+
     let mut guards = vec![];
 
+    // The warnings of initializing the layers are logged after the subscriber is
+    // installed, then they are written to the log file instead of the stderr.
+    let mut warnings: Vec<String> = Vec::new();
+
     // Setup stdout layer.
     let (stdout_writer, stdout_guard) = tracing_appender::non_blocking(std::io::stdout());
 
@@ -50,69 +345,142 @@ pub fn init_tracing(
     } else {
         LevelFilter::OFF
     };
-    let stdout_logging_layer = Layer::new()
-        .with_writer(stdout_writer)
-        .with_file(true)
-        .with_line_number(true)
-        .with_target(false)
-        .with_thread_names(false)
-        .with_thread_ids(false)
-        .with_timer(ChronoLocal::rfc_3339())
-        .pretty()
-        .with_filter(stdout_filter);
+    let stdout_logging_layer = stdout_layer(stdout_writer, log_format).with_filter(stdout_filter);
     guards.push(stdout_guard);
 
     // Setup file layer.
-    fs::create_dir_all(log_dir).expect("failed to create log directory");
-    let rolling_appender = BasicRollingFileAppender::new(
-        log_dir.join(name).with_extension("log"),
-        RollingConditionBasic::new().hourly(),
+    fs::create_dir_all(&log_dir).expect("failed to create log directory");
+    let rolling_appender = rolling_file_appender(
+        log_dir.join(&name).with_extension("log"),
+        log_rotation,
         log_max_files,
+        log_compression,
     )
     .expect("failed to create rolling file appender");
 
     let (rolling_writer, rolling_writer_guard) = tracing_appender::non_blocking(rolling_appender);
-    let file_logging_layer = Layer::new()
-        .with_writer(rolling_writer)
-        .with_ansi(false)
-        .with_file(true)
-        .with_line_number(true)
-        .with_target(false)
-        .with_thread_names(false)
-        .with_thread_ids(false)
-        .with_timer(ChronoLocal::rfc_3339())
-        .compact();
+    let file_logging_layer = file_layer(rolling_writer, log_format);
     guards.push(rolling_writer_guard);
 
-    // Setup env filter for log level.
-    let env_filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::default().add_directive(log_level.into()));
+    // Setup env filter for log level, the log level is unknown if the filter is created
+    // from the RUST_LOG environment variable.
+    let rust_log = std::env::var(EnvFilter::DEFAULT_ENV).ok();
+    let current_log_level = match rust_log {
+        Some(ref rust_log) if EnvFilter::try_new(rust_log).is_ok() => None,
+        _ => Some(log_level),
+    };
+    let env_filter = env_filter(rust_log, log_level, &log_filters, &mut warnings);
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    let log_level_handle = LogLevelHandle::new(handle, current_log_level, log_filters);
 
     let subscriber = Registry::default()
         .with(env_filter)
         .with(file_logging_layer)
-        .with(stdout_logging_layer);
-
-    // Setup jaeger layer.
-    if let Some(jaeger_addr) = jaeger_addr {
-        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
-        let tracer = opentelemetry_jaeger::new_agent_pipeline()
-            .with_service_name(name)
-            .with_endpoint(jaeger_addr)
-            .install_batch(opentelemetry::runtime::Tokio)
-            .expect("install");
-        let jaeger_layer = tracing_opentelemetry::layer().with_tracer(tracer);
-        let subscriber = subscriber.with(jaeger_layer);
-
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("failed to set global subscriber");
-    } else {
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("failed to set global subscriber");
+        .with(stdout_logging_layer)
+        .with(syslog_layer(
+            &name,
+            tracing_config.syslog_addr,
+            &mut guards,
+            &mut warnings,
+        ));
+
+    // Setup tracing layer, the layer is not installed if no span is sampled. The spans
+    // are exported to the otlp endpoint of the environment variables if it is set,
+    // otherwise to the otlp collector or the jaeger agent of the address.
+    let span_exporter = span_exporter(
+        tracing_config.addr,
+        tracing_config.protocol,
+        tracing_config.headers,
+    );
+    // The warnings of the metrics endpoint are logged only if the metrics are pushed.
+    let mut metrics_warnings = Vec::new();
+    let (metrics_endpoint, metrics_headers) = metrics_exporter(
+        span_exporter.as_ref(),
+        tracing_config.metrics_endpoint.clone(),
+        &mut metrics_warnings,
+    );
+    if tracing_config.metrics_otlp {
+        warnings.extend(metrics_warnings);
+    }
+
+    let sampler = span_exporter
+        .as_ref()
+        .and_then(|_| sampler(tracing_config.sample_ratio));
+    let mut tracer_provider: Option<Box<dyn TracerProviderHandle>> = None;
+    let resource = resource(
+        &name,
+        resource_attributes(
+            std::env::var(OTEL_RESOURCE_ATTRIBUTES).ok(),
+            tracing_config.resource_attributes,
+        ),
+    );
+    let tracer = match (span_exporter, sampler) {
+        (Some(span_exporter), Some(sampler)) => {
+            opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+            let trace_config = opentelemetry::sdk::trace::config()
+                .with_sampler(sampler)
+                .with_resource(resource.clone());
+
+            Some(tracer(span_exporter, &tracing_config.tls, trace_config))
+        }
+        _ => None,
+    };
+
+    match tracer {
+        Some(Ok(tracer)) => {
+            // The tracer provider is held by the tracing guard to export the buffered
+            // spans when the program exits.
+            tracer_provider = tracer
+                .provider()
+                .map(|provider| Box::new(provider) as Box<dyn TracerProviderHandle>);
+            let opentelemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
+            let subscriber = subscriber.with(opentelemetry_layer);
+
+            tracing::subscriber::set_global_default(subscriber)
+                .expect("failed to set global subscriber");
+        }
+        Some(Err(err)) => {
+            // The tracing layer is not installed if the exporter fails to build, then
+            // the logs are still written without exporting the spans.
+            tracing::subscriber::set_global_default(subscriber)
+                .expect("failed to set global subscriber");
+            warn!(
+                "tracing layer is skipped, failed to build span exporter: {}",
+                err
+            );
+        }
+        None => {
+            tracing::subscriber::set_global_default(subscriber)
+                .expect("failed to set global subscriber");
+        }
     }
 
     LogTracer::init().expect("failed to init LogTracer");
 
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+
+    // Setup metrics pipeline, the metrics share the resource and the tls with the spans. The
+    // metrics are not pushed if the meter provider fails to build.
+    let meter_provider = match meter_provider(
+        tracing_config.metrics_otlp,
+        tracing_config.metrics_interval,
+        metrics_endpoint,
+        &metrics_headers,
+        &tracing_config.tls,
+        resource,
+    ) {
+        Ok(meter_provider) => meter_provider,
+        Err(err) => {
+            warn!(
+                "otlp metrics are skipped, failed to build meter provider: {}",
+                err
+            );
+            None
+        }
+    };
+
     info!(
         "tracing initialized directory: {}, level: {}",
         log_dir.as_path().display(),
@@ -121,10 +489,641 @@ pub fn init_tracing(
 
     // Redirect stderr to file.
     if redirect_stderr {
-        redirect_stderr_to_file(log_dir);
+        redirect_stderr_to_file(&log_dir);
+    }
+
+    (
+        TracingGuard {
+            meter_provider,
+            tracer_provider,
+            _log_guards: guards,
+        },
+        log_level_handle,
+    )
+}
+
+// set_log_filter changes the log filter at runtime, the directive can be a level
+// such as `debug` which keeps the configured log filters, or a full filter directive
+// such as `info,dragonfly_client::grpc=debug` which replaces the whole log filter.
+pub fn set_log_filter(handle: &LogLevelHandle, directive: &str) -> Result<()> {
+    if directive.is_empty() {
+        return Err(Error::InvalidParameter);
+    }
+
+    if let Ok(level) = Level::from_str(directive) {
+        return set_log_level(handle, level);
+    }
+
+    let env_filter = EnvFilter::try_new(directive).or_err(ErrorType::ParseError)?;
+    handle
+        .handle
+        .reload(env_filter)
+        .or_err(ErrorType::ConfigError)?;
+    *handle.level.lock().unwrap() = None;
+
+    info!("log filter changed to {}", directive);
+    Ok(())
+}
+
+// set_log_level changes the log level at runtime, the configured log filters are
+// appended to the level.
+pub fn set_log_level(handle: &LogLevelHandle, level: Level) -> Result<()> {
+    let mut warnings = Vec::new();
+    let env_filter = env_filter(None, level, &handle.log_filters, &mut warnings);
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+
+    handle
+        .handle
+        .reload(env_filter)
+        .or_err(ErrorType::ConfigError)?;
+    *handle.level.lock().unwrap() = Some(level);
+
+    info!("log level changed to {}", level);
+    Ok(())
+}
+
+// toggle_debug_log_level changes the log level to DEBUG, if DEBUG is already enabled,
+// it changes the log level back to the default level. It returns the new log level.
+pub fn toggle_debug_log_level(handle: &LogLevelHandle, default_level: Level) -> Result<Level> {
+    // The log filters may enable DEBUG for some targets, so the current log level is
+    // checked instead of the filter if it is known.
+    let current_level = *handle.level.lock().unwrap();
+    let debug_enabled = match current_level {
+        Some(level) => level >= Level::DEBUG,
+        None => handle
+            .handle
+            .with_current(|env_filter| {
+                env_filter
+                    .max_level_hint()
+                    .map_or(false, |level| level >= LevelFilter::DEBUG)
+            })
+            .or_err(ErrorType::ConfigError)?,
+    };
+
+    let level = if debug_enabled {
+        default_level
+    } else {
+        Level::DEBUG
+    };
+
+    set_log_level(handle, level)?;
+    Ok(level)
+}
+
+// env_filter creates the filter with the log level and the filter directives appended in order,
+// the filter is created from the RUST_LOG environment variable instead if it is valid. The
+// invalid directives are skipped and pushed to the warnings, which are logged by the caller.
+fn env_filter(
+    rust_log: Option<String>,
+    log_level: Level,
+    log_filters: &[String],
+    warnings: &mut Vec<String>,
+) -> EnvFilter {
+    if let Some(rust_log) = rust_log {
+        match EnvFilter::try_new(&rust_log) {
+            Ok(env_filter) => return env_filter,
+            Err(err) => warnings.push(format!(
+                "invalid {} {}, fallback to log level {}: {}",
+                EnvFilter::DEFAULT_ENV,
+                rust_log,
+                log_level,
+                err
+            )),
+        }
     }
 
-    guards
+    log_filters.iter().fold(
+        EnvFilter::default().add_directive(log_level.into()),
+        |env_filter, log_filter| match log_filter.parse() {
+            Ok(directive) => env_filter.add_directive(directive),
+            Err(err) => {
+                warnings.push(format!("invalid log filter {}: {}", log_filter, err));
+                env_filter
+            }
+        },
+    )
+}
+
+// span_exporter returns the exporter of the spans, the otlp exporter configured by the
+// environment variables takes precedence over the address. The address is the otlp
+// collector if the protocol is set, otherwise the jaeger agent. It returns none if
+// neither is set.
+fn span_exporter(
+    jaeger_addr: Option<String>,
+    jaeger_protocol: Option<TracingProtocol>,
+    jaeger_headers: HashMap<String, String>,
+) -> Option<SpanExporter> {
+    // The protocol of the environment variables takes precedence over the configured
+    // protocol, and the invalid protocol is ignored. The otlp endpoint of the environment
+    // variables uses http by default.
+    let env_protocol = env_value(OTEL_EXPORTER_OTLP_TRACES_PROTOCOL)
+        .or_else(|| env_value(OTEL_EXPORTER_OTLP_PROTOCOL))
+        .and_then(|protocol| TracingProtocol::from_str(&protocol).ok());
+    let protocol = env_protocol
+        .or(jaeger_protocol)
+        .unwrap_or(TracingProtocol::Http);
+
+    // The configured headers override the headers of the environment variable.
+    let mut headers = env_value(OTEL_EXPORTER_OTLP_HEADERS)
+        .map(|headers| parse_key_values(&headers))
+        .unwrap_or_default();
+    headers.extend(jaeger_headers);
+
+    // The endpoint of the traces signal is used as is, and the base endpoint is
+    // normalized with the protocol.
+    if let Some(endpoint) = env_value(OTEL_EXPORTER_OTLP_TRACES_ENDPOINT) {
+        return Some(SpanExporter::Otlp {
+            protocol,
+            endpoint,
+            headers,
+        });
+    }
+
+    if let Some(endpoint) = env_value(OTEL_EXPORTER_OTLP_ENDPOINT) {
+        return Some(SpanExporter::Otlp {
+            protocol,
+            endpoint: otlp_endpoint(&endpoint, protocol),
+            headers,
+        });
+    }
+
+    let addr = jaeger_addr?;
+    match jaeger_protocol {
+        Some(_) => Some(SpanExporter::Otlp {
+            protocol,
+            endpoint: otlp_endpoint(&addr, protocol),
+            headers,
+        }),
+        None => Some(SpanExporter::Jaeger { addr }),
+    }
+}
+
+// otlp_endpoint normalizes the base endpoint of the otlp collector, the endpoint without the
+// scheme is plaintext, e.g. collector:4317 is http://collector:4317, and the https endpoint
+// is kept. The path of the traces signal is appended for http, and the grpc endpoint is
+// used without the path.
+fn otlp_endpoint(endpoint: &str, protocol: TracingProtocol) -> String {
+    let endpoint = endpoint.trim().trim_end_matches('/');
+    let endpoint = if endpoint.contains("://") {
+        endpoint.to_string()
+    } else {
+        format!("http://{}", endpoint)
+    };
+
+    match protocol {
+        TracingProtocol::Grpc => endpoint,
+        TracingProtocol::Http => format!("{}/v1/traces", endpoint),
+    }
+}
+
+// otlp_metadata converts the headers to the metadata of the grpc exporter, the header names
+// are lowercased by the metadata.
+fn otlp_metadata(headers: &HashMap<String, String>) -> Result<MetadataMap> {
+    let mut metadata = MetadataMap::with_capacity(headers.len());
+    for (key, value) in headers {
+        metadata.insert(
+            MetadataKey::from_bytes(key.as_bytes()).or_err(ErrorType::ParseError)?,
+            value.parse().or_err(ErrorType::ParseError)?,
+        );
+    }
+
+    Ok(metadata)
+}
+
+// tracer installs the batch span processor of the exporter and returns the tracer, it
+// returns an error if the exporter fails to build, e.g. the tls certs are invalid.
+fn tracer(
+    span_exporter: SpanExporter,
+    tls: &TracingTLS,
+    trace_config: opentelemetry::sdk::trace::Config,
+) -> Result<Tracer> {
+    let tracer = match span_exporter {
+        SpanExporter::Otlp {
+            protocol: TracingProtocol::Grpc,
+            endpoint,
+            headers,
+        } => {
+            let mut exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint.as_str())
+                .with_metadata(otlp_metadata(&headers)?);
+
+            // The tls is applied to the https endpoint, and the plaintext endpoint
+            // uses the plaintext channel.
+            if let Some(tls_config) = otlp_tls_config(endpoint.as_str(), tls)? {
+                exporter = exporter.with_tls_config(tls_config);
+            }
+
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(exporter)
+                .with_trace_config(trace_config)
+                .install_batch(opentelemetry::runtime::Tokio)
+        }
+        SpanExporter::Otlp {
+            protocol: TracingProtocol::Http,
+            endpoint,
+            headers,
+        } => {
+            let mut exporter = opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint.as_str())
+                .with_headers(headers);
+
+            // The tls is applied to the https endpoint, and the plaintext endpoint
+            // uses the default http client.
+            if let Some(http_client) = otlp_http_client(endpoint.as_str(), tls)? {
+                exporter = exporter.with_http_client(http_client);
+            }
+
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(exporter)
+                .with_trace_config(trace_config)
+                .install_batch(opentelemetry::runtime::Tokio)
+        }
+        SpanExporter::Jaeger { addr } => opentelemetry_jaeger::new_agent_pipeline()
+            .with_endpoint(addr)
+            .with_trace_config(trace_config)
+            .install_batch(opentelemetry::runtime::Tokio),
+    }
+    .or_err(ErrorType::ConfigError)?;
+
+    Ok(tracer)
+}
+
+// metrics_exporter returns the endpoint and the headers of the otlp metrics exporter. The
+// metrics are pushed by grpc, so the endpoint of the spans is shared only if the spans are
+// exported by grpc. If no endpoint is configured, the default endpoint of the local collector
+// is used with a warning pushed to the warnings.
+fn metrics_exporter(
+    span_exporter: Option<&SpanExporter>,
+    metrics_endpoint: Option<String>,
+    warnings: &mut Vec<String>,
+) -> (String, HashMap<String, String>) {
+    let (span_endpoint, headers) = match span_exporter {
+        Some(SpanExporter::Otlp {
+            protocol,
+            endpoint,
+            headers,
+        }) => (
+            (*protocol == TracingProtocol::Grpc).then(|| endpoint.clone()),
+            headers.clone(),
+        ),
+        _ => (
+            None,
+            env_value(OTEL_EXPORTER_OTLP_HEADERS)
+                .map(|headers| parse_key_values(&headers))
+                .unwrap_or_default(),
+        ),
+    };
+
+    let endpoint = env_value(OTEL_EXPORTER_OTLP_METRICS_ENDPOINT)
+        .or_else(|| {
+            metrics_endpoint.map(|endpoint| otlp_endpoint(&endpoint, TracingProtocol::Grpc))
+        })
+        .or(span_endpoint);
+    if let Some(endpoint) = endpoint {
+        return (endpoint, headers);
+    }
+
+    match span_exporter {
+        Some(SpanExporter::Otlp { endpoint, .. }) => warnings.push(format!(
+            "otlp metrics are pushed to the default endpoint {}, because the spans are \
+             exported by http to {}, set the metrics endpoint of the tracing to push \
+             the metrics to the collector",
+            DEFAULT_OTLP_METRICS_ENDPOINT, endpoint
+        )),
+        _ => warnings.push(format!(
+            "otlp metrics are pushed to the default endpoint {}, because the metrics endpoint \
+             of the tracing is not set",
+            DEFAULT_OTLP_METRICS_ENDPOINT
+        )),
+    }
+
+    (DEFAULT_OTLP_METRICS_ENDPOINT.to_string(), headers)
+}
+
+// meter_provider builds the meter provider which pushes the metrics of the registries to the
+// otlp endpoint by the interval, and sets it as the global meter provider. It returns none if
+// the otlp metrics are disabled.
+fn meter_provider(
+    metrics_otlp: bool,
+    interval: Duration,
+    endpoint: String,
+    headers: &HashMap<String, String>,
+    tls: &TracingTLS,
+    resource: Resource,
+) -> Result<Option<BasicController>> {
+    if !metrics_otlp {
+        return Ok(None);
+    }
+
+    let mut exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint.as_str())
+        .with_metadata(otlp_metadata(headers)?);
+
+    // The tls is applied to the https endpoint, and the plaintext endpoint
+    // uses the plaintext channel.
+    if let Some(tls_config) = otlp_tls_config(endpoint.as_str(), tls)? {
+        exporter = exporter.with_tls_config(tls_config);
+    }
+
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(
+            selectors::simple::inexpensive(),
+            cumulative_temporality_selector(),
+            opentelemetry::runtime::Tokio,
+        )
+        .with_exporter(exporter)
+        .with_resource(resource)
+        .with_period(interval)
+        .build()
+        .or_err(ErrorType::ConfigError)?;
+
+    // The metrics of the registries are observed when the meter provider collects them.
+    register_otlp_metrics(&meter_provider.versioned_meter(
+        "dragonfly-client",
+        Some(env!("CARGO_PKG_VERSION")),
+        None,
+    ))
+    .or_err(ErrorType::ConfigError)?;
+
+    Ok(Some(meter_provider))
+}
+
+// env_value returns the trimmed value of the environment variable, it returns none
+// if the environment variable is not set or empty.
+fn env_value(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// otlp_tls_config builds the tls configuration of the grpc exporter if the endpoint is https,
+// the collector is verified by the system root certs and the CA cert. It returns none if the
+// endpoint is plaintext.
+fn otlp_tls_config(endpoint: &str, tls: &TracingTLS) -> Result<Option<ClientTlsConfig>> {
+    if !endpoint.to_ascii_lowercase().starts_with("https://") {
+        return Ok(None);
+    }
+
+    if tls.insecure_skip_verify {
+        return Err(ExternalError::new(ErrorType::TLSConfigError)
+            .with_context("insecure skip verify is not supported by the grpc protocol")
+            .into());
+    }
+
+    let mut tls_config = ClientTlsConfig::new();
+    if let Some(ca_cert) = tls.ca_cert.as_ref() {
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(fs::read(ca_cert)?));
+    }
+
+    if let (Some(cert), Some(key)) = (tls.cert.as_ref(), tls.key.as_ref()) {
+        tls_config = tls_config.identity(Identity::from_pem(fs::read(cert)?, fs::read(key)?));
+    }
+
+    Ok(Some(tls_config))
+}
+
+// otlp_http_client builds the http client of the otlp exporter with the tls configuration if the
+// endpoint is https, the collector is verified by the CA cert or the system root certs. It returns
+// none if the endpoint is plaintext.
+fn otlp_http_client(
+    endpoint: &str,
+    tls: &TracingTLS,
+) -> Result<Option<opentelemetry_reqwest::Client>> {
+    if !endpoint.to_ascii_lowercase().starts_with("https://") {
+        return Ok(None);
+    }
+
+    let mut builder = opentelemetry_reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(tls.insecure_skip_verify);
+    if let Some(ca_cert) = tls.ca_cert.as_ref() {
+        let ca_cert = opentelemetry_reqwest::Certificate::from_pem(&fs::read(ca_cert)?)
+            .or_err(ErrorType::CertificateError)?;
+        builder = builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(ca_cert);
+    }
+
+    // The identity of the mutual tls is parsed from the client cert and key in one PEM buffer.
+    if let (Some(cert), Some(key)) = (tls.cert.as_ref(), tls.key.as_ref()) {
+        let mut identity = fs::read(cert)?;
+        identity.push(b'\n');
+        identity.extend(fs::read(key)?);
+        builder = builder.identity(
+            opentelemetry_reqwest::Identity::from_pem(&identity)
+                .or_err(ErrorType::CertificateError)?,
+        );
+    }
+
+    Ok(Some(builder.build().or_err(ErrorType::TLSConfigError)?))
+}
+
+// resource_attributes merges the resource attributes of the OTEL_RESOURCE_ATTRIBUTES environment
+// variable and the configured attributes, the configured attributes override the attributes of
+// the environment variable with the same key.
+fn resource_attributes(
+    otel_resource_attributes: Option<String>,
+    attributes: HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut merged = otel_resource_attributes
+        .map(|otel_resource_attributes| parse_key_values(&otel_resource_attributes))
+        .unwrap_or_default();
+    merged.extend(attributes);
+    merged
+}
+
+// parse_key_values parses the comma separated key value pairs of the opentelemetry environment
+// variables, e.g. "foo=bar,baz=qux", the values are percent decoded and the invalid pairs are
+// ignored.
+fn parse_key_values(content: &str) -> HashMap<String, String> {
+    content
+        .split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+
+            Some((key.to_string(), percent_decode(value.trim())))
+        })
+        .collect()
+}
+
+// percent_decode decodes the percent encoded string, the invalid escapes are kept as is.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let byte = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = byte {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// resource returns the resource of the traces with the service name, the additional
+// attributes override the built-in attributes with the same key.
+fn resource(name: &str, attributes: HashMap<String, String>) -> Resource {
+    Resource::new(vec![KeyValue::new("service.name", name.to_string())]).merge(&Resource::new(
+        attributes
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value)),
+    ))
+}
+
+// sampler returns the parent based sampler with the ratio of the sampled traces,
+// it returns none if the ratio is zero, then no span is sampled.
+fn sampler(ratio: f64) -> Option<Sampler> {
+    if ratio <= 0.0 {
+        return None;
+    }
+
+    if ratio >= 1.0 {
+        return Some(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)));
+    }
+
+    Some(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+        ratio,
+    ))))
+}
+
+// syslog_layer creates the layer sending the logs to the syslog, it returns none
+// if the syslog address is not set or the syslog is unreachable, then the logs are
+// still written to the file. The guard of the non-blocking syslog writer is pushed
+// to the guards, and the warnings are logged after the subscriber is installed.
+#[cfg(feature = "syslog")]
+fn syslog_layer<S>(
+    name: &str,
+    syslog_addr: Option<String>,
+    guards: &mut Vec<WorkerGuard>,
+    warnings: &mut Vec<String>,
+) -> Option<Box<dyn tracing_subscriber::Layer<S> + Send + Sync + 'static>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let syslog_addr = syslog_addr?;
+    match self::syslog::SyslogLayer::new(name, &syslog_addr) {
+        Ok((layer, guard)) => {
+            guards.push(guard);
+            Some(layer.boxed())
+        }
+        Err(err) => {
+            warnings.push(format!(
+                "syslog layer is skipped, failed to connect to syslog {}: {}",
+                syslog_addr, err
+            ));
+            None
+        }
+    }
+}
+
+// syslog_layer returns none if the syslog feature is disabled, the syslog address
+// is ignored.
+#[cfg(not(feature = "syslog"))]
+fn syslog_layer<S>(
+    _name: &str,
+    syslog_addr: Option<String>,
+    _guards: &mut Vec<WorkerGuard>,
+    warnings: &mut Vec<String>,
+) -> Option<Box<dyn tracing_subscriber::Layer<S> + Send + Sync + 'static>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    if let Some(syslog_addr) = syslog_addr {
+        warnings.push(format!(
+            "syslog address {} is ignored, because the syslog feature is disabled",
+            syslog_addr
+        ));
+    }
+
+    None
+}
+
+// rolling_file_appender creates the rolling file appender with the rotation policy,
+// the max files are kept for all rotation policies, and the rotated files are
+// compressed with gzip if the log compression is enabled.
+fn rolling_file_appender(
+    path: PathBuf,
+    log_rotation: LogRotation,
+    log_max_files: usize,
+    log_compression: bool,
+) -> std::io::Result<LogFileAppender> {
+    let condition = match log_rotation {
+        LogRotation::Hourly => RollingConditionBasic::new().hourly(),
+        LogRotation::Daily => RollingConditionBasic::new().daily(),
+        LogRotation::MaxSize(size) => RollingConditionBasic::new().max_size(size),
+    };
+
+    LogFileAppender::new(path, condition, log_max_files, log_compression)
+}
+
+// stdout_layer creates the layer writing the logs to the stdout with the format,
+// the compact format is pretty printed for the console.
+fn stdout_layer<S, W>(
+    writer: W,
+    log_format: LogFormat,
+) -> Box<dyn tracing_subscriber::Layer<S> + Send + Sync + 'static>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = Layer::new()
+        .with_writer(writer)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_names(false)
+        .with_thread_ids(false)
+        .with_timer(ChronoLocal::rfc_3339());
+
+    match log_format {
+        LogFormat::Compact => layer.with_target(false).pretty().boxed(),
+        LogFormat::Json => layer.with_ansi(false).json().flatten_event(true).boxed(),
+    }
+}
+
+// file_layer creates the layer writing the logs to the file with the format.
+fn file_layer<S, W>(
+    writer: W,
+    log_format: LogFormat,
+) -> Box<dyn tracing_subscriber::Layer<S> + Send + Sync + 'static>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = Layer::new()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_names(false)
+        .with_thread_ids(false)
+        .with_timer(ChronoLocal::rfc_3339());
+
+    match log_format {
+        LogFormat::Compact => layer.with_target(false).compact().boxed(),
+        LogFormat::Json => layer.json().flatten_event(true).boxed(),
+    }
 }
 
 // Redirect stderr to file.
@@ -140,3 +1139,940 @@ fn redirect_stderr_to_file(log_dir: &Path) {
         libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    // BufferWriter is the writer to capture the logs in memory.
+    #[derive(Clone, Default)]
+    struct BufferWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for BufferWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for BufferWriter {
+        type Writer = BufferWriter;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn should_write_json_logs() {
+        let writer = BufferWriter::default();
+        let subscriber = Registry::default().with(file_layer(writer.clone(), LogFormat::Json));
+        tracing::subscriber::with_default(subscriber, || {
+            info!(task_id = "foo", "download finished");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let line = content.lines().next().unwrap();
+        let log: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(log["level"], "INFO");
+        assert_eq!(log["message"], "download finished");
+        assert_eq!(log["task_id"], "foo");
+        assert!(log["target"].is_string());
+        assert!(log["timestamp"].is_string());
+    }
+
+    #[test]
+    fn should_write_json_logs_to_stdout() {
+        let writer = BufferWriter::default();
+        let subscriber = Registry::default().with(stdout_layer(writer.clone(), LogFormat::Json));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("download", task_id = "foo");
+            let _enter = span.enter();
+            info!("download started");
+            tracing::error!("download failed: connection reset\ncaused by: timeout");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let logs: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0]["level"], "INFO");
+        assert_eq!(logs[0]["message"], "download started");
+        assert_eq!(logs[0]["span"]["task_id"], "foo");
+        assert_eq!(logs[1]["level"], "ERROR");
+        assert!(logs[1]["message"].as_str().unwrap().contains('\n'));
+        for log in logs {
+            assert!(log["target"].is_string());
+            assert!(log["timestamp"].is_string());
+        }
+    }
+
+    #[test]
+    fn should_build_env_filter_with_log_filters() {
+        let writer = BufferWriter::default();
+        let mut warnings = Vec::new();
+        let filter = env_filter(
+            None,
+            Level::INFO,
+            &[
+                "h2=warn".to_string(),
+                "dragonfly_client_storage=debug".to_string(),
+                "h2=foo".to_string(),
+                "h2=error".to_string(),
+            ],
+            &mut warnings,
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("h2=foo"));
+
+        let subscriber = Registry::default()
+            .with(filter)
+            .with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            info!("info of dfdaemon");
+            tracing::debug!("debug of dfdaemon");
+            tracing::debug!(target: "dragonfly_client_storage", "debug of storage");
+            tracing::warn!(target: "h2", "warn of h2");
+            tracing::error!(target: "h2", "error of h2");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(content.contains("info of dfdaemon"));
+        assert!(!content.contains("debug of dfdaemon"));
+        assert!(content.contains("debug of storage"));
+        assert!(!content.contains("warn of h2"));
+        assert!(content.contains("error of h2"));
+    }
+
+    #[test]
+    fn should_build_env_filter_from_rust_log() {
+        let mut warnings = Vec::new();
+        let filter = env_filter(
+            Some("debug".to_string()),
+            Level::INFO,
+            &["h2=warn".to_string()],
+            &mut warnings,
+        );
+        assert_eq!(filter.to_string(), "debug");
+        assert!(warnings.is_empty());
+
+        // The invalid RUST_LOG falls back to the log level with a warning.
+        let filter = env_filter(Some("h2=foo".to_string()), Level::INFO, &[], &mut warnings);
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::INFO));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("RUST_LOG h2=foo"));
+    }
+
+    #[test]
+    fn should_build_resource_with_attributes() {
+        let trace_resource = resource("dfdaemon", HashMap::new());
+        assert_eq!(trace_resource.len(), 1);
+        assert_eq!(
+            trace_resource
+                .get("service.name".into())
+                .unwrap()
+                .to_string(),
+            "dfdaemon"
+        );
+
+        let trace_resource = resource(
+            "dfdaemon",
+            HashMap::from([
+                ("cluster".to_string(), "foo".to_string()),
+                ("service.name".to_string(), "bar".to_string()),
+            ]),
+        );
+        assert_eq!(trace_resource.len(), 2);
+        assert_eq!(
+            trace_resource.get("cluster".into()).unwrap().to_string(),
+            "foo"
+        );
+        assert_eq!(
+            trace_resource
+                .get("service.name".into())
+                .unwrap()
+                .to_string(),
+            "bar"
+        );
+    }
+
+    #[test]
+    fn should_export_spans_to_otlp_endpoint_of_env() {
+        // The jaeger agent is used without the otlp endpoint and the protocol.
+        assert_eq!(span_exporter(None, None, HashMap::new()), None);
+        assert_eq!(
+            span_exporter(Some("127.0.0.1:6831".to_string()), None, HashMap::new()),
+            Some(SpanExporter::Jaeger {
+                addr: "127.0.0.1:6831".to_string()
+            })
+        );
+
+        // The metrics use the default endpoint with a warning without the grpc span exporter.
+        let mut warnings = Vec::new();
+        assert_eq!(
+            metrics_exporter(None, None, &mut warnings),
+            (DEFAULT_OTLP_METRICS_ENDPOINT.to_string(), HashMap::new())
+        );
+        assert_eq!(warnings.len(), 1);
+
+        let http_exporter = SpanExporter::Otlp {
+            protocol: TracingProtocol::Http,
+            endpoint: "http://collector:4318/v1/traces".to_string(),
+            headers: HashMap::new(),
+        };
+        let mut warnings = Vec::new();
+        assert_eq!(
+            metrics_exporter(Some(&http_exporter), None, &mut warnings),
+            (DEFAULT_OTLP_METRICS_ENDPOINT.to_string(), HashMap::new())
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("http://collector:4318/v1/traces"));
+
+        // The metrics endpoint of the tracing is used with the http span exporter.
+        let mut warnings = Vec::new();
+        assert_eq!(
+            metrics_exporter(
+                Some(&http_exporter),
+                Some("collector:4317".to_string()),
+                &mut warnings
+            ),
+            ("http://collector:4317".to_string(), HashMap::new())
+        );
+        assert!(warnings.is_empty());
+
+        // The metrics share the endpoint and the headers of the grpc span exporter.
+        let grpc_exporter = SpanExporter::Otlp {
+            protocol: TracingProtocol::Grpc,
+            endpoint: "https://collector:4317".to_string(),
+            headers: HashMap::from([("tenant".to_string(), "foo".to_string())]),
+        };
+        assert_eq!(
+            metrics_exporter(Some(&grpc_exporter), None, &mut warnings),
+            (
+                "https://collector:4317".to_string(),
+                HashMap::from([("tenant".to_string(), "foo".to_string())])
+            )
+        );
+        assert!(warnings.is_empty());
+
+        // The endpoint of the metrics signal takes precedence.
+        std::env::set_var(OTEL_EXPORTER_OTLP_METRICS_ENDPOINT, "http://metrics:4317");
+        assert_eq!(
+            metrics_exporter(
+                Some(&grpc_exporter),
+                Some("collector:4317".to_string()),
+                &mut warnings
+            )
+            .0,
+            "http://metrics:4317".to_string()
+        );
+        std::env::remove_var(OTEL_EXPORTER_OTLP_METRICS_ENDPOINT);
+
+        // The otlp collector of the address is used with the protocol.
+        assert_eq!(
+            span_exporter(
+                Some("collector:4317".to_string()),
+                Some(TracingProtocol::Grpc),
+                HashMap::from([("authorization".to_string(), "Bearer foo".to_string())]),
+            ),
+            Some(SpanExporter::Otlp {
+                protocol: TracingProtocol::Grpc,
+                endpoint: "http://collector:4317".to_string(),
+                headers: HashMap::from([("authorization".to_string(), "Bearer foo".to_string())]),
+            })
+        );
+        assert_eq!(
+            span_exporter(
+                Some("https://collector:4318".to_string()),
+                Some(TracingProtocol::Http),
+                HashMap::new(),
+            ),
+            Some(SpanExporter::Otlp {
+                protocol: TracingProtocol::Http,
+                endpoint: "https://collector:4318/v1/traces".to_string(),
+                headers: HashMap::new(),
+            })
+        );
+
+        // The otlp endpoint of the environment variables is used.
+        std::env::set_var(OTEL_EXPORTER_OTLP_ENDPOINT, "http://collector:4318/");
+        std::env::set_var(
+            OTEL_EXPORTER_OTLP_HEADERS,
+            "api-key=foo, tenant=b%20r,invalid",
+        );
+        let expected = Some(SpanExporter::Otlp {
+            protocol: TracingProtocol::Http,
+            endpoint: "http://collector:4318/v1/traces".to_string(),
+            headers: HashMap::from([
+                ("api-key".to_string(), "foo".to_string()),
+                ("tenant".to_string(), "b r".to_string()),
+            ]),
+        });
+        assert_eq!(span_exporter(None, None, HashMap::new()), expected);
+        assert_eq!(
+            span_exporter(Some("127.0.0.1:6831".to_string()), None, HashMap::new()),
+            expected
+        );
+
+        // The protocol of the environment variable takes precedence over the configured
+        // protocol, and the configured headers override the headers of the environment
+        // variable.
+        std::env::set_var(OTEL_EXPORTER_OTLP_PROTOCOL, "grpc");
+        assert_eq!(
+            span_exporter(
+                None,
+                Some(TracingProtocol::Http),
+                HashMap::from([("tenant".to_string(), "baz".to_string())]),
+            ),
+            Some(SpanExporter::Otlp {
+                protocol: TracingProtocol::Grpc,
+                endpoint: "http://collector:4318".to_string(),
+                headers: HashMap::from([
+                    ("api-key".to_string(), "foo".to_string()),
+                    ("tenant".to_string(), "baz".to_string()),
+                ]),
+            })
+        );
+
+        std::env::remove_var(OTEL_EXPORTER_OTLP_ENDPOINT);
+        std::env::remove_var(OTEL_EXPORTER_OTLP_HEADERS);
+        std::env::remove_var(OTEL_EXPORTER_OTLP_PROTOCOL);
+    }
+
+    #[test]
+    fn should_normalize_otlp_endpoint() {
+        assert_eq!(
+            otlp_endpoint("collector:4317", TracingProtocol::Grpc),
+            "http://collector:4317"
+        );
+        assert_eq!(
+            otlp_endpoint("https://collector:4317/", TracingProtocol::Grpc),
+            "https://collector:4317"
+        );
+        assert_eq!(
+            otlp_endpoint("collector:4318", TracingProtocol::Http),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            otlp_endpoint("HTTPS://collector:4318/otlp/", TracingProtocol::Http),
+            "HTTPS://collector:4318/otlp/v1/traces"
+        );
+    }
+
+    #[test]
+    fn should_convert_headers_to_otlp_metadata() {
+        let metadata = otlp_metadata(&HashMap::from([(
+            "Authorization".to_string(),
+            "Bearer foo".to_string(),
+        )]))
+        .unwrap();
+        assert_eq!(metadata.get("authorization").unwrap(), "Bearer foo");
+
+        assert!(otlp_metadata(&HashMap::from([(
+            "in valid".to_string(),
+            "foo".to_string()
+        )]))
+        .is_err());
+        assert!(otlp_metadata(&HashMap::from([("foo".to_string(), "b\nr".to_string())])).is_err());
+    }
+
+    // StubTracerProvider records the calls of the tracer provider handle.
+    struct StubTracerProvider {
+        calls: std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>,
+    }
+
+    // StubTracerProvider implements TracerProviderHandle.
+    impl TracerProviderHandle for StubTracerProvider {
+        fn force_flush(&self) {
+            self.calls.lock().unwrap().push("force_flush");
+        }
+
+        fn shutdown(&self) {
+            self.calls.lock().unwrap().push("shutdown");
+        }
+    }
+
+    #[test]
+    fn should_flush_and_shutdown_tracer_provider_on_drop() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let guard = TracingGuard {
+            meter_provider: None,
+            tracer_provider: Some(Box::new(StubTracerProvider {
+                calls: calls.clone(),
+            })),
+            _log_guards: Vec::new(),
+        };
+        assert!(calls.lock().unwrap().is_empty());
+
+        drop(guard);
+        assert_eq!(*calls.lock().unwrap(), vec!["force_flush", "shutdown"]);
+
+        // The guard without the tracer provider is dropped silently.
+        drop(TracingGuard {
+            meter_provider: None,
+            tracer_provider: None,
+            _log_guards: Vec::new(),
+        });
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_build_meter_provider_if_metrics_otlp_enabled() {
+        assert!(meter_provider(
+            false,
+            Tracing::default().metrics_interval,
+            DEFAULT_OTLP_METRICS_ENDPOINT.to_string(),
+            &HashMap::new(),
+            &TracingTLS::default(),
+            resource("dfdaemon", HashMap::new()),
+        )
+        .unwrap()
+        .is_none());
+
+        assert!(meter_provider(
+            true,
+            Duration::from_secs(1),
+            DEFAULT_OTLP_METRICS_ENDPOINT.to_string(),
+            &HashMap::from([("tenant".to_string(), "foo".to_string())]),
+            &TracingTLS::default(),
+            resource("dfdaemon", HashMap::new()),
+        )
+        .unwrap()
+        .is_some());
+
+        // The invalid tls fails to build the meter provider instead of panicking.
+        assert!(meter_provider(
+            true,
+            Tracing::default().metrics_interval,
+            "https://collector:4317".to_string(),
+            &HashMap::new(),
+            &TracingTLS {
+                ca_cert: Some(PathBuf::from("/nonexistent/ca.crt")),
+                ..Default::default()
+            },
+            resource("dfdaemon", HashMap::new()),
+        )
+        .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_build_tracer_by_protocol() {
+        use opentelemetry::trace::{Span as _, Tracer as _};
+
+        for protocol in [TracingProtocol::Grpc, TracingProtocol::Http] {
+            let tracer = tracer(
+                SpanExporter::Otlp {
+                    protocol,
+                    endpoint: otlp_endpoint("127.0.0.1:1", protocol),
+                    headers: HashMap::from([("tenant".to_string(), "foo".to_string())]),
+                },
+                &TracingTLS::default(),
+                opentelemetry::sdk::trace::config(),
+            )
+            .unwrap();
+            assert!(tracer.provider().is_some());
+
+            // The spans are sampled by the tracer of the provider.
+            let span = tracer.start("download");
+            assert!(span.span_context().is_valid());
+            assert!(span.span_context().is_sampled());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_not_build_tracer_with_invalid_tls() {
+        let tls = TracingTLS {
+            ca_cert: Some(PathBuf::from("/nonexistent/ca.crt")),
+            ..Default::default()
+        };
+
+        // The missing CA cert fails to build the exporter of the https endpoint.
+        assert!(tracer(
+            SpanExporter::Otlp {
+                protocol: TracingProtocol::Http,
+                endpoint: "https://collector:4318/v1/traces".to_string(),
+                headers: HashMap::new(),
+            },
+            &tls,
+            opentelemetry::sdk::trace::config(),
+        )
+        .is_err());
+
+        // The tls is ignored by the plaintext endpoint.
+        assert!(tracer(
+            SpanExporter::Otlp {
+                protocol: TracingProtocol::Http,
+                endpoint: "http://collector:4318/v1/traces".to_string(),
+                headers: HashMap::new(),
+            },
+            &tls,
+            opentelemetry::sdk::trace::config(),
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn should_build_otlp_tls_config_for_https_endpoint() {
+        // The plaintext endpoint uses the plaintext channel.
+        assert!(
+            otlp_tls_config("http://collector:4317", &TracingTLS::default())
+                .unwrap()
+                .is_none()
+        );
+
+        // The https endpoint selects the tls path with the system root certs.
+        assert!(
+            otlp_tls_config("https://collector:4317", &TracingTLS::default())
+                .unwrap()
+                .is_some()
+        );
+
+        // The missing CA cert fails, and the insecure skip verify is not supported.
+        let tls = TracingTLS {
+            ca_cert: Some(PathBuf::from("/nonexistent/ca.crt")),
+            ..Default::default()
+        };
+        assert!(otlp_tls_config("https://collector:4317", &tls).is_err());
+
+        let tls = TracingTLS {
+            insecure_skip_verify: true,
+            ..Default::default()
+        };
+        assert!(otlp_tls_config("https://collector:4317", &tls).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_handshake_with_collector_by_ca_cert() {
+        let dir = tempdir::TempDir::new("tracing").unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(dir.path().join("ca.crt"), cert.serialize_pem().unwrap()).unwrap();
+
+        // The stub collector accepts the tls connection with the self-signed cert.
+        let mut server_config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.serialize_der().unwrap().into()],
+                rustls_pki_types::PrivateKeyDer::Pkcs8(cert.serialize_private_key_der().into()),
+            )
+            .unwrap();
+        server_config.alpn_protocols = vec![b"h2".to_vec()];
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handshake = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            acceptor.accept(stream).await.is_ok()
+        });
+
+        let tracer = tracer(
+            SpanExporter::Otlp {
+                protocol: TracingProtocol::Grpc,
+                endpoint: format!("https://localhost:{}", port),
+                headers: HashMap::new(),
+            },
+            &TracingTLS {
+                ca_cert: Some(dir.path().join("ca.crt")),
+                ..Default::default()
+            },
+            opentelemetry::sdk::trace::config(),
+        )
+        .unwrap();
+
+        // The span is exported by flushing the tracer provider.
+        {
+            use opentelemetry::trace::{Span as _, Tracer as _};
+            tracer.start("download").end();
+        }
+        let provider = tracer.provider().unwrap();
+        tokio::task::spawn_blocking(move || {
+            provider.force_flush();
+        });
+
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(10), handshake)
+                .await
+                .unwrap()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn should_build_otlp_http_client_for_https_endpoint() {
+        // The plaintext endpoint uses the default http client.
+        assert!(
+            otlp_http_client("http://collector:4318/v1/traces", &TracingTLS::default())
+                .unwrap()
+                .is_none()
+        );
+
+        // The https endpoint selects the tls path with the system root certs.
+        assert!(
+            otlp_http_client("https://collector:4318/v1/traces", &TracingTLS::default())
+                .unwrap()
+                .is_some()
+        );
+
+        // The https endpoint selects the tls path with the CA cert and the client cert.
+        let dir = tempdir::TempDir::new("tracing").unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["collector".to_string()]).unwrap();
+        std::fs::write(dir.path().join("ca.crt"), cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(
+            dir.path().join("client.key"),
+            cert.serialize_private_key_pem(),
+        )
+        .unwrap();
+        let tls = TracingTLS {
+            ca_cert: Some(dir.path().join("ca.crt")),
+            cert: Some(dir.path().join("ca.crt")),
+            key: Some(dir.path().join("client.key")),
+            ..Default::default()
+        };
+        assert!(otlp_http_client("HTTPS://collector:4318/v1/traces", &tls)
+            .unwrap()
+            .is_some());
+
+        // The missing CA cert fails.
+        let tls = TracingTLS {
+            ca_cert: Some(dir.path().join("missing.crt")),
+            ..Default::default()
+        };
+        assert!(otlp_http_client("https://collector:4318/v1/traces", &tls).is_err());
+    }
+
+    #[test]
+    fn should_merge_resource_attributes_of_env() {
+        assert!(resource_attributes(None, HashMap::new()).is_empty());
+
+        // The configured attributes override the attributes of the environment variable.
+        let attributes = resource_attributes(
+            Some("deployment.environment=production,cluster=foo,=bar".to_string()),
+            HashMap::from([("cluster".to_string(), "baz".to_string())]),
+        );
+        assert_eq!(
+            attributes,
+            HashMap::from([
+                (
+                    "deployment.environment".to_string(),
+                    "production".to_string()
+                ),
+                ("cluster".to_string(), "baz".to_string()),
+            ])
+        );
+
+        // The invalid escapes are kept as is.
+        assert_eq!(percent_decode("100%25"), "100%");
+        assert_eq!(percent_decode("%zz%"), "%zz%");
+        assert_eq!(percent_decode("é%41"), "éA");
+    }
+
+    #[test]
+    fn should_build_sampler_by_ratio() {
+        assert!(sampler(0.0).is_none());
+        assert!(sampler(-1.0).is_none());
+        assert_eq!(
+            format!("{:?}", sampler(1.0).unwrap()),
+            format!("{:?}", Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+        );
+        assert_eq!(
+            format!("{:?}", sampler(0.25).unwrap()),
+            format!(
+                "{:?}",
+                Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(0.25)))
+            )
+        );
+    }
+
+    #[test]
+    fn should_add_syslog_layer_only_with_address() {
+        let mut guards = Vec::new();
+        let mut warnings = Vec::new();
+        assert!(syslog_layer::<Registry>("dfdaemon", None, &mut guards, &mut warnings).is_none());
+        assert!(warnings.is_empty());
+
+        #[cfg(feature = "syslog")]
+        {
+            assert!(syslog_layer::<Registry>(
+                "dfdaemon",
+                Some("udp://127.0.0.1:514".to_string()),
+                &mut guards,
+                &mut warnings
+            )
+            .is_some());
+            assert_eq!(guards.len(), 1);
+            assert!(warnings.is_empty());
+        }
+
+        #[cfg(not(feature = "syslog"))]
+        {
+            assert!(syslog_layer::<Registry>(
+                "dfdaemon",
+                Some("udp://127.0.0.1:514".to_string()),
+                &mut guards,
+                &mut warnings
+            )
+            .is_none());
+            assert_eq!(warnings.len(), 1);
+        }
+    }
+
+    #[cfg(feature = "syslog")]
+    #[test]
+    fn should_skip_syslog_layer_if_syslog_is_unreachable() {
+        let dir = tempdir::TempDir::new("tracing").unwrap();
+        let addr = dir.path().join("missing.sock").display().to_string();
+        let mut guards = Vec::new();
+        let mut warnings = Vec::new();
+        assert!(
+            syslog_layer::<Registry>("dfdaemon", Some(addr), &mut guards, &mut warnings).is_none()
+        );
+        assert!(guards.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("missing.sock"));
+    }
+
+    #[cfg(feature = "syslog")]
+    #[test]
+    fn should_send_logs_to_syslog() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let addr = format!("udp://{}", server.local_addr().unwrap());
+
+        let mut guards = Vec::new();
+        let mut warnings = Vec::new();
+        let subscriber = Registry::default().with(syslog_layer(
+            "dfdaemon",
+            Some(addr),
+            &mut guards,
+            &mut warnings,
+        ));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(task_id = "foo", "download failed");
+        });
+
+        // The logs are sent by the worker thread of the non-blocking writer.
+        let mut buf = [0; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        let message = String::from_utf8_lossy(&buf[..n]);
+
+        // The facility is daemon(3) and the severity is error(3), so the priority is 27.
+        assert!(message.starts_with("<27>"), "message: {}", message);
+        assert!(message.contains("dfdaemon"));
+        assert!(message.contains("download failed task_id=\"foo\""));
+    }
+
+    #[test]
+    fn should_parse_log_rotation() {
+        assert_eq!(
+            LogRotation::from_str("hourly").unwrap(),
+            LogRotation::Hourly
+        );
+        assert_eq!(LogRotation::from_str("Daily").unwrap(), LogRotation::Daily);
+        assert_eq!(
+            LogRotation::from_str("1024").unwrap(),
+            LogRotation::MaxSize(1024)
+        );
+        assert_eq!(
+            LogRotation::from_str("512KB").unwrap(),
+            LogRotation::MaxSize(512 * 1024)
+        );
+        assert_eq!(
+            LogRotation::from_str("100mb").unwrap(),
+            LogRotation::MaxSize(100 * 1024 * 1024)
+        );
+        assert_eq!(
+            LogRotation::from_str("1G").unwrap(),
+            LogRotation::MaxSize(1024 * 1024 * 1024)
+        );
+        assert!(LogRotation::from_str("0").is_err());
+        assert!(LogRotation::from_str("weekly").is_err());
+        assert!(LogRotation::from_str("10TB").is_err());
+        assert!(LogRotation::from_str("18446744073709551615G").is_err());
+    }
+
+    #[test]
+    fn should_roll_by_max_size() {
+        use std::io::Write;
+
+        let dir = tempdir::TempDir::new("tracing").unwrap();
+        let path = dir.path().join("dfdaemon.log");
+        let mut appender =
+            rolling_file_appender(path.clone(), LogRotation::MaxSize(100), 2, false).unwrap();
+
+        // The log file is not rolled before the size exceeds the threshold.
+        appender.write_all(&[b'a'; 60]).unwrap();
+        appender.flush().unwrap();
+        assert!(!dir.path().join("dfdaemon.log.1").exists());
+
+        // The log file is rolled after the size exceeds the threshold.
+        appender.write_all(&[b'b'; 60]).unwrap();
+        appender.write_all(&[b'c'; 10]).unwrap();
+        appender.flush().unwrap();
+        assert!(dir.path().join("dfdaemon.log.1").exists());
+        assert_eq!(fs::read(&path).unwrap(), vec![b'c'; 10]);
+
+        // The max files are kept.
+        for _ in 0..5 {
+            appender.write_all(&[b'd'; 120]).unwrap();
+        }
+        appender.flush().unwrap();
+        assert!(dir.path().join("dfdaemon.log.2").exists());
+        assert!(!dir.path().join("dfdaemon.log.3").exists());
+    }
+
+    #[test]
+    fn should_parse_log_format() {
+        use clap::ValueEnum;
+
+        assert_eq!(LogFormat::from_str("json", false).unwrap(), LogFormat::Json);
+        assert_eq!(
+            LogFormat::from_str("compact", false).unwrap(),
+            LogFormat::Compact
+        );
+        assert_eq!(
+            LogFormat::from_str("text", false).unwrap(),
+            LogFormat::Compact
+        );
+        assert!(LogFormat::from_str("xml", false).is_err());
+    }
+
+    #[test]
+    fn should_write_compact_logs() {
+        let writer = BufferWriter::default();
+        let subscriber = Registry::default().with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            info!("download finished");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(content.contains("INFO"));
+        assert!(content.contains("download finished"));
+        assert!(serde_json::from_str::<serde_json::Value>(content.trim()).is_err());
+    }
+
+    #[test]
+    fn should_set_log_filter() {
+        let writer = BufferWriter::default();
+        let (env_filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let handle = LogLevelHandle::new(handle, Some(Level::INFO), vec![]);
+        let subscriber = Registry::default()
+            .with(env_filter)
+            .with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("debug before reload");
+            set_log_filter(&handle, "debug").unwrap();
+            tracing::debug!("debug after reload");
+            set_log_filter(&handle, "warn,dragonfly_client=error").unwrap();
+            info!("info after reload");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(!content.contains("debug before reload"));
+        assert!(content.contains("debug after reload"));
+        assert!(!content.contains("info after reload"));
+    }
+
+    #[test]
+    fn should_set_log_level() {
+        let writer = BufferWriter::default();
+        let (env_filter, handle) = reload::Layer::new(EnvFilter::new("warn"));
+        let handle = LogLevelHandle::new(handle, Some(Level::WARN), vec![]);
+        let subscriber = Registry::default()
+            .with(env_filter)
+            .with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            info!("info before reload");
+            set_log_level(&handle, Level::INFO).unwrap();
+            info!("info after reload");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(!content.contains("info before reload"));
+        assert!(content.contains("info after reload"));
+    }
+
+    #[test]
+    fn should_not_set_invalid_log_filter() {
+        let (_env_filter, handle) =
+            reload::Layer::<EnvFilter, Registry>::new(EnvFilter::new("info"));
+        let handle = LogLevelHandle::new(handle, Some(Level::INFO), vec![]);
+        assert!(set_log_filter(&handle, "").is_err());
+        assert!(set_log_filter(&handle, "info,dragonfly_client=foo").is_err());
+        assert_eq!(
+            handle
+                .handle
+                .with_current(|env_filter| env_filter.max_level_hint())
+                .unwrap(),
+            Some(LevelFilter::INFO)
+        );
+    }
+
+    #[test]
+    fn should_toggle_debug_log_level() {
+        let writer = BufferWriter::default();
+        let (env_filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let handle = LogLevelHandle::new(handle, Some(Level::INFO), vec![]);
+        let subscriber = Registry::default()
+            .with(env_filter)
+            .with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(
+                toggle_debug_log_level(&handle, Level::INFO).unwrap(),
+                Level::DEBUG
+            );
+            tracing::debug!("debug enabled");
+            assert_eq!(
+                toggle_debug_log_level(&handle, Level::INFO).unwrap(),
+                Level::INFO
+            );
+            tracing::debug!("debug disabled");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(content.contains("debug enabled"));
+        assert!(!content.contains("debug disabled"));
+    }
+
+    #[test]
+    fn should_keep_log_filters_when_log_level_changed() {
+        let writer = BufferWriter::default();
+        let log_filters = vec![
+            "dragonfly_client_storage=debug".to_string(),
+            "h2=error".to_string(),
+        ];
+        let (env_filter, handle) =
+            reload::Layer::new(env_filter(None, Level::INFO, &log_filters, &mut Vec::new()));
+        let handle = LogLevelHandle::new(handle, Some(Level::INFO), log_filters);
+        let subscriber = Registry::default()
+            .with(env_filter)
+            .with(file_layer(writer.clone(), LogFormat::Compact));
+        tracing::subscriber::with_default(subscriber, || {
+            // The DEBUG of the storage does not make the level toggled back.
+            assert_eq!(
+                toggle_debug_log_level(&handle, Level::INFO).unwrap(),
+                Level::DEBUG
+            );
+            tracing::debug!("debug of dfdaemon");
+            tracing::warn!(target: "h2", "warn of h2 with debug");
+
+            set_log_level(&handle, Level::WARN).unwrap();
+            tracing::debug!(target: "dragonfly_client_storage", "debug of storage with warn");
+
+            set_log_filter(&handle, "info").unwrap();
+            tracing::warn!(target: "h2", "warn of h2 with info");
+        });
+
+        let content = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert!(content.contains("debug of dfdaemon"));
+        assert!(!content.contains("warn of h2 with debug"));
+        assert!(content.contains("debug of storage with warn"));
+        assert!(!content.contains("warn of h2 with info"));
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use chrono::{DateTime, Local};
+use flate2::{write::GzEncoder, Compression};
+use rolling_file::{RollingCondition, RollingConditionBasic, RollingFileAppender};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+// ManualRollover is the rolling condition that never rolls over by itself, the
+// rollover is triggered by the LogFileAppender.
+#[derive(Debug)]
+struct ManualRollover;
+
+// ManualRollover implements the RollingCondition trait.
+impl RollingCondition for ManualRollover {
+    // should_rollover always returns false.
+    fn should_rollover(&mut self, _: &DateTime<Local>, _: u64) -> bool {
+        false
+    }
+}
+
+// LogFileAppender is the rolling file appender of the log, the rotated log files are
+// named with the Debian-style naming scheme, e.g. dfdaemon.log.1, and they are compressed
+// to dfdaemon.log.1.gz if the compression is enabled. The live log file is never compressed.
+#[derive(Debug)]
+pub struct LogFileAppender {
+    // path is the path of the live log file.
+    path: PathBuf,
+
+    // condition is the rolling condition of the log file.
+    condition: RollingConditionBasic,
+
+    // max_files is the max number of the rotated log files.
+    max_files: usize,
+
+    // compression indicates whether to compress the rotated log files.
+    compression: bool,
+
+    // current_filesize is the size of the live log file.
+    current_filesize: u64,
+
+    // appender is the rolling file appender writing the live log file.
+    appender: RollingFileAppender<ManualRollover>,
+}
+
+// LogFileAppender implements the rolling file appender of the log.
+impl LogFileAppender {
+    // new creates a new LogFileAppender, the parent directory of the path must exist.
+    pub fn new(
+        path: PathBuf,
+        condition: RollingConditionBasic,
+        max_files: usize,
+        compression: bool,
+    ) -> io::Result<Self> {
+        let appender = RollingFileAppender::new(&path, ManualRollover, max_files)?;
+        let current_filesize = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+
+        Ok(Self {
+            path,
+            condition,
+            max_files: max_files.max(1),
+            compression,
+            current_filesize,
+            appender,
+        })
+    }
+
+    // rollover rotates the log files, and compresses the log file rotated from the
+    // live log file if the compression is enabled.
+    fn rollover(&mut self) -> io::Result<()> {
+        if !self.compression {
+            self.appender.rollover()?;
+            self.current_filesize = 0;
+            return Ok(());
+        }
+
+        // Rotate the compressed log files to make room for the new one.
+        let _ = fs::remove_file(self.compressed_filename_for(self.max_files));
+        for i in (1..self.max_files).rev() {
+            if let Err(err) = fs::rename(
+                self.compressed_filename_for(i),
+                self.compressed_filename_for(i + 1),
+            ) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(err);
+                }
+            }
+        }
+
+        self.appender.rollover()?;
+        self.current_filesize = 0;
+
+        // Compress the rotated log file and remove the plain one.
+        let rotated = self.filename_for(1);
+        compress(&rotated, &self.compressed_filename_for(1))?;
+        fs::remove_file(rotated)
+    }
+
+    // filename_for returns the filename of the nth rotated log file.
+    fn filename_for(&self, n: usize) -> PathBuf {
+        let mut filename = OsString::from(self.path.as_os_str());
+        filename.push(format!(".{}", n));
+        filename.into()
+    }
+
+    // compressed_filename_for returns the filename of the nth compressed log file.
+    fn compressed_filename_for(&self, n: usize) -> PathBuf {
+        let mut filename = OsString::from(self.path.as_os_str());
+        filename.push(format!(".{}.gz", n));
+        filename.into()
+    }
+}
+
+// LogFileAppender implements the Write trait.
+impl Write for LogFileAppender {
+    // write writes the buffer to the live log file, and rotates the log files
+    // if the rolling condition is met.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Local::now();
+        if self.condition.should_rollover(&now, self.current_filesize) {
+            if let Err(err) = self.rollover() {
+                // Continue writing to the live log file if the rotation fails, it is
+                // better than losing the logs. The logger can't be used here, so the
+                // error is written to the stderr.
+                eprintln!(
+                    "WARNING: failed to rotate log file {}: {}",
+                    self.path.display(),
+                    err
+                );
+            }
+        }
+
+        let n = self.appender.write(buf)?;
+        self.current_filesize += n as u64;
+        Ok(n)
+    }
+
+    // flush flushes the live log file.
+    fn flush(&mut self) -> io::Result<()> {
+        self.appender.flush()
+    }
+}
+
+// compress compresses the file to the destination with gzip.
+fn compress(from: &PathBuf, to: &PathBuf) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(from)?);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(to)?), Compression::default());
+    io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn should_compress_rotated_log_files() {
+        let dir = tempdir::TempDir::new("rolling").unwrap();
+        let path = dir.path().join("dfdaemon.log");
+        let mut appender = LogFileAppender::new(
+            path.clone(),
+            RollingConditionBasic::new().max_size(100),
+            2,
+            true,
+        )
+        .unwrap();
+
+        // The rotated log file is compressed, and the live log file is not.
+        appender.write_all(&[b'a'; 120]).unwrap();
+        appender.write_all(&[b'b'; 10]).unwrap();
+        appender.flush().unwrap();
+        assert!(!dir.path().join("dfdaemon.log.1").exists());
+        assert_eq!(fs::read(&path).unwrap(), vec![b'b'; 10]);
+
+        let mut content = Vec::new();
+        GzDecoder::new(File::open(dir.path().join("dfdaemon.log.1.gz")).unwrap())
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, vec![b'a'; 120]);
+
+        // The max compressed files are kept.
+        for _ in 0..5 {
+            appender.write_all(&[b'c'; 120]).unwrap();
+        }
+        appender.flush().unwrap();
+        assert!(dir.path().join("dfdaemon.log.2.gz").exists());
+        assert!(!dir.path().join("dfdaemon.log.3.gz").exists());
+        assert!(!dir.path().join("dfdaemon.log.1").exists());
+        assert!(!dir.path().join("dfdaemon.log.2").exists());
+    }
+}
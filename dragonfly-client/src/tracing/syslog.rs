@@ -0,0 +1,241 @@
+/*
+ *     Copyright 2023 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use syslog::{Facility, Formatter3164, LogFormat, LoggerBackend, Severity};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::layer::{Context, Layer};
+
+// SyslogLayer is the layer sending the logs to the syslog, the logs are formatted on the
+// calling thread and sent to the syslog by the non-blocking worker thread, then the tokio
+// workers are not blocked by the syslog.
+pub struct SyslogLayer {
+    // formatter formats the logs in the rfc 3164 format.
+    formatter: Formatter3164,
+
+    // writer sends the formatted logs to the worker thread of the syslog writer.
+    writer: NonBlocking,
+}
+
+// SyslogLayer implements the syslog layer.
+impl SyslogLayer {
+    // new creates a new syslog layer, the addr is the path of the local unix
+    // socket, e.g. /dev/log, or the remote address with the protocol, e.g.
+    // udp://127.0.0.1:514 and tcp://127.0.0.1:601, udp is the default protocol.
+    // The returned guard flushes the buffered logs when it is dropped.
+    pub fn new(name: &str, addr: &str) -> syslog::Result<(Self, WorkerGuard)> {
+        let formatter = Formatter3164 {
+            facility: Facility::LOG_DAEMON,
+            hostname: None,
+            process: name.to_string(),
+            pid: std::process::id(),
+        };
+
+        let (writer, guard) = tracing_appender::non_blocking(SyslogWriter::new(addr)?);
+        Ok((Self { formatter, writer }, guard))
+    }
+}
+
+// SyslogLayer implements the Layer trait.
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    // on_event sends the event to the syslog with the severity mapped from the level.
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let severity = match *event.metadata().level() {
+            Level::ERROR => Severity::LOG_ERR,
+            Level::WARN => Severity::LOG_WARNING,
+            Level::INFO => Severity::LOG_INFO,
+            Level::DEBUG | Level::TRACE => Severity::LOG_DEBUG,
+        };
+
+        // The log is formatted into one buffer, then it is sent to the syslog as one
+        // message by the worker thread. The non-blocking writer drops the log if its
+        // buffer is full, and fails only if the worker thread has exited.
+        let mut message = Vec::new();
+        if self
+            .formatter
+            .format(&mut message, severity, visitor.message)
+            .is_ok()
+        {
+            if let Err(err) = self.writer.clone().write_all(&message) {
+                eprintln!("failed to send log to syslog writer: {}", err);
+            }
+        }
+    }
+}
+
+// SyslogWriter writes the formatted logs to the syslog in the worker thread, it reconnects
+// to the syslog if the write fails, e.g. the syslog daemon is restarted.
+struct SyslogWriter {
+    // addr is the address of the syslog.
+    addr: String,
+
+    // backend is the connection to the syslog, it is none if the reconnection fails.
+    backend: Option<LoggerBackend>,
+}
+
+// SyslogWriter implements the syslog writer.
+impl SyslogWriter {
+    // new creates a new syslog writer, it fails if the syslog is unreachable.
+    fn new(addr: &str) -> syslog::Result<Self> {
+        Ok(Self {
+            addr: addr.to_string(),
+            backend: Some(connect(addr)?),
+        })
+    }
+
+    // send sends the message by the connection, the connection is created if it is
+    // closed by the last failure.
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        let backend = match self.backend {
+            Some(ref mut backend) => backend,
+            None => self.backend.insert(
+                connect(&self.addr)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?,
+            ),
+        };
+
+        let result = backend.write_all(message).and_then(|_| backend.flush());
+        if result.is_err() {
+            self.backend = None;
+        }
+
+        result
+    }
+}
+
+// SyslogWriter implements the Write trait, each write is a formatted log.
+impl Write for SyslogWriter {
+    fn write(&mut self, message: &[u8]) -> io::Result<usize> {
+        // If the connection is broken, the log is sent again by a new connection. If the
+        // reconnection fails, the log is dropped and the next log reconnects again, the
+        // failure is reported once until the syslog is reachable.
+        let connected = self.backend.is_some();
+        self.send(message).or_else(|err| {
+            if !connected {
+                return Err(err);
+            }
+
+            eprintln!(
+                "failed to send log to syslog {}, reconnecting: {}",
+                self.addr, err
+            );
+            self.send(message)
+        })?;
+
+        Ok(message.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// connect connects to the syslog of the address, the address is the path of the
+// local unix socket, or the remote address with the tcp or udp protocol.
+fn connect(addr: &str) -> syslog::Result<LoggerBackend> {
+    // The formatter of the logger is unused, the logs are formatted by the layer.
+    let formatter = Formatter3164 {
+        facility: Facility::LOG_DAEMON,
+        hostname: None,
+        process: String::new(),
+        pid: 0,
+    };
+    let logger = if addr.starts_with('/') {
+        syslog::unix_custom(formatter, addr)?
+    } else if let Some(addr) = addr.strip_prefix("tcp://") {
+        syslog::tcp(formatter, addr)?
+    } else {
+        let addr = addr.strip_prefix("udp://").unwrap_or(addr);
+        syslog::udp(formatter, "0.0.0.0:0", addr)?
+    };
+
+    Ok(logger.backend)
+}
+
+// MessageVisitor formats the message and fields of the event.
+#[derive(Default)]
+struct MessageVisitor {
+    // message is the formatted message of the event.
+    message: String,
+}
+
+// MessageVisitor implements the Visit trait.
+impl Visit for MessageVisitor {
+    // record_debug records the field, the message field is in front of the others.
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    #[test]
+    fn should_reconnect_to_syslog() {
+        let dir = tempdir::TempDir::new("syslog").unwrap();
+        let path = dir.path().join("syslog.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let mut writer = SyslogWriter::new(&path.display().to_string()).unwrap();
+        writer.write_all(b"foo").unwrap();
+
+        let mut buf = [0; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"foo");
+
+        // The syslog is restarted, the log is sent by the new connection.
+        drop(server);
+        std::fs::remove_file(&path).unwrap();
+        let server = UnixDatagram::bind(&path).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        writer.write_all(b"bar").unwrap();
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"bar");
+
+        // The syslog is unreachable, the log is dropped and the next log reconnects.
+        drop(server);
+        std::fs::remove_file(&path).unwrap();
+        assert!(writer.write_all(b"baz").is_err());
+        assert!(writer.backend.is_none());
+
+        let server = UnixDatagram::bind(&path).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        writer.write_all(b"qux").unwrap();
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"qux");
+    }
+}